@@ -0,0 +1,76 @@
+//! Benchmark: per-frame cost of a fresh `RtFilter` vs one reused across frames.
+//!
+//! Run with: cargo run --release --example rt_filter_reuse [width] [height] [frames]
+//! Defaults to 1920x1080, 30 frames. Requires OIDN built and OIDN_DIR set (or pkg-config).
+
+use oidn_wgpu::{OidnDevice, Quality, RtFilter};
+use std::time::{Duration, Instant};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1).map(|a| a.parse::<u32>());
+    let width = args.next().transpose()?.unwrap_or(1920);
+    let height = args.next().transpose()?.unwrap_or(1080);
+    let frames = args.next().transpose()?.unwrap_or(30).max(1);
+    let n = (width * height * 3) as usize;
+
+    let color: Vec<f32> = (0..n).map(|i| 0.5 + 0.1 * rand_simple(i)).collect();
+    let albedo = vec![0.8f32; n];
+    let normal: Vec<f32> = (0..n).map(|i| if i % 3 == 2 { 1.0 } else { 0.0 }).collect();
+    let mut output = vec![0.0f32; n];
+
+    let device = OidnDevice::new()?;
+
+    // Fresh filter every frame: allocates buffers, sets images and commits each time.
+    let fresh = time_frames(frames, || {
+        let mut filter = RtFilter::new(&device)?;
+        filter
+            .set_dimensions(width, height)
+            .set_quality(Quality::Fast);
+        filter.execute_with_aux(Some(&color), &mut output, Some(&albedo), Some(&normal))
+    })?;
+
+    // One filter for all frames: buffers and the committed state are reused.
+    let mut filter = RtFilter::new(&device)?;
+    filter
+        .set_dimensions(width, height)
+        .set_quality(Quality::Fast);
+    let reused = time_frames(frames, || {
+        filter.execute_with_aux(Some(&color), &mut output, Some(&albedo), Some(&normal))
+    })?;
+
+    println!(
+        "{}x{}, {} frames (color + albedo + normal):",
+        width, height, frames
+    );
+    println!(
+        "  fresh filter per frame: {:8.2} ms/frame",
+        ms(fresh / frames)
+    );
+    println!(
+        "  reused filter:          {:8.2} ms/frame",
+        ms(reused / frames)
+    );
+    Ok(())
+}
+
+fn time_frames(
+    frames: u32,
+    mut frame: impl FnMut() -> Result<(), oidn_wgpu::Error>,
+) -> Result<Duration, oidn_wgpu::Error> {
+    // Warm-up frame (first commit builds the network).
+    frame()?;
+    let start = Instant::now();
+    for _ in 0..frames {
+        frame()?;
+    }
+    Ok(start.elapsed())
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn rand_simple(seed: usize) -> f32 {
+    let x = (seed as u64).wrapping_mul(0x9e3779b97f4a7c15);
+    ((x >> 32) as f32) / (u32::MAX as f32)
+}
@@ -33,11 +33,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let pixel_count = (w * h) as usize;
         let mut cpu_pixels = vec![0u8; pixel_count * (bpp as usize)];
         let u16_view: &mut [u16] = bytemuck::cast_slice_mut(&mut cpu_pixels);
-        for i in 0..(pixel_count * 4) {
+        for (i, px) in u16_view.iter_mut().enumerate() {
             let x = (i % (w as usize * 4)) as f32 / (w as f32 * 4.0);
             let y = (i / (w as usize * 4)) as f32 / h as f32;
             let v = (x * y).sin() * 0.5 + 0.5;
-            u16_view[i] = half::f16::from_f32(v).to_bits();
+            *px = half::f16::from_f32(v).to_bits();
         }
         for i in (0..(pixel_count * 4)).step_by(4) {
             u16_view[i + 3] = half::f16::from_f32(1.0).to_bits();
@@ -123,12 +123,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             &input_tex,
             &output_tex,
             format,
-            &DenoiseOptions {
-                quality: Quality::Balanced,
-                hdr: true,
-                srgb: false,
-                input_scale: None,
-            },
+            &DenoiseOptions::default()
+                .with_quality(Quality::Balanced)
+                .with_hdr(true),
         )?;
 
         println!("Denoised {}x{} wgpu texture successfully.", w, h);
@@ -0,0 +1,199 @@
+//! Match an OIDN physical device to a [`wgpu::Adapter`], so denoising runs on the same GPU as rendering.
+//!
+//! See [`OidnDevice::for_wgpu_adapter`] and [`AdapterMatchOptions`].
+
+use crate::device::{physical_devices, OidnDevice, OidnDeviceType, PciAddress, PhysicalDeviceInfo};
+use crate::sys;
+use crate::Error;
+
+const VENDOR_NVIDIA: u32 = 0x10de;
+const VENDOR_AMD: u32 = 0x1002;
+const VENDOR_INTEL: u32 = 0x8086;
+const VENDOR_APPLE: u32 = 0x106b;
+
+/// Adapter names of common software rasterizers, matched case-insensitively.
+const SOFTWARE_ADAPTER_NAMES: &[&str] = &[
+    "llvmpipe",
+    "lavapipe",
+    "swiftshader",
+    "microsoft basic render",
+];
+
+/// Device to create when no physical device matches the adapter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AdapterFallback {
+    /// Use the CPU device (always available, deterministic).
+    #[default]
+    Cpu,
+    /// Use OIDN's default device (auto-selects the best available backend).
+    Default,
+    /// Return [`Error::DeviceCreationFailed`].
+    Error,
+}
+
+/// Strategy that selected the device in [`OidnDevice::for_wgpu_adapter`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdapterMatch {
+    /// The adapter is a software rasterizer (e.g. llvmpipe/lavapipe); the CPU device was used.
+    Software,
+    /// Matched by the UUID in [`AdapterMatchOptions::uuid`].
+    Uuid,
+    /// Matched by the LUID in [`AdapterMatchOptions::luid`].
+    Luid,
+    /// Matched by the PCI address in [`AdapterMatchOptions::pci_address`].
+    PciAddress,
+    /// Matched by adapter name (exactly one physical device with the same name).
+    Name,
+    /// Matched by PCI vendor ID (exactly one physical device on the vendor's backend).
+    Vendor,
+    /// No physical device matched; the fallback device was used.
+    Fallback(AdapterFallback),
+}
+
+/// Options for [`OidnDevice::for_wgpu_adapter_with`].
+///
+/// `wgpu::AdapterInfo` does not carry a UUID, LUID or PCI address. When the application can obtain
+/// them from the backend (e.g. Vulkan `VkPhysicalDeviceIDProperties` through wgpu-hal), pass them
+/// here; they are tried before name and vendor matching.
+#[derive(Clone, Debug, Default)]
+pub struct AdapterMatchOptions {
+    /// Backend-reported device UUID.
+    pub uuid: Option<[u8; sys::OIDN_UUID_SIZE]>,
+    /// Backend-reported device LUID (Windows).
+    pub luid: Option<[u8; sys::OIDN_LUID_SIZE]>,
+    /// PCI address of the adapter.
+    pub pci_address: Option<PciAddress>,
+    /// What to create when nothing matches. Default: [`AdapterFallback::Cpu`].
+    pub fallback: AdapterFallback,
+}
+
+/// Result of matching an adapter against the physical device list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AdapterTarget {
+    Cpu,
+    Physical(i32),
+}
+
+/// Whether the adapter is a software rasterizer.
+fn is_software_adapter(info: &wgpu::AdapterInfo) -> bool {
+    if info.device_type == wgpu::DeviceType::Cpu {
+        return true;
+    }
+    let name = info.name.to_lowercase();
+    SOFTWARE_ADAPTER_NAMES.iter().any(|s| name.contains(s))
+}
+
+/// OIDN backend that drives GPUs of the adapter's vendor.
+fn vendor_device_type(info: &wgpu::AdapterInfo) -> Option<OidnDeviceType> {
+    if info.backend == wgpu::Backend::Metal {
+        return Some(OidnDeviceType::Metal);
+    }
+    match info.vendor {
+        VENDOR_NVIDIA => Some(OidnDeviceType::Cuda),
+        VENDOR_AMD => Some(OidnDeviceType::Hip),
+        VENDOR_INTEL => Some(OidnDeviceType::Sycl),
+        VENDOR_APPLE => Some(OidnDeviceType::Metal),
+        _ => None,
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Returns the single GPU device matching `pred`, or `None` if there are zero or several.
+fn unique(
+    devices: &[PhysicalDeviceInfo],
+    pred: impl Fn(&PhysicalDeviceInfo) -> bool,
+) -> Option<&PhysicalDeviceInfo> {
+    let mut it = devices
+        .iter()
+        .filter(|d| d.device_type != Some(OidnDeviceType::Cpu) && pred(d));
+    let first = it.next()?;
+    if it.next().is_some() {
+        return None;
+    }
+    Some(first)
+}
+
+/// Matches `info` against `devices`. Returns `None` if nothing matched (caller applies the fallback).
+pub(crate) fn match_adapter(
+    info: &wgpu::AdapterInfo,
+    options: &AdapterMatchOptions,
+    devices: &[PhysicalDeviceInfo],
+) -> Option<(AdapterTarget, AdapterMatch)> {
+    if is_software_adapter(info) {
+        return Some((AdapterTarget::Cpu, AdapterMatch::Software));
+    }
+    let found = |d: &PhysicalDeviceInfo, m: AdapterMatch| Some((AdapterTarget::Physical(d.id), m));
+
+    if let Some(uuid) = options.uuid {
+        if let Some(d) = unique(devices, |d| d.uuid == Some(uuid)) {
+            return found(d, AdapterMatch::Uuid);
+        }
+    }
+    if let Some(luid) = options.luid {
+        if let Some(d) = unique(devices, |d| d.luid == Some(luid)) {
+            return found(d, AdapterMatch::Luid);
+        }
+    }
+    if let Some(pci) = options.pci_address {
+        if let Some(d) = unique(devices, |d| d.pci_address == Some(pci)) {
+            return found(d, AdapterMatch::PciAddress);
+        }
+    }
+    let name = normalize_name(&info.name);
+    if !name.is_empty() {
+        if let Some(d) = unique(devices, |d| normalize_name(&d.name) == name) {
+            return found(d, AdapterMatch::Name);
+        }
+    }
+    if let Some(device_type) = vendor_device_type(info) {
+        if let Some(d) = unique(devices, |d| d.device_type == Some(device_type)) {
+            return found(d, AdapterMatch::Vendor);
+        }
+    }
+    None
+}
+
+impl OidnDevice {
+    /// Creates the OIDN device on the same physical device as a wgpu adapter.
+    ///
+    /// Tries, in order: software adapter → CPU device, name match, vendor match. Falls back to the
+    /// CPU device. Use [`Self::for_wgpu_adapter_with`] to supply UUID/LUID/PCI identifiers or a
+    /// different fallback. Returns the device together with the strategy that selected it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DeviceCreationFailed`] or the device's error if creation fails.
+    pub fn for_wgpu_adapter(info: &wgpu::AdapterInfo) -> Result<(Self, AdapterMatch), Error> {
+        Self::for_wgpu_adapter_with(info, &AdapterMatchOptions::default())
+    }
+
+    /// Like [`Self::for_wgpu_adapter`], with explicit identifiers and fallback policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DeviceCreationFailed`] if nothing matched and the fallback is
+    /// [`AdapterFallback::Error`], or if device creation fails.
+    pub fn for_wgpu_adapter_with(
+        info: &wgpu::AdapterInfo,
+        options: &AdapterMatchOptions,
+    ) -> Result<(Self, AdapterMatch), Error> {
+        match match_adapter(info, options, &physical_devices()) {
+            Some((AdapterTarget::Cpu, m)) => Ok((Self::cpu()?, m)),
+            Some((AdapterTarget::Physical(id), m)) => Ok((Self::new_by_id(id)?, m)),
+            None => {
+                let device = match options.fallback {
+                    AdapterFallback::Cpu => Self::cpu()?,
+                    AdapterFallback::Default => Self::new()?,
+                    AdapterFallback::Error => return Err(Error::DeviceCreationFailed),
+                };
+                Ok((device, AdapterMatch::Fallback(options.fallback)))
+            }
+        }
+    }
+}
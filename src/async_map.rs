@@ -0,0 +1,131 @@
+//! Awaiting wgpu buffer mappings without blocking a thread.
+//!
+//! See [`Poller`], [`denoise_texture_async`](crate::denoise_texture_async) and
+//! [`TextureDenoiser::denoise_async`](crate::TextureDenoiser::denoise_async).
+
+use crate::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// Who polls the wgpu device while an async denoise waits for a buffer mapping.
+///
+/// wgpu only runs `map_async` callbacks from `Device::poll`, so someone has to poll for the
+/// futures to complete.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Poller {
+    /// A helper thread polls the device until the mapping has completed. There is one such
+    /// thread per wgpu device, shared by all its pending mappings; it exits once no mapping has
+    /// been pending for a second, so a per-frame denoise keeps reusing it.
+    #[default]
+    Background,
+    /// The application polls the device, e.g. `device.poll(wgpu::PollType::Poll)` once per
+    /// event-loop iteration; the future completes on the poll that finishes the mapping.
+    External,
+}
+
+/// How long a background poll thread waits for a new mapping before exiting (and releasing its
+/// handle to the device).
+const POLL_THREAD_IDLE: Duration = Duration::from_secs(1);
+
+/// Number of pending mappings of a device's poll thread, and the signal that it changed.
+type PendingMappings = (Mutex<usize>, Condvar);
+
+/// The running background poll threads, one per device. Lock before a thread's count.
+static POLL_THREADS: Mutex<Vec<(wgpu::Device, Arc<PendingMappings>)>> = Mutex::new(Vec::new());
+
+#[derive(Default)]
+struct MapState {
+    result: Option<Result<(), wgpu::BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+/// Completes when a `map_async` callback has run.
+pub(crate) struct MapFuture {
+    state: Arc<Mutex<MapState>>,
+}
+
+/// Starts mapping `slice` with `mode`; the returned future completes when the mapping has.
+pub(crate) fn map_buffer(
+    wgpu_device: &wgpu::Device,
+    slice: wgpu::BufferSlice<'_>,
+    mode: wgpu::MapMode,
+    poller: Poller,
+) -> MapFuture {
+    let state = Arc::new(Mutex::new(MapState::default()));
+    let callback_state = Arc::clone(&state);
+    let pending = (poller == Poller::Background).then(|| start_polling(wgpu_device));
+    slice.map_async(mode, move |result| {
+        let waker = {
+            let mut state = callback_state.lock().unwrap();
+            state.result = Some(result);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        if let Some(pending) = pending {
+            *pending.0.lock().unwrap() -= 1;
+        }
+    });
+    MapFuture { state }
+}
+
+/// Counts one more pending mapping on `wgpu_device`'s poll thread, starting the thread if the
+/// device has none.
+fn start_polling(wgpu_device: &wgpu::Device) -> Arc<PendingMappings> {
+    let mut threads = POLL_THREADS.lock().unwrap();
+    if let Some((_, pending)) = threads.iter().find(|(device, _)| device == wgpu_device) {
+        *pending.0.lock().unwrap() += 1;
+        pending.1.notify_one();
+        return Arc::clone(pending);
+    }
+    let pending = Arc::new((Mutex::new(1), Condvar::new()));
+    threads.push((wgpu_device.clone(), Arc::clone(&pending)));
+    let device = wgpu_device.clone();
+    let thread_pending = Arc::clone(&pending);
+    std::thread::spawn(move || poll_thread(device, thread_pending));
+    pending
+}
+
+/// Polls `device` while mappings are pending; exits (unregistering itself) after
+/// [`POLL_THREAD_IDLE`] without any.
+fn poll_thread(device: wgpu::Device, pending: Arc<PendingMappings>) {
+    loop {
+        let count = pending.0.lock().unwrap();
+        let (count, wait) = pending
+            .1
+            .wait_timeout_while(count, POLL_THREAD_IDLE, |count| *count == 0)
+            .unwrap();
+        if wait.timed_out() {
+            // Re-check under the registry lock, so a mapping cannot register with an exiting
+            // thread.
+            drop(count);
+            let mut threads = POLL_THREADS.lock().unwrap();
+            if *pending.0.lock().unwrap() == 0 {
+                threads.retain(|(_, other)| !Arc::ptr_eq(other, &pending));
+                return;
+            }
+            continue;
+        }
+        drop(count);
+        let _ = device.poll(wgpu::PollType::wait_indefinitely());
+    }
+}
+
+impl Future for MapFuture {
+    type Output = Result<(), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result.map_err(Error::BufferMapFailed)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
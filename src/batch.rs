@@ -0,0 +1,182 @@
+//! Denoising many color layers (light groups, lighting components) with shared albedo/normal.
+//!
+//! See [`BatchDenoiser`].
+
+use crate::device::OidnDevice;
+use crate::filter::RtFilter;
+use crate::Error;
+
+/// One layer: optional separate color input and the output (color is denoised in place if `None`).
+type Job<'a> = (Option<&'a [f32]>, &'a mut [f32]);
+
+/// Denoises a batch of color layers that share the same albedo and normal AOVs.
+///
+/// Each filter uploads the aux images once per batch (with
+/// [`ExecutionMode::Buffered`](crate::ExecutionMode::Buffered); zero-copy modes bind them once)
+/// and then only uploads and denoises color per layer. With more than one filter, layers are
+/// spread over the filters and denoised on scoped threads. A failing layer does not stop the
+/// others; results are returned per layer.
+#[derive(Debug)]
+pub struct BatchDenoiser {
+    filters: Vec<RtFilter>,
+    width: u32,
+    height: u32,
+}
+
+impl BatchDenoiser {
+    /// Creates `parallelism` RT filters on `device` (at least one). On GPU devices executions
+    /// share one queue, so more than one filter mainly overlaps the host-side copies.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of [`RtFilter::new`].
+    pub fn new(device: &OidnDevice, parallelism: usize) -> Result<Self, Error> {
+        let filters = (0..parallelism.max(1))
+            .map(|_| RtFilter::new(device))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            filters,
+            width: 0,
+            height: 0,
+        })
+    }
+
+    /// Image dimensions (must be set before denoising).
+    pub fn set_dimensions(&mut self, width: u32, height: u32) -> &mut Self {
+        self.width = width;
+        self.height = height;
+        for filter in &mut self.filters {
+            filter.set_dimensions(width, height);
+        }
+        self
+    }
+
+    /// Applies `configure` to every filter (e.g. `|f| { f.set_hdr(true).set_quality(q); }`).
+    pub fn configure(&mut self, mut configure: impl FnMut(&mut RtFilter)) -> &mut Self {
+        for filter in &mut self.filters {
+            configure(filter);
+        }
+        self
+    }
+
+    /// Number of filters layers are spread over.
+    pub fn parallelism(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Underlying device (for sync/error).
+    pub fn device(&self) -> &OidnDevice {
+        self.filters[0].device()
+    }
+
+    /// Albedo/normal uploads of all filters.
+    #[cfg(test)]
+    pub(crate) fn aux_uploads(&self) -> usize {
+        self.filters.iter().map(RtFilter::aux_uploads).sum()
+    }
+
+    /// Denoises every layer in place. All slices are `width * height * 3` floats (RGB).
+    ///
+    /// Returns one result per layer, in order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDimensions`] if dimensions are unset or an aux slice has the wrong
+    /// size; per-layer failures are reported in the returned results.
+    pub fn denoise_in_place(
+        &mut self,
+        layers: &mut [&mut [f32]],
+        albedo: Option<&[f32]>,
+        normal: Option<&[f32]>,
+    ) -> Result<Vec<Result<(), Error>>, Error> {
+        let jobs = layers
+            .iter_mut()
+            .map(|layer| (None, &mut **layer))
+            .collect();
+        self.run(jobs, albedo, normal)
+    }
+
+    /// Denoises `layers[i]` into `outputs[i]`. See [`Self::denoise_in_place`].
+    ///
+    /// # Errors
+    ///
+    /// As [`Self::denoise_in_place`], and [`Error::InvalidDimensions`] if the number of outputs
+    /// differs from the number of layers.
+    pub fn denoise(
+        &mut self,
+        layers: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        albedo: Option<&[f32]>,
+        normal: Option<&[f32]>,
+    ) -> Result<Vec<Result<(), Error>>, Error> {
+        if layers.len() != outputs.len() {
+            return Err(Error::InvalidDimensions);
+        }
+        let jobs = layers
+            .iter()
+            .zip(outputs.iter_mut())
+            .map(|(layer, output)| (Some(*layer), &mut **output))
+            .collect();
+        self.run(jobs, albedo, normal)
+    }
+
+    fn run(
+        &mut self,
+        jobs: Vec<Job<'_>>,
+        albedo: Option<&[f32]>,
+        normal: Option<&[f32]>,
+    ) -> Result<Vec<Result<(), Error>>, Error> {
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::InvalidDimensions);
+        }
+        let n = self.width as usize * self.height as usize * 3;
+        if albedo.is_some_and(|a| a.len() != n) || normal.is_some_and(|a| a.len() != n) {
+            return Err(Error::InvalidDimensions);
+        }
+
+        let workers = self.filters.len().min(jobs.len()).max(1);
+        let mut queues: Vec<Vec<(usize, Job<'_>)>> = (0..workers).map(|_| Vec::new()).collect();
+        for (i, job) in jobs.into_iter().enumerate() {
+            queues[i % workers].push((i, job));
+        }
+        let total = queues.iter().map(Vec::len).sum();
+
+        let run_queue = |filter: &mut RtFilter, queue: Vec<(usize, Job<'_>)>| {
+            filter.pin_aux(true);
+            let results: Vec<_> = queue
+                .into_iter()
+                .map(|(i, (color, output))| {
+                    (i, filter.execute_with_aux(color, output, albedo, normal))
+                })
+                .collect();
+            filter.pin_aux(false);
+            results
+        };
+        let finished: Vec<(usize, Result<(), Error>)> = if workers == 1 {
+            run_queue(&mut self.filters[0], queues.pop().unwrap_or_default())
+        } else {
+            let run_queue = &run_queue;
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = self
+                    .filters
+                    .iter_mut()
+                    .zip(queues)
+                    .map(|(filter, queue)| scope.spawn(move || run_queue(filter, queue)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .flat_map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                    .collect()
+            })
+        };
+
+        let mut results: Vec<Option<Result<(), Error>>> = (0..total).map(|_| None).collect();
+        for (i, result) in finished {
+            results[i] = Some(result);
+        }
+        Ok(results
+            .into_iter()
+            .map(|r| r.expect("every layer has a result"))
+            .collect())
+    }
+}
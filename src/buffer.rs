@@ -65,9 +65,12 @@ impl From<ExternalMemoryTypeFlag> for sys::OIDNExternalMemoryTypeFlag {
 }
 
 /// OIDN buffer. Owns or wraps device-accessible memory.
+///
+/// Holds a handle to the device it was allocated from, so the device stays alive until the
+/// buffer is released.
 pub struct OidnBuffer {
     pub(crate) raw: sys::OIDNBuffer,
-    _device: std::marker::PhantomData<OidnDevice>,
+    device: OidnDevice,
 }
 
 impl std::fmt::Debug for OidnBuffer {
@@ -92,7 +95,7 @@ impl OidnBuffer {
         }
         Ok(Self {
             raw,
-            _device: std::marker::PhantomData,
+            device: device.clone(),
         })
     }
 
@@ -114,7 +117,7 @@ impl OidnBuffer {
         }
         Ok(Self {
             raw,
-            _device: std::marker::PhantomData,
+            device: device.clone(),
         })
     }
 
@@ -135,7 +138,7 @@ impl OidnBuffer {
         }
         Ok(Self {
             raw,
-            _device: std::marker::PhantomData,
+            device: device.clone(),
         })
     }
 
@@ -158,7 +161,7 @@ impl OidnBuffer {
         }
         Ok(Self {
             raw,
-            _device: std::marker::PhantomData,
+            device: device.clone(),
         })
     }
 
@@ -191,7 +194,7 @@ impl OidnBuffer {
         }
         Ok(Self {
             raw,
-            _device: std::marker::PhantomData,
+            device: device.clone(),
         })
     }
 
@@ -214,7 +217,7 @@ impl OidnBuffer {
         }
         Ok(Self {
             raw,
-            _device: std::marker::PhantomData,
+            device: device.clone(),
         })
     }
 
@@ -265,6 +268,11 @@ impl OidnBuffer {
         sys::oidnWriteBufferAsync(self.raw, byte_offset, byte_size, src);
     }
 
+    /// Device this buffer was allocated from.
+    pub fn device(&self) -> &OidnDevice {
+        &self.device
+    }
+
     /// Retains the buffer (increments OIDN reference count). For advanced interop when sharing the buffer.
     pub fn retain(&self) {
         unsafe { sys::oidnRetainBuffer(self.raw) };
@@ -1,11 +1,58 @@
 //! OIDN logical device (CPU or GPU backend) and physical device queries.
 //! Full API: type-based and physical-ID/UUID/LUID/PCI/CUDA/HIP/Metal device creation.
+//! Use [`physical_devices()`] for a typed, owned list of physical devices.
 
+use crate::error::{DeviceError, ErrorCode};
 use crate::sys;
 use crate::Error;
 use std::ffi::{CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr;
-use std::sync::Arc;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex, Weak};
+
+/// Closure type accepted by [`OidnDevice::set_error_handler`].
+type ErrorHandler = dyn Fn(ErrorCode, &str) + Send + Sync + 'static;
+
+/// Errors kept per device and thread until [`OidnDevice::take_error`]; later ones are dropped.
+const MAX_QUEUED_ERRORS: usize = 64;
+
+/// One device's errors queued on a thread.
+struct QueuedErrors {
+    /// [`DeviceInner::queue_key`] of the device; dead once the device is dropped.
+    device: Weak<()>,
+    errors: Vec<DeviceError>,
+}
+
+thread_local! {
+    /// Errors reported through the callback on this thread since its last
+    /// [`OidnDevice::take_error`], per device. OIDN reports an error on the thread whose call
+    /// failed, so each thread only drains its own, and the queues are freed when the thread exits.
+    static QUEUED_ERRORS: RefCell<Vec<QueuedErrors>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Runs `f` on this thread's queue for the device with `key`, after dropping the queues of
+/// devices that no longer exist. Does nothing while the thread is exiting.
+fn with_error_queue<R>(
+    key: &Arc<()>,
+    f: impl FnOnce(&mut Vec<QueuedErrors>, Option<usize>) -> R,
+) -> Option<R> {
+    QUEUED_ERRORS
+        .try_with(|queues| {
+            let mut queues = queues.borrow_mut();
+            queues.retain(|q| q.device.strong_count() > 0);
+            let key = Arc::downgrade(key);
+            let index = queues.iter().position(|q| q.device.ptr_eq(&key));
+            f(&mut queues, index)
+        })
+        .ok()
+}
+
+/// Number of devices with errors queued on the current thread.
+#[cfg(test)]
+pub(crate) fn queued_error_devices() -> usize {
+    QUEUED_ERRORS.with(|queues| queues.borrow().len())
+}
 
 /// Number of physical devices supported by OIDN. Valid IDs are `0 .. num_physical_devices()`.
 pub fn num_physical_devices() -> i32 {
@@ -46,6 +93,125 @@ pub fn get_physical_device_data(physical_device_id: i32, name: &str) -> Option<(
     }
 }
 
+/// PCI address of a physical device (domain, bus, device, function).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PciAddress {
+    pub domain: i32,
+    pub bus: i32,
+    pub device: i32,
+    pub function: i32,
+}
+
+impl std::fmt::Display for PciAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04x}:{:02x}:{:02x}.{:x}",
+            self.domain, self.bus, self.device, self.function
+        )
+    }
+}
+
+/// Owned description of a physical device, copied out of OIDN by [`physical_devices()`].
+///
+/// Unlike [`get_physical_device_string`] / [`get_physical_device_data`], all fields stay valid
+/// after further OIDN calls.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PhysicalDeviceInfo {
+    /// Physical device ID (use with [`OidnDevice::new_by_id`]).
+    pub id: i32,
+    /// Device name as reported by OIDN.
+    pub name: String,
+    /// Backend that drives this device. `None` if OIDN reported an unknown type.
+    pub device_type: Option<OidnDeviceType>,
+    /// Device UUID, if supported by the backend.
+    pub uuid: Option<[u8; sys::OIDN_UUID_SIZE]>,
+    /// Device LUID, if supported by the backend (Windows).
+    pub luid: Option<[u8; sys::OIDN_LUID_SIZE]>,
+    /// Node mask accompanying the LUID.
+    pub node_mask: Option<u32>,
+    /// PCI address, if supported by the backend.
+    pub pci_address: Option<PciAddress>,
+}
+
+impl PhysicalDeviceInfo {
+    /// Queries physical device `id`. Returns `None` if the ID is out of range.
+    pub fn query(id: i32) -> Option<Self> {
+        if id < 0 || id >= num_physical_devices() {
+            return None;
+        }
+        let uuid = if get_physical_device_bool(id, "uuidSupported") {
+            physical_device_bytes(id, "uuid")
+        } else {
+            None
+        };
+        let (luid, node_mask) = if get_physical_device_bool(id, "luidSupported") {
+            (
+                physical_device_bytes(id, "luid"),
+                Some(get_physical_device_int(id, "nodeMask") as u32),
+            )
+        } else {
+            (None, None)
+        };
+        let pci_address = if get_physical_device_bool(id, "pciAddressSupported") {
+            Some(PciAddress {
+                domain: get_physical_device_int(id, "pciDomain"),
+                bus: get_physical_device_int(id, "pciBus"),
+                device: get_physical_device_int(id, "pciDevice"),
+                function: get_physical_device_int(id, "pciFunction"),
+            })
+        } else {
+            None
+        };
+        Some(Self {
+            id,
+            name: get_physical_device_string(id, "name").unwrap_or_default(),
+            device_type: OidnDeviceType::from_raw(get_physical_device_int(id, "type")),
+            uuid,
+            luid,
+            node_mask,
+            pci_address,
+        })
+    }
+
+    /// Creates a committed device for this physical device.
+    pub fn create_device(&self) -> Result<OidnDevice, Error> {
+        OidnDevice::from_physical_device(self)
+    }
+}
+
+impl std::fmt::Display for PhysicalDeviceInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.id, self.name)?;
+        match self.device_type {
+            Some(t) => write!(f, " ({}", t)?,
+            None => write!(f, " (unknown")?,
+        }
+        if let Some(pci) = self.pci_address {
+            write!(f, ", PCI {}", pci)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Copies a fixed-size opaque physical device parameter (e.g. `"uuid"`) into an owned array.
+fn physical_device_bytes<const N: usize>(physical_device_id: i32, name: &str) -> Option<[u8; N]> {
+    let (ptr, size) = get_physical_device_data(physical_device_id, name)?;
+    if size != N {
+        return None;
+    }
+    let mut out = [0u8; N];
+    unsafe { ptr::copy_nonoverlapping(ptr as *const u8, out.as_mut_ptr(), N) };
+    Some(out)
+}
+
+/// Enumerates all physical devices supported by OIDN, in physical device ID order.
+pub fn physical_devices() -> Vec<PhysicalDeviceInfo> {
+    (0..num_physical_devices())
+        .filter_map(PhysicalDeviceInfo::query)
+        .collect()
+}
+
 /// Whether the CPU device is supported.
 pub fn is_cpu_device_supported() -> bool {
     unsafe { sys::oidnIsCPUDeviceSupported() }
@@ -73,27 +239,96 @@ pub unsafe fn is_metal_device_supported(device: *mut std::ffi::c_void) -> bool {
 /// Returns the first unqueried error for the current thread (e.g. from a failed device creation)
 /// and clears it. Can be called without a device to check why `OidnDevice::new()` or similar failed.
 pub fn take_global_error() -> Option<Error> {
+    Error::from_device_errors(stored_error(ptr::null_mut()).into_iter().collect())
+}
+
+/// Takes the error OIDN stores for `device` (null = global) on the current thread.
+fn stored_error(device: sys::OIDNDevice) -> Option<DeviceError> {
     let mut msg_ptr: *const std::ffi::c_char = ptr::null();
-    let code = unsafe { sys::oidnGetDeviceError(ptr::null_mut(), &mut msg_ptr) };
-    if code == sys::OIDNError::None {
-        return None;
-    }
+    let code = unsafe { sys::oidnGetDeviceError(device, &mut msg_ptr) };
+    let code = ErrorCode::from_raw(code)?;
     let message = if msg_ptr.is_null() {
         String::new()
     } else {
         unsafe { CStr::from_ptr(msg_ptr).to_string_lossy().into_owned() }
     };
-    Some(Error::OidnError { code: code as u32, message })
+    Some(DeviceError { code, message })
 }
 
 /// Open Image Denoise logical device.
 ///
 /// Prefer creating one per application and reusing it; filter creation is relatively expensive.
 /// See [`Self::new`], [`Self::cpu`], and backend-specific constructors.
+///
+/// Cloning is cheap: all clones share one OIDN device reference, which is released when the last
+/// clone is dropped.
 #[derive(Clone)]
 pub struct OidnDevice {
-    pub(crate) raw: sys::OIDNDevice,
-    _refcount: Arc<()>,
+    inner: Arc<DeviceInner>,
+}
+
+/// State shared by all clones of an [`OidnDevice`].
+struct DeviceInner {
+    raw: sys::OIDNDevice,
+    /// Closure installed by [`OidnDevice::set_error_handler`]. The OIDN callback's `user_ptr`
+    /// points at this `DeviceInner`, which outlives the OIDN device reference we hold.
+    error_handler: Mutex<Option<Arc<ErrorHandler>>>,
+    /// Identifies this device's queues in [`QUEUED_ERRORS`], which collect every error reported
+    /// through the callback (OIDN itself only keeps the first one per thread).
+    queue_key: Arc<()>,
+}
+
+impl Drop for DeviceInner {
+    fn drop(&mut self) {
+        unsafe {
+            // OIDN may keep the device alive (e.g. via retain()); never leave it pointing at freed state.
+            sys::oidnSetDeviceErrorFunction(self.raw, None, ptr::null_mut());
+            sys::oidnReleaseDevice(self.raw);
+        }
+    }
+}
+
+/// OIDN error callback that queues the error and forwards it to the closure stored in
+/// [`DeviceInner`].
+unsafe extern "C" fn error_handler_trampoline(
+    user_ptr: *mut std::ffi::c_void,
+    code: sys::OIDNError,
+    message: *const std::ffi::c_char,
+) {
+    let inner = &*(user_ptr as *const DeviceInner);
+    let Some(code) = ErrorCode::from_raw(code) else {
+        return;
+    };
+    let message = if message.is_null() {
+        std::borrow::Cow::Borrowed("")
+    } else {
+        CStr::from_ptr(message).to_string_lossy()
+    };
+    with_error_queue(&inner.queue_key, |queues, index| {
+        let index = index.unwrap_or_else(|| {
+            queues.push(QueuedErrors {
+                device: Arc::downgrade(&inner.queue_key),
+                errors: Vec::new(),
+            });
+            queues.len() - 1
+        });
+        let errors = &mut queues[index].errors;
+        if errors.len() < MAX_QUEUED_ERRORS {
+            errors.push(DeviceError {
+                code,
+                message: message.clone().into_owned(),
+            });
+        }
+    });
+    let handler = match inner.error_handler.lock() {
+        Ok(slot) => slot.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    };
+    let Some(handler) = handler else {
+        return;
+    };
+    // A panic must not unwind into OIDN's C++ frames.
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| handler(code, &message)));
 }
 
 impl std::fmt::Debug for OidnDevice {
@@ -141,45 +376,33 @@ impl OidnDevice {
 
     /// Creates a device of the given type.
     pub fn with_type(device_type: OidnDeviceType) -> Result<Self, Error> {
-        let raw = unsafe { sys::oidnNewDevice(device_type.to_raw()) };
-        if raw.is_null() {
-            return Err(Error::DeviceCreationFailed);
-        }
-        unsafe { sys::oidnCommitDevice(raw) };
-        Ok(Self {
-            raw,
-            _refcount: Arc::new(()),
-        })
+        OidnDeviceBuilder::new(device_type).build()
+    }
+
+    /// Creates a device for a physical device returned by [`physical_devices()`].
+    pub fn from_physical_device(info: &PhysicalDeviceInfo) -> Result<Self, Error> {
+        Self::builder().physical_device(info).build()
+    }
+
+    /// Returns a builder for configuring device parameters (thread count, affinity, verbosity)
+    /// that must be set before the device is committed.
+    pub fn builder() -> OidnDeviceBuilder {
+        OidnDeviceBuilder::new(OidnDeviceType::Default)
     }
 
     /// Creates a device from a physical device ID (0 to `num_physical_devices()` - 1).
     pub fn new_by_id(physical_device_id: i32) -> Result<Self, Error> {
-        let raw = unsafe { sys::oidnNewDeviceByID(physical_device_id) };
-        if raw.is_null() {
-            return Err(Error::DeviceCreationFailed);
-        }
-        unsafe { sys::oidnCommitDevice(raw) };
-        Ok(Self { raw, _refcount: Arc::new(()) })
+        Self::builder().physical_device_id(physical_device_id).build()
     }
 
     /// Creates a device from a physical device UUID (16 bytes; see [`crate::OIDN_UUID_SIZE`]).
     pub fn new_by_uuid(uuid: &[u8; sys::OIDN_UUID_SIZE]) -> Result<Self, Error> {
-        let raw = unsafe { sys::oidnNewDeviceByUUID(uuid.as_ptr() as *const std::ffi::c_void) };
-        if raw.is_null() {
-            return Err(Error::DeviceCreationFailed);
-        }
-        unsafe { sys::oidnCommitDevice(raw) };
-        Ok(Self { raw, _refcount: Arc::new(()) })
+        Self::builder().uuid(uuid).build()
     }
 
     /// Creates a device from a physical device LUID (8 bytes; see [`crate::OIDN_LUID_SIZE`]).
     pub fn new_by_luid(luid: &[u8; sys::OIDN_LUID_SIZE]) -> Result<Self, Error> {
-        let raw = unsafe { sys::oidnNewDeviceByLUID(luid.as_ptr() as *const std::ffi::c_void) };
-        if raw.is_null() {
-            return Err(Error::DeviceCreationFailed);
-        }
-        unsafe { sys::oidnCommitDevice(raw) };
-        Ok(Self { raw, _refcount: Arc::new(()) })
+        Self::builder().luid(luid).build()
     }
 
     /// Creates a device from a PCI address (domain, bus, device, function).
@@ -189,14 +412,9 @@ impl OidnDevice {
         pci_device: i32,
         pci_function: i32,
     ) -> Result<Self, Error> {
-        let raw = unsafe {
-            sys::oidnNewDeviceByPCIAddress(pci_domain, pci_bus, pci_device, pci_function)
-        };
-        if raw.is_null() {
-            return Err(Error::DeviceCreationFailed);
-        }
-        unsafe { sys::oidnCommitDevice(raw) };
-        Ok(Self { raw, _refcount: Arc::new(()) })
+        Self::builder()
+            .pci_address(pci_domain, pci_bus, pci_device, pci_function)
+            .build()
     }
 
     /// Creates a CUDA device for the given device ID and optional stream.
@@ -216,7 +434,7 @@ impl OidnDevice {
             return Err(Error::DeviceCreationFailed);
         }
         sys::oidnCommitDevice(raw);
-        Ok(Self { raw, _refcount: Arc::new(()) })
+        Ok(Self::from_raw(raw))
     }
 
     /// Creates a HIP device for the given device ID and optional stream.
@@ -235,7 +453,7 @@ impl OidnDevice {
             return Err(Error::DeviceCreationFailed);
         }
         sys::oidnCommitDevice(raw);
-        Ok(Self { raw, _refcount: Arc::new(()) })
+        Ok(Self::from_raw(raw))
     }
 
     /// Creates a Metal device from an array of Metal command queues (MTLCommandQueue).
@@ -250,31 +468,34 @@ impl OidnDevice {
             return Err(Error::DeviceCreationFailed);
         }
         sys::oidnCommitDevice(raw);
-        Ok(Self { raw, _refcount: Arc::new(()) })
+        Ok(Self::from_raw(raw))
     }
 
     /// Sets a boolean device parameter. Must call `commit()` before first use if you change parameters.
+    /// Parameters that only take effect before the first commit (e.g. `setAffinity`) must be set
+    /// through [`OidnDeviceBuilder`].
     pub fn set_bool(&self, name: &str, value: bool) {
         let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnSetDeviceBool(self.raw, c_name.as_ptr(), value) };
+        unsafe { sys::oidnSetDeviceBool(self.raw(), c_name.as_ptr(), value) };
     }
 
-    /// Sets an integer device parameter.
+    /// Sets an integer device parameter. Use [`OidnDeviceBuilder`] for pre-commit parameters
+    /// such as `numThreads` and `verbose`.
     pub fn set_int(&self, name: &str, value: i32) {
         let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnSetDeviceInt(self.raw, c_name.as_ptr(), value) };
+        unsafe { sys::oidnSetDeviceInt(self.raw(), c_name.as_ptr(), value) };
     }
 
     /// Gets a boolean device parameter.
     pub fn get_bool(&self, name: &str) -> bool {
         let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnGetDeviceBool(self.raw, c_name.as_ptr()) }
+        unsafe { sys::oidnGetDeviceBool(self.raw(), c_name.as_ptr()) }
     }
 
     /// Gets an integer device parameter.
     pub fn get_int(&self, name: &str) -> i32 {
         let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnGetDeviceInt(self.raw, c_name.as_ptr()) }
+        unsafe { sys::oidnGetDeviceInt(self.raw(), c_name.as_ptr()) }
     }
 
     /// Gets an unsigned integer device parameter (OIDN exposes this as cast of get_int).
@@ -284,10 +505,47 @@ impl OidnDevice {
 
     /// Commits all previous device parameter changes. Must be called before first filter creation.
     pub fn commit(&self) {
-        unsafe { sys::oidnCommitDevice(self.raw) };
+        unsafe { sys::oidnCommitDevice(self.raw()) };
+    }
+
+    /// Installs a closure that OIDN calls for every error and warning on this device.
+    ///
+    /// The closure is shared by all clones of the device and kept alive until it is replaced,
+    /// cleared with [`Self::clear_error_handler`], or the last clone is dropped. It may be invoked
+    /// from any thread that uses the device. A panic inside the closure is caught and discarded.
+    /// Replaces any callback set with [`Self::set_error_function_raw`].
+    pub fn set_error_handler<F>(&self, handler: F)
+    where
+        F: Fn(ErrorCode, &str) + Send + Sync + 'static,
+    {
+        self.install_error_handler(Some(Arc::new(handler)));
+    }
+
+    /// Removes the closure installed by [`Self::set_error_handler`]. Errors are still collected
+    /// for [`Self::take_error`].
+    pub fn clear_error_handler(&self) {
+        self.install_error_handler(None);
+    }
+
+    /// Stores `handler` and (re)installs the callback that queues errors and forwards them to it.
+    fn install_error_handler(&self, handler: Option<Arc<ErrorHandler>>) {
+        *self.error_handler_slot() = handler;
+        let user_ptr = Arc::as_ptr(&self.inner) as *mut std::ffi::c_void;
+        unsafe {
+            sys::oidnSetDeviceErrorFunction(self.raw(), Some(error_handler_trampoline), user_ptr)
+        };
+    }
+
+    fn error_handler_slot(&self) -> std::sync::MutexGuard<'_, Option<Arc<ErrorHandler>>> {
+        match self.inner.error_handler.lock() {
+            Ok(slot) => slot,
+            Err(poisoned) => poisoned.into_inner(),
+        }
     }
 
     /// Sets the error callback. The callback is invoked from OIDN; it must not panic.
+    /// Prefer [`Self::set_error_handler`], which needs no `unsafe`; a raw callback also stops
+    /// [`Self::take_error`] from collecting more than the first error.
     /// `user_ptr` is passed to the callback. Must remain valid until device is released or callback is cleared.
     ///
     /// # Safety
@@ -298,50 +556,286 @@ impl OidnDevice {
         func: sys::OIDNErrorFunction,
         user_ptr: *mut std::ffi::c_void,
     ) {
-        sys::oidnSetDeviceErrorFunction(self.raw, func, user_ptr);
+        *self.error_handler_slot() = None;
+        sys::oidnSetDeviceErrorFunction(self.raw(), func, user_ptr);
     }
 
-    /// Returns the first unqueried error and clears it.
+    /// Returns all errors reported on the current thread since its last call, combined into one
+    /// [`Error`] (see [`Error::device_errors`]), and clears them. Errors raised on other threads
+    /// stay queued for those threads. Cancellation alone is [`Error::Cancelled`].
+    ///
+    /// With a raw callback from [`Self::set_error_function_raw`], errors are not collected and
+    /// only the first one (which OIDN keeps per thread) is returned.
     pub fn take_error(&self) -> Option<Error> {
-        let mut msg_ptr: *const std::ffi::c_char = ptr::null();
-        let code = unsafe { sys::oidnGetDeviceError(self.raw, &mut msg_ptr) };
-        if code == sys::OIDNError::None {
-            return None;
+        // Always query OIDN too, which clears its stored copy of the first error.
+        let stored = stored_error(self.raw());
+        let mut errors = with_error_queue(&self.inner.queue_key, |queues, index| {
+            index.map(|i| queues.swap_remove(i).errors)
+        })
+        .flatten()
+        .unwrap_or_default();
+        if errors.is_empty() {
+            errors.extend(stored);
         }
-        let message = if msg_ptr.is_null() {
-            String::new()
-        } else {
-            unsafe { CStr::from_ptr(msg_ptr).to_string_lossy().into_owned() }
-        };
-        Some(Error::OidnError { code: code as u32, message })
+        Error::from_device_errors(errors)
+    }
+
+    /// Whether the device can access host memory directly (`systemMemorySupported`), which allows
+    /// zero-copy shared images from Rust slices. True for the CPU device.
+    pub fn system_memory_supported(&self) -> bool {
+        self.get_bool("systemMemorySupported")
+    }
+
+    /// Whether `self` and `other` are handles to the same OIDN device (e.g. clones of each other).
+    pub fn same_device(&self, other: &OidnDevice) -> bool {
+        self.raw() == other.raw()
     }
 
     /// Waits for all async operations on this device to complete.
     pub fn sync(&self) {
-        unsafe { sys::oidnSyncDevice(self.raw) };
+        unsafe { sys::oidnSyncDevice(self.raw()) };
     }
 
-    /// Retains the device (increments OIDN reference count). For advanced interop only; our `Clone` uses Arc.
+    /// Retains the device (increments OIDN reference count). For advanced interop only.
+    ///
+    /// The extra reference is never released by this crate, so the device leaks. Clone the
+    /// [`OidnDevice`] instead; all clones share one reference that is released exactly once.
+    #[deprecated(note = "leaks an OIDN device reference; clone the OidnDevice instead")]
     pub fn retain(&self) {
-        unsafe { sys::oidnRetainDevice(self.raw) };
+        unsafe { sys::oidnRetainDevice(self.raw()) };
+    }
+
+    /// Wraps a freshly created (uncommitted) device handle. Takes ownership of one reference.
+    fn from_raw(raw: sys::OIDNDevice) -> Self {
+        let device = Self {
+            inner: Arc::new(DeviceInner {
+                raw,
+                error_handler: Mutex::new(None),
+                queue_key: Arc::new(()),
+            }),
+        };
+        // Collect every error, not just the first one OIDN keeps.
+        device.install_error_handler(None);
+        device
     }
 
     pub(crate) fn raw(&self) -> sys::OIDNDevice {
-        self.raw
+        self.inner.raw
     }
 }
 
-impl Drop for OidnDevice {
-    fn drop(&mut self) {
-        unsafe { sys::oidnReleaseDevice(self.raw) }
+// OIDN devices are thread-safe; the handler slot is guarded by a mutex.
+unsafe impl Send for DeviceInner {}
+unsafe impl Sync for DeviceInner {}
+
+/// Which physical device an [`OidnDeviceBuilder`] creates.
+#[derive(Clone, Copy, Debug)]
+enum DeviceSelector {
+    Type(OidnDeviceType),
+    PhysicalId(i32),
+    Uuid([u8; sys::OIDN_UUID_SIZE]),
+    Luid([u8; sys::OIDN_LUID_SIZE]),
+    PciAddress(i32, i32, i32, i32),
+}
+
+/// Builder for [`OidnDevice`] that applies pre-commit parameters and then commits once.
+///
+/// OIDN only honours `numThreads`, `setAffinity` and `verbose` when they are set before
+/// `oidnCommitDevice`, which every [`OidnDevice`] constructor calls immediately. Use the builder
+/// to configure them:
+///
+/// ```ignore
+/// let device = OidnDevice::builder()
+///     .device_type(OidnDeviceType::Cpu)
+///     .num_threads(8)
+///     .set_affinity(true)
+///     .build()?;
+/// ```
+#[derive(Clone)]
+pub struct OidnDeviceBuilder {
+    selector: DeviceSelector,
+    num_threads: Option<u32>,
+    set_affinity: Option<bool>,
+    verbose: Option<u32>,
+    error_callback: Option<ErrorCallback>,
+}
+
+/// Error callback configured on an [`OidnDeviceBuilder`].
+#[derive(Clone)]
+enum ErrorCallback {
+    Raw(sys::OIDNErrorFunction, *mut std::ffi::c_void),
+    Handler(Arc<ErrorHandler>),
+}
+
+impl std::fmt::Debug for OidnDeviceBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OidnDeviceBuilder")
+            .field("selector", &self.selector)
+            .field("num_threads", &self.num_threads)
+            .field("set_affinity", &self.set_affinity)
+            .field("verbose", &self.verbose)
+            .field("error_callback", &self.error_callback.is_some())
+            .finish()
+    }
+}
+
+impl Default for OidnDeviceBuilder {
+    fn default() -> Self {
+        Self::new(OidnDeviceType::Default)
     }
 }
 
-unsafe impl Send for OidnDevice {}
-unsafe impl Sync for OidnDevice {}
+impl OidnDeviceBuilder {
+    /// Creates a builder for a device of the given type.
+    pub fn new(device_type: OidnDeviceType) -> Self {
+        Self {
+            selector: DeviceSelector::Type(device_type),
+            num_threads: None,
+            set_affinity: None,
+            verbose: None,
+            error_callback: None,
+        }
+    }
+
+    /// Selects the device by type (replaces any previous physical device selection).
+    pub fn device_type(&mut self, device_type: OidnDeviceType) -> &mut Self {
+        self.selector = DeviceSelector::Type(device_type);
+        self
+    }
+
+    /// Selects the device by physical device ID (0 to `num_physical_devices()` - 1).
+    pub fn physical_device_id(&mut self, physical_device_id: i32) -> &mut Self {
+        self.selector = DeviceSelector::PhysicalId(physical_device_id);
+        self
+    }
+
+    /// Selects a physical device returned by [`physical_devices()`].
+    pub fn physical_device(&mut self, info: &PhysicalDeviceInfo) -> &mut Self {
+        self.physical_device_id(info.id)
+    }
+
+    /// Selects the device by physical device UUID.
+    pub fn uuid(&mut self, uuid: &[u8; sys::OIDN_UUID_SIZE]) -> &mut Self {
+        self.selector = DeviceSelector::Uuid(*uuid);
+        self
+    }
+
+    /// Selects the device by physical device LUID.
+    pub fn luid(&mut self, luid: &[u8; sys::OIDN_LUID_SIZE]) -> &mut Self {
+        self.selector = DeviceSelector::Luid(*luid);
+        self
+    }
+
+    /// Selects the device by PCI address (domain, bus, device, function).
+    pub fn pci_address(
+        &mut self,
+        pci_domain: i32,
+        pci_bus: i32,
+        pci_device: i32,
+        pci_function: i32,
+    ) -> &mut Self {
+        self.selector = DeviceSelector::PciAddress(pci_domain, pci_bus, pci_device, pci_function);
+        self
+    }
+
+    /// Maximum number of worker threads (CPU device only). `0` = use all available. Default: 0.
+    pub fn num_threads(&mut self, num_threads: u32) -> &mut Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    /// Whether to pin worker threads to hardware threads (CPU device only). Default: true.
+    pub fn set_affinity(&mut self, set_affinity: bool) -> &mut Self {
+        self.set_affinity = Some(set_affinity);
+        self
+    }
+
+    /// Verbosity level of OIDN's console output (0 = quiet, up to 4). Default: 0.
+    pub fn verbose(&mut self, verbose: u32) -> &mut Self {
+        self.verbose = Some(verbose);
+        self
+    }
+
+    /// Installs an error handler before the device is committed, so errors raised during commit
+    /// are reported too. See [`OidnDevice::set_error_handler`].
+    pub fn error_handler<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(ErrorCode, &str) + Send + Sync + 'static,
+    {
+        self.error_callback = Some(ErrorCallback::Handler(Arc::new(handler)));
+        self
+    }
+
+    /// Sets the raw error callback before the device is committed. Replaces [`Self::error_handler`].
+    /// See [`OidnDevice::set_error_function_raw`].
+    ///
+    /// # Safety
+    ///
+    /// `func` must be a valid error callback that does not panic. `user_ptr` must remain valid until the device is dropped or the callback is cleared.
+    pub unsafe fn error_function_raw(
+        &mut self,
+        func: sys::OIDNErrorFunction,
+        user_ptr: *mut std::ffi::c_void,
+    ) -> &mut Self {
+        self.error_callback = Some(ErrorCallback::Raw(func, user_ptr));
+        self
+    }
+
+    /// Creates the device, applies all parameters and commits it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DeviceCreationFailed`] if no matching device is available (use
+    /// [`take_global_error()`] for the OIDN message), or the device's error if a parameter
+    /// was rejected during commit.
+    pub fn build(&self) -> Result<OidnDevice, Error> {
+        let raw = unsafe {
+            match self.selector {
+                DeviceSelector::Type(t) => sys::oidnNewDevice(t.to_raw()),
+                DeviceSelector::PhysicalId(id) => sys::oidnNewDeviceByID(id),
+                DeviceSelector::Uuid(uuid) => {
+                    sys::oidnNewDeviceByUUID(uuid.as_ptr() as *const std::ffi::c_void)
+                }
+                DeviceSelector::Luid(luid) => {
+                    sys::oidnNewDeviceByLUID(luid.as_ptr() as *const std::ffi::c_void)
+                }
+                DeviceSelector::PciAddress(domain, bus, device, function) => {
+                    sys::oidnNewDeviceByPCIAddress(domain, bus, device, function)
+                }
+            }
+        };
+        if raw.is_null() {
+            return Err(Error::DeviceCreationFailed);
+        }
+        let device = OidnDevice::from_raw(raw);
+        match &self.error_callback {
+            Some(ErrorCallback::Raw(func, user_ptr)) => unsafe {
+                device.set_error_function_raw(*func, *user_ptr)
+            },
+            Some(ErrorCallback::Handler(handler)) => {
+                device.install_error_handler(Some(handler.clone()))
+            }
+            None => {}
+        }
+        if let Some(n) = self.num_threads {
+            device.set_int("numThreads", n as i32);
+        }
+        if let Some(affinity) = self.set_affinity {
+            device.set_bool("setAffinity", affinity);
+        }
+        if let Some(verbose) = self.verbose {
+            device.set_int("verbose", verbose as i32);
+        }
+        device.commit();
+        if let Some(e) = device.take_error() {
+            return Err(e);
+        }
+        Ok(device)
+    }
+}
 
 /// OIDN device type (CPU, GPU backends, or default auto-select).
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub enum OidnDeviceType {
     /// Auto-select best available (e.g. CUDA if built and available).
     #[default]
@@ -358,7 +852,34 @@ pub enum OidnDeviceType {
     Metal,
 }
 
+impl std::fmt::Display for OidnDeviceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            OidnDeviceType::Default => "default",
+            OidnDeviceType::Cpu => "CPU",
+            OidnDeviceType::Sycl => "SYCL",
+            OidnDeviceType::Cuda => "CUDA",
+            OidnDeviceType::Hip => "HIP",
+            OidnDeviceType::Metal => "Metal",
+        };
+        f.write_str(name)
+    }
+}
+
 impl OidnDeviceType {
+    /// Maps an OIDN `type` parameter value (e.g. from a physical device) to a device type.
+    fn from_raw(value: i32) -> Option<Self> {
+        match value {
+            v if v == sys::OIDNDeviceType::Default as i32 => Some(OidnDeviceType::Default),
+            v if v == sys::OIDNDeviceType::CPU as i32 => Some(OidnDeviceType::Cpu),
+            v if v == sys::OIDNDeviceType::SYCL as i32 => Some(OidnDeviceType::Sycl),
+            v if v == sys::OIDNDeviceType::CUDA as i32 => Some(OidnDeviceType::Cuda),
+            v if v == sys::OIDNDeviceType::HIP as i32 => Some(OidnDeviceType::Hip),
+            v if v == sys::OIDNDeviceType::Metal as i32 => Some(OidnDeviceType::Metal),
+            _ => None,
+        }
+    }
+
     fn to_raw(self) -> sys::OIDNDeviceType {
         match self {
             OidnDeviceType::Default => sys::OIDNDeviceType::Default,
@@ -2,8 +2,73 @@
 //!
 //! See [`Error`] for the main error type returned by public APIs.
 
+use crate::sys;
 use std::fmt;
 
+/// OIDN error code, as reported by the device error callback and `oidnGetDeviceError`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// Unknown error.
+    Unknown,
+    /// Invalid argument (e.g. unknown parameter, wrong image size).
+    InvalidArgument,
+    /// Invalid operation (e.g. executing an uncommitted filter).
+    InvalidOperation,
+    /// Not enough memory.
+    OutOfMemory,
+    /// The hardware is not supported (e.g. missing GPU backend).
+    UnsupportedHardware,
+    /// The operation was cancelled by a progress monitor.
+    Cancelled,
+}
+
+impl ErrorCode {
+    /// Maps a raw OIDN error; `None` for `OIDN_ERROR_NONE`.
+    pub(crate) fn from_raw(code: sys::OIDNError) -> Option<Self> {
+        match code {
+            sys::OIDNError::None => None,
+            sys::OIDNError::Unknown => Some(ErrorCode::Unknown),
+            sys::OIDNError::InvalidArgument => Some(ErrorCode::InvalidArgument),
+            sys::OIDNError::InvalidOperation => Some(ErrorCode::InvalidOperation),
+            sys::OIDNError::OutOfMemory => Some(ErrorCode::OutOfMemory),
+            sys::OIDNError::UnsupportedHardware => Some(ErrorCode::UnsupportedHardware),
+            sys::OIDNError::Cancelled => Some(ErrorCode::Cancelled),
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ErrorCode::Unknown => "unknown error",
+            ErrorCode::InvalidArgument => "invalid argument",
+            ErrorCode::InvalidOperation => "invalid operation",
+            ErrorCode::OutOfMemory => "out of memory",
+            ErrorCode::UnsupportedHardware => "unsupported hardware",
+            ErrorCode::Cancelled => "cancelled",
+        };
+        f.write_str(s)
+    }
+}
+
+/// One error reported by an OIDN device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.message.is_empty() {
+            write!(f, "{}", self.code)
+        } else {
+            write!(f, "{}: {}", self.code, self.message)
+        }
+    }
+}
+
 /// Errors from OIDN or oidn-wgpu.
 ///
 /// This type implements [`std::error::Error`], [`Send`], and [`Sync`], so it can be
@@ -11,8 +76,13 @@ use std::fmt;
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum Error {
-    /// OIDN API returned an error.
-    OidnError { code: u32, message: String },
+    /// OIDN reported an error. When several errors were queued, the first one is in `code` and
+    /// `message` and the rest, in order, in `others`.
+    OidnError {
+        code: ErrorCode,
+        message: String,
+        others: Vec<DeviceError>,
+    },
     /// Device creation failed (e.g. no supported backend).
     DeviceCreationFailed,
     /// Filter creation failed.
@@ -23,6 +93,28 @@ pub enum Error {
     InvalidDimensions,
     /// Unsupported texture format for denoising.
     UnsupportedFormat,
+    /// A buffer from a different device was bound to a filter.
+    DeviceMismatch,
+    /// The filter does not support the requested quality (e.g. RTLightmap with `Fast`).
+    UnsupportedQuality(crate::Quality),
+    /// The filter type does not accept this image parameter (e.g. `albedo` on RTLightmap).
+    UnsupportedImage(String),
+    /// An image the denoiser was set up for was not passed (e.g. `albedo` for a
+    /// [`TextureDenoiser`](crate::TextureDenoiser) created with
+    /// [`AuxImages::Albedo`](crate::AuxImages::Albedo)).
+    MissingImage(String),
+    /// Unknown filter parameter name (or one that cannot be passed to OIDN).
+    InvalidParameter(String),
+    /// A parameter value of the wrong type or out of range.
+    InvalidParameterValue(String),
+    /// The parameter can only be read (e.g. `tileAlignment`).
+    ReadOnlyParameter(String),
+    /// Trained weights are not a valid TZA file.
+    InvalidWeights(String),
+    /// Reading a file failed.
+    Io(std::io::Error),
+    /// Execution was cancelled by a progress monitor or [`CancellationToken`](crate::CancellationToken).
+    Cancelled,
     /// wgpu buffer mapping failed.
     BufferMapFailed(wgpu::BufferAsyncError),
 }
@@ -30,18 +122,96 @@ pub enum Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Error::OidnError { code, message } => write!(f, "OIDN error ({}): {}", code, message),
+            Error::OidnError { code, message, others } => {
+                write!(f, "OIDN error ({}): {}", code, message)?;
+                if !others.is_empty() {
+                    write!(f, " (and {} more:", others.len())?;
+                    for e in others {
+                        write!(f, " [{}]", e)?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
             Error::DeviceCreationFailed => write!(f, "OIDN device creation failed"),
             Error::FilterCreationFailed => write!(f, "OIDN filter creation failed"),
             Error::OutOfMemory => write!(f, "OIDN out of memory"),
             Error::InvalidDimensions => write!(f, "invalid image dimensions"),
             Error::UnsupportedFormat => write!(f, "unsupported texture format for denoising"),
-            Error::BufferMapFailed(e) => write!(f, "wgpu buffer map failed: {:?}", e),
+            Error::DeviceMismatch => write!(f, "buffer belongs to a different OIDN device than the filter"),
+            Error::UnsupportedQuality(q) => write!(f, "quality {:?} is not supported by this filter", q),
+            Error::UnsupportedImage(name) => write!(f, "image parameter `{}` is not supported by this filter", name),
+            Error::MissingImage(name) => write!(f, "image `{}` is required but was not passed", name),
+            Error::InvalidParameter(name) => write!(f, "unknown filter parameter `{}`", name),
+            Error::InvalidParameterValue(reason) => write!(f, "invalid filter parameter value: {}", reason),
+            Error::ReadOnlyParameter(name) => write!(f, "filter parameter `{}` is read-only", name),
+            Error::InvalidWeights(reason) => write!(f, "invalid OIDN weights: {}", reason),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Cancelled => write!(f, "OIDN execution cancelled"),
+            Error::BufferMapFailed(e) => write!(f, "wgpu buffer map failed: {}", e),
+        }
+    }
+}
+
+impl Error {
+    /// The OIDN error code behind this error, if it came from OIDN: the first code of
+    /// [`Error::OidnError`], [`ErrorCode::Cancelled`] for [`Error::Cancelled`] and
+    /// [`ErrorCode::OutOfMemory`] for [`Error::OutOfMemory`].
+    pub fn kind(&self) -> Option<ErrorCode> {
+        match self {
+            Error::OidnError { code, .. } => Some(*code),
+            Error::Cancelled => Some(ErrorCode::Cancelled),
+            Error::OutOfMemory => Some(ErrorCode::OutOfMemory),
+            _ => None,
+        }
+    }
+
+    /// All OIDN errors this error was built from, in the order they were reported.
+    pub fn device_errors(&self) -> Vec<DeviceError> {
+        match self {
+            Error::OidnError { code, message, others } => {
+                let first = DeviceError {
+                    code: *code,
+                    message: message.clone(),
+                };
+                std::iter::once(first).chain(others.iter().cloned()).collect()
+            }
+            Error::Cancelled => vec![DeviceError {
+                code: ErrorCode::Cancelled,
+                message: String::new(),
+            }],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Combines drained device errors: `None` if there are none, [`Error::Cancelled`] if every
+    /// one is a cancellation, otherwise [`Error::OidnError`].
+    pub(crate) fn from_device_errors(errors: Vec<DeviceError>) -> Option<Self> {
+        if errors.is_empty() {
+            return None;
+        }
+        if errors.iter().all(|e| e.code == ErrorCode::Cancelled) {
+            return Some(Error::Cancelled);
         }
+        let mut errors = errors.into_iter();
+        let first = errors.next()?;
+        Some(Error::OidnError {
+            code: first.code,
+            message: first.message,
+            others: errors.collect(),
+        })
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::BufferMapFailed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 // Required for use with ? and multithreaded error handling (C-GOOD-ERR).
 unsafe impl Send for Error {}
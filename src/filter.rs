@@ -2,6 +2,11 @@
 
 use crate::buffer::OidnBuffer;
 use crate::device::OidnDevice;
+use crate::image::{ImageLayout, ImageMut, ImageRef};
+use crate::params::{self, ParamType, ParamValue, RtLightmapParam, RtLightmapParams, RtParam, RtParams};
+use crate::pending::{InFlight, PendingExecution};
+use crate::progress::{CancellationToken, ProgressMonitor};
+use crate::weights::{Weights, WeightsBinding};
 use crate::sys;
 use crate::Error;
 use std::ffi::CString;
@@ -21,7 +26,7 @@ pub enum Quality {
 }
 
 impl Quality {
-    fn to_raw(self) -> sys::OIDNQuality {
+    pub(crate) fn to_raw(self) -> sys::OIDNQuality {
         match self {
             Quality::Default => sys::OIDNQuality::Default,
             Quality::Fast => sys::OIDNQuality::Fast,
@@ -29,115 +34,252 @@ impl Quality {
             Quality::High => sys::OIDNQuality::High,
         }
     }
+
+    /// Maps an OIDN `quality` value; `None` if it is not a known quality.
+    pub(crate) fn from_raw(value: i32) -> Option<Self> {
+        match value {
+            v if v == sys::OIDNQuality::Default as i32 => Some(Quality::Default),
+            v if v == sys::OIDNQuality::Fast as i32 => Some(Quality::Fast),
+            v if v == sys::OIDNQuality::Balanced as i32 => Some(Quality::Balanced),
+            v if v == sys::OIDNQuality::High as i32 => Some(Quality::High),
+            _ => None,
+        }
+    }
+}
+
+/// How the typed filters pass host slices to OIDN.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExecutionMode {
+    /// [`Self::Shared`] when the device reports `systemMemorySupported` (e.g. the CPU device),
+    /// otherwise [`Self::Buffered`].
+    #[default]
+    Auto,
+    /// Copy slices into persistent OIDN buffers and copy the output back.
+    Buffered,
+    /// Bind slices directly as shared images (zero-copy). The device must be able to access
+    /// system memory; otherwise execution fails with the device's error.
+    ///
+    /// Only used by blocking executions; async ones always copy (see
+    /// [`RtFilter::execute_image_async`]).
+    Shared,
 }
 
 /// Ray tracing denoising filter (OIDN "RT" filter).
 ///
 /// Denoises a beauty (color) image, optionally using albedo and normal AOVs.
 /// Reuse the same filter for multiple frames when dimensions match.
-pub struct RtFilter<'a> {
-    device: &'a OidnDevice,
-    raw: sys::OIDNFilter,
-    width: u32,
-    height: u32,
+///
+/// The filter holds its own [`OidnDevice`] handle, so it has no lifetime parameter and can be
+/// stored next to the device or moved into a worker thread.
+pub struct RtFilter {
+    /// OIDN filter, images and commit tracking (shared with [`RtLightmapFilter`]).
+    filter: ImageFilter,
     hdr: bool,
     srgb: bool,
     clean_aux: bool,
     input_scale: f32,
     quality: Quality,
+    max_memory_mb: Option<u32>,
 }
 
-impl std::fmt::Debug for RtFilter<'_> {
+impl std::fmt::Debug for RtFilter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("RtFilter")
-            .field("width", &self.width)
-            .field("height", &self.height)
+            .field("width", &self.filter.width)
+            .field("height", &self.filter.height)
             .field("hdr", &self.hdr)
             .field("srgb", &self.srgb)
             .field("quality", &self.quality)
+            .field("max_memory_mb", &self.max_memory_mb)
+            .field("execution_mode", &self.filter.execution_mode)
             .finish_non_exhaustive()
     }
 }
 
-impl<'a> RtFilter<'a> {
+impl RtFilter {
     /// Creates a new RT filter. Reuse the same filter for multiple frames when dimensions match.
     ///
     /// # Errors
     ///
     /// Returns [`Error::FilterCreationFailed`] if the RT filter type is not available, or the
     /// device's last error (e.g. [`Error::OidnError`]).
-    pub fn new(device: &'a OidnDevice) -> Result<Self, Error> {
-        let type_name = CString::new("RT").unwrap();
-        let raw = unsafe { sys::oidnNewFilter(device.raw(), type_name.as_ptr()) };
-        if raw.is_null() {
-            return Err(device.take_error().unwrap_or(Error::FilterCreationFailed));
-        }
+    pub fn new(device: &OidnDevice) -> Result<Self, Error> {
         Ok(Self {
-            device,
-            raw,
-            width: 0,
-            height: 0,
+            filter: ImageFilter::new(device, "RT")?,
             hdr: true,
             srgb: false,
             clean_aux: false,
             input_scale: f32::NAN,
             quality: Quality::Default,
+            max_memory_mb: None,
         })
     }
 
     /// Image dimensions (must be set before execute).
     pub fn set_dimensions(&mut self, width: u32, height: u32) -> &mut Self {
-        self.width = width;
-        self.height = height;
+        self.filter.set_dimensions(width, height);
         self
     }
 
+    /// Underlying device (for sync/error).
+    pub fn device(&self) -> &OidnDevice {
+        &self.filter.handle.device
+    }
+
     /// Whether the input is HDR. Default: true.
     pub fn set_hdr(&mut self, hdr: bool) -> &mut Self {
+        self.filter.params_dirty |= self.hdr != hdr;
         self.hdr = hdr;
         self
     }
 
     /// Whether the input is sRGB (LDR). Default: false.
     pub fn set_srgb(&mut self, srgb: bool) -> &mut Self {
+        self.filter.params_dirty |= self.srgb != srgb;
         self.srgb = srgb;
         self
     }
 
     /// Whether albedo/normal are noise-free (prefiltered). Default: false.
     pub fn set_clean_aux(&mut self, clean: bool) -> &mut Self {
+        self.filter.params_dirty |= self.clean_aux != clean;
         self.clean_aux = clean;
         self
     }
 
     /// Input scale (e.g. for HDR). NaN = auto.
     pub fn set_input_scale(&mut self, scale: f32) -> &mut Self {
+        self.filter.params_dirty |= self.input_scale.to_bits() != scale.to_bits();
         self.input_scale = scale;
         self
     }
 
     /// Filter quality. Default: High.
     pub fn set_quality(&mut self, quality: Quality) -> &mut Self {
+        self.filter.params_dirty |= self.quality != quality;
         self.quality = quality;
         self
     }
 
+    /// Approximate maximum memory the filter may use, in megabytes (`None` = no limit, the default).
+    pub fn set_max_memory_mb(&mut self, max_memory_mb: Option<u32>) -> &mut Self {
+        self.filter.params_dirty |= self.max_memory_mb != max_memory_mb;
+        self.max_memory_mb = max_memory_mb;
+        self
+    }
+
+    /// Sets a parameter by [`RtParam`], like the typed setters. Ints are passed as is for
+    /// `maxMemoryMB` (negative = no limit) and must be a [`Quality`] value for `quality`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ReadOnlyParameter`] for `tileAlignment`/`tileOverlap`, or
+    /// [`Error::InvalidParameterValue`] if `value` has the wrong type or is not a valid quality.
+    pub fn set_param(&mut self, param: RtParam, value: impl Into<ParamValue>) -> Result<&mut Self, Error> {
+        let value = value.into();
+        params::check_value(param.name(), param.value_type(), param.is_read_only(), value)?;
+        match (param, value) {
+            (RtParam::Hdr, ParamValue::Bool(v)) => self.set_hdr(v),
+            (RtParam::Srgb, ParamValue::Bool(v)) => self.set_srgb(v),
+            (RtParam::CleanAux, ParamValue::Bool(v)) => self.set_clean_aux(v),
+            (RtParam::InputScale, ParamValue::Float(v)) => self.set_input_scale(v),
+            (RtParam::Quality, _) => self.set_quality(params::quality_from_value(value)?),
+            (RtParam::MaxMemoryMb, ParamValue::Int(v)) => {
+                self.set_max_memory_mb(params::max_memory_from_raw(v))
+            }
+            _ => unreachable!("checked by check_value"),
+        };
+        Ok(self)
+    }
+
+    /// Reads a parameter back from OIDN. Values set on this filter reach OIDN when the next
+    /// execution commits them.
+    pub fn get_param(&self, param: RtParam) -> ParamValue {
+        // Parameter names contain no NUL, so this only falls back for the type's sake.
+        self.filter
+            .get_param(param.name(), param.value_type())
+            .unwrap_or_else(|_| param.value_type().zero())
+    }
+
     /// Gets a boolean filter parameter (e.g. `"hdr"`, `"srgb"`).
+    #[deprecated(note = "use get_param with an RtParam")]
     pub fn get_bool(&self, name: &str) -> bool {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnGetFilterBool(self.raw, c_name.as_ptr()) }
+        self.get_named(name, ParamType::Bool).as_bool().unwrap_or_default()
     }
 
     /// Gets an integer filter parameter (e.g. `"quality"`).
+    #[deprecated(note = "use get_param with an RtParam")]
     pub fn get_int(&self, name: &str) -> i32 {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnGetFilterInt(self.raw, c_name.as_ptr()) }
+        self.get_named(name, ParamType::Int).as_int().unwrap_or_default()
     }
 
     /// Gets a float filter parameter (e.g. `"inputScale"`).
+    #[deprecated(note = "use get_param with an RtParam")]
     pub fn get_float(&self, name: &str) -> f32 {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnGetFilterFloat(self.raw, c_name.as_ptr()) }
+        self.get_named(name, ParamType::Float).as_float().unwrap_or_default()
+    }
+
+    /// [`Self::get_param`] for known names of type `value_type`; other names are read as is.
+    /// Names OIDN cannot take (containing NUL) read as the type's zero value, like unknown ones.
+    fn get_named(&self, name: &str, value_type: ParamType) -> ParamValue {
+        match RtParam::from_name(name) {
+            Some(param) if param.value_type() == value_type => self.get_param(param),
+            _ => self
+                .filter
+                .get_param(name, value_type)
+                .unwrap_or_else(|_| value_type.zero()),
+        }
+    }
+
+    /// Snapshot of all parameters as OIDN has committed them (e.g. for logging). Before the first
+    /// execution these are OIDN's defaults.
+    pub fn params(&self) -> RtParams {
+        let get = |param| self.get_param(param);
+        RtParams {
+            hdr: get(RtParam::Hdr).as_bool().unwrap_or_default(),
+            srgb: get(RtParam::Srgb).as_bool().unwrap_or_default(),
+            clean_aux: get(RtParam::CleanAux).as_bool().unwrap_or_default(),
+            input_scale: get(RtParam::InputScale).as_float().unwrap_or(f32::NAN),
+            quality: get(RtParam::Quality).as_int().and_then(Quality::from_raw),
+            max_memory_mb: get(RtParam::MaxMemoryMb)
+                .as_int()
+                .and_then(params::max_memory_from_raw),
+            tile_alignment: get(RtParam::TileAlignment).as_int().unwrap_or_default().max(0) as u32,
+            tile_overlap: get(RtParam::TileOverlap).as_int().unwrap_or_default().max(0) as u32,
+        }
+    }
+
+    /// Replaces the built-in weights with custom trained ones (the `weights` data parameter).
+    ///
+    /// The filter keeps `weights` alive while they are bound; they take effect on the next
+    /// execution, which returns an OIDN error if the network cannot be built from them (e.g.
+    /// weights for a different filter).
+    pub fn set_weights(&mut self, weights: Weights) -> &mut Self {
+        self.filter.set_weights(weights);
+        self
+    }
+
+    /// Restores the built-in weights.
+    pub fn clear_weights(&mut self) -> &mut Self {
+        self.filter.clear_weights();
+        self
+    }
+
+    /// Custom weights currently bound, if any.
+    pub fn weights(&self) -> Option<&Weights> {
+        self.filter.weights.get()
+    }
+
+    /// How slices are passed to OIDN. Default: [`ExecutionMode::Auto`] (zero-copy on devices that
+    /// can access system memory, such as the CPU device).
+    pub fn set_execution_mode(&mut self, mode: ExecutionMode) -> &mut Self {
+        self.filter.execution_mode = mode;
+        self
+    }
+
+    /// Whether the next blocking execution binds slices directly as shared images.
+    pub fn uses_shared_images(&self) -> bool {
+        self.filter.uses_shared_images()
     }
 
     /// Sets the progress monitor callback. Callback receives progress in [0,1]; return `false` to cancel.
@@ -151,7 +293,29 @@ impl<'a> RtFilter<'a> {
         func: sys::OIDNProgressMonitorFunction,
         user_ptr: *mut std::ffi::c_void,
     ) {
-        sys::oidnSetFilterProgressMonitorFunction(self.raw, func, user_ptr);
+        sys::oidnSetFilterProgressMonitorFunction(self.filter.handle.raw, func, user_ptr);
+    }
+
+    /// Calls `monitor` with the execution progress in `[0, 1]`; returning `false` cancels the
+    /// execution, which then fails with [`Error::Cancelled`]. Replaces any raw progress callback.
+    pub fn set_progress_monitor(
+        &mut self,
+        monitor: impl FnMut(f64) -> bool + Send + 'static,
+    ) -> &mut Self {
+        self.filter.handle.set_progress_monitor(Box::new(monitor));
+        self
+    }
+
+    /// Cancels executions once `token` (or a clone of it) is cancelled; see [`CancellationToken`].
+    pub fn set_cancellation_token(&mut self, token: &CancellationToken) -> &mut Self {
+        self.filter.handle.set_cancellation_token(token);
+        self
+    }
+
+    /// Removes the progress monitor and cancellation token.
+    pub fn clear_progress_monitor(&mut self) -> &mut Self {
+        self.filter.handle.clear_progress_monitor();
+        self
     }
 
     /// Denoises color in-place. `color` must be `width * height * 3` floats (RGB).
@@ -160,13 +324,13 @@ impl<'a> RtFilter<'a> {
     ///
     /// Returns [`Error::InvalidDimensions`] if dimensions are unset or buffer sizes do not match,
     /// or an OIDN error from the device.
-    pub fn execute_in_place(&self, color: &mut [f32]) -> Result<(), Error> {
+    pub fn execute_in_place(&mut self, color: &mut [f32]) -> Result<(), Error> {
         self.execute_with_aux(None, color, None, None)
     }
 
     /// Denoises color in-place with optional albedo and normal AOVs (each `width * height * 3` floats).
     pub fn execute_in_place_with_aux(
-        &self,
+        &mut self,
         color: &mut [f32],
         albedo: Option<&[f32]>,
         normal: Option<&[f32]>,
@@ -175,234 +339,735 @@ impl<'a> RtFilter<'a> {
     }
 
     /// Denoises color into output. Slices must be `width * height * 3` floats (RGB).
-    pub fn execute(&self, color: Option<&[f32]>, output: &mut [f32]) -> Result<(), Error> {
+    pub fn execute(&mut self, color: Option<&[f32]>, output: &mut [f32]) -> Result<(), Error> {
         self.execute_with_aux(color, output, None, None)
     }
 
     /// Denoises color into output with optional albedo and normal AOVs (each `width * height * 3` floats).
+    ///
+    /// In shared mode (see [`ExecutionMode`]) the slices are bound directly as OIDN images for the
+    /// duration of the call. Otherwise the filter copies them into OIDN buffers that it keeps
+    /// between calls while the dimensions are unchanged. Either way it only re-commits when a
+    /// parameter or an image binding changed.
     pub fn execute_with_aux(
-        &self,
+        &mut self,
         color: Option<&[f32]>,
         output: &mut [f32],
         albedo: Option<&[f32]>,
         normal: Option<&[f32]>,
     ) -> Result<(), Error> {
-        let w = self.width as usize;
-        let h = self.height as usize;
+        let shared = self.uses_shared_images();
+        self.launch_slices(color, output, albedo, normal, shared)?
+            .wait()
+    }
+
+    /// Denoises a color image into `output`, or `output` in place if `color` is `None`.
+    ///
+    /// See [`Self::execute_image_with_aux`].
+    pub fn execute_image(&mut self, color: Option<ImageRef<'_>>, output: &mut ImageMut<'_>) -> Result<(), Error> {
+        self.execute_image_with_aux(color, output, None, None)
+    }
+
+    /// Denoises image views (any of `Float3/4`, `Half3/4`, with arbitrary strides) into `output`,
+    /// or `output` in place if `color` is `None`.
+    ///
+    /// Each image may have its own format and strides; all must match the filter dimensions.
+    /// Only the first three channels of `output` are written; alpha and padding are preserved.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDimensions`] if dimensions are unset or an image has different
+    /// dimensions, or an OIDN error from the device.
+    pub fn execute_image_with_aux(
+        &mut self,
+        color: Option<ImageRef<'_>>,
+        output: &mut ImageMut<'_>,
+        albedo: Option<ImageRef<'_>>,
+        normal: Option<ImageRef<'_>>,
+    ) -> Result<(), Error> {
+        let color = color.map_or(ColorSource::InPlace, ColorSource::Image);
+        let shared = self.uses_shared_images();
+        self.launch(color, output.reborrow(), albedo, normal, shared)?
+            .wait()
+    }
+
+    /// Starts denoising `color` into `output` (in place if `color` is `None`) without waiting.
+    ///
+    /// See [`Self::execute_image_async`].
+    pub fn execute_async<'a>(
+        &'a mut self,
+        color: Option<&'a [f32]>,
+        output: &'a mut [f32],
+    ) -> Result<PendingExecution<'a>, Error> {
+        self.execute_async_with_aux(color, output, None, None)
+    }
+
+    /// Starts denoising with optional albedo and normal AOVs (each `width * height * 3` floats)
+    /// without waiting. See [`Self::execute_image_async`].
+    pub fn execute_async_with_aux<'a>(
+        &'a mut self,
+        color: Option<&'a [f32]>,
+        output: &'a mut [f32],
+        albedo: Option<&'a [f32]>,
+        normal: Option<&'a [f32]>,
+    ) -> Result<PendingExecution<'a>, Error> {
+        self.launch_slices(color, output, albedo, normal, false)
+    }
+
+    /// Starts denoising image views without waiting for the device (`oidnExecuteFilterAsync`).
+    ///
+    /// Async executions always copy the images into the filter's own OIDN buffers, whatever the
+    /// [`ExecutionMode`], and the output is copied back when the returned [`PendingExecution`] is
+    /// waited on. OIDN therefore never accesses the borrowed images after this call returns, and
+    /// leaking the handle only means the output is not written (see [`PendingExecution`]).
+    ///
+    /// # Errors
+    ///
+    /// As [`Self::execute_image_with_aux`]; errors from the execution itself are returned when
+    /// waiting.
+    pub fn execute_image_async<'a>(
+        &'a mut self,
+        color: Option<ImageRef<'a>>,
+        output: ImageMut<'a>,
+        albedo: Option<ImageRef<'a>>,
+        normal: Option<ImageRef<'a>>,
+    ) -> Result<PendingExecution<'a>, Error> {
+        let color = color.map_or(ColorSource::InPlace, ColorSource::Image);
+        self.launch(color, output, albedo, normal, false)
+    }
+
+    /// Denoises a noisy albedo or normal image into `output` (auxiliary prefiltering; no color).
+    ///
+    /// Exactly one of `albedo` and `normal` must be set.
+    pub(crate) fn prefilter_aux(
+        &mut self,
+        albedo: Option<ImageRef<'_>>,
+        normal: Option<ImageRef<'_>>,
+        output: &mut ImageMut<'_>,
+    ) -> Result<(), Error> {
+        debug_assert!(albedo.is_some() != normal.is_some());
+        let shared = self.uses_shared_images();
+        self.launch(ColorSource::None, output.reborrow(), albedo, normal, shared)?
+            .wait()
+    }
+
+    /// While pinned, buffered executions upload albedo/normal only once; the caller must pass the
+    /// same aux images to every execution until unpinning.
+    pub(crate) fn pin_aux(&mut self, pinned: bool) {
+        self.filter.images.pin_aux(pinned);
+    }
+
+    /// Number of albedo/normal uploads into the filter's buffers.
+    #[cfg(test)]
+    pub(crate) fn aux_uploads(&self) -> usize {
+        self.filter.images.aux_uploads
+    }
+
+    /// Number of `oidnCommitFilter` calls.
+    #[cfg(test)]
+    pub(crate) fn commits(&self) -> usize {
+        self.filter.commits
+    }
+
+    /// `(tileAlignment, tileOverlap)` for color plus the given aux images. Leaves the filter's
+    /// dimensions at 1x1.
+    ///
+    /// OIDN only reports tiling for a committed filter, and it depends on which aux images are
+    /// bound, so a 1x1 image with the same inputs is run first.
+    pub(crate) fn probe_tiling(&mut self, albedo: bool, normal: bool) -> Result<(u32, u32), Error> {
+        let pixel = [0.0f32; 3];
+        let mut out = [0.0f32; 3];
+        self.set_dimensions(1, 1).execute_with_aux(
+            Some(&pixel),
+            &mut out,
+            albedo.then_some(&pixel[..]),
+            normal.then_some(&pixel[..]),
+        )?;
+        let params = self.params();
+        Ok((params.tile_alignment.max(1), params.tile_overlap))
+    }
+
+    /// Checks RGB `f32` slices against the dimensions and launches them.
+    fn launch_slices<'a>(
+        &'a mut self,
+        color: Option<&'a [f32]>,
+        output: &'a mut [f32],
+        albedo: Option<&'a [f32]>,
+        normal: Option<&'a [f32]>,
+        shared: bool,
+    ) -> Result<PendingExecution<'a>, Error> {
+        let (w, h) = (self.filter.width, self.filter.height);
         if w == 0 || h == 0 {
             return Err(Error::InvalidDimensions);
         }
-        let n = w * h * 3;
+        let n = w as usize * h as usize * 3;
         if output.len() != n {
             return Err(Error::InvalidDimensions);
         }
-        if let Some(c) = color {
-            if c.len() != n {
-                return Err(Error::InvalidDimensions);
-            }
+        if [color, albedo, normal]
+            .into_iter()
+            .flatten()
+            .any(|input| input.len() != n)
+        {
+            return Err(Error::InvalidDimensions);
         }
 
-        let device = self.device.raw();
-        let color_buf = if let Some(c) = color {
-            let buf = unsafe { sys::oidnNewBuffer(device, n * std::mem::size_of::<f32>()) };
-            if buf.is_null() {
-                return Err(self.device.take_error().unwrap_or(Error::OutOfMemory));
-            }
-            unsafe {
-                sys::oidnWriteBuffer(buf, 0, n * std::mem::size_of::<f32>(), c.as_ptr() as *const _);
-            }
-            Some(buf)
+        fn view(data: Option<&[f32]>, w: u32, h: u32) -> Result<Option<ImageRef<'_>>, Error> {
+            data.map(|d| ImageRef::from_f32(d, sys::OIDNFormat::Float3, w, h))
+                .transpose()
+        }
+        let (color, albedo, normal) = (view(color, w, h)?, view(albedo, w, h)?, view(normal, w, h)?);
+        let output = ImageMut::from_f32(output, sys::OIDNFormat::Float3, w, h)?;
+        let color = color.map_or(ColorSource::InPlace, ColorSource::Image);
+        self.launch(color, output, albedo, normal, shared)
+    }
+
+    /// Binds the images and starts executing (see [`ImageFilter::launch`]).
+    fn launch<'a>(
+        &'a mut self,
+        color: ColorSource<'a>,
+        output: ImageMut<'a>,
+        albedo: Option<ImageRef<'a>>,
+        normal: Option<ImageRef<'a>>,
+        shared: bool,
+    ) -> Result<PendingExecution<'a>, Error> {
+        let params = [
+            (RtParam::Hdr, ParamValue::Bool(self.hdr)),
+            (RtParam::Srgb, ParamValue::Bool(self.srgb)),
+            (RtParam::CleanAux, ParamValue::Bool(self.clean_aux)),
+            (RtParam::InputScale, ParamValue::Float(self.input_scale)),
+            (RtParam::Quality, ParamValue::Int(self.quality.to_raw() as i32)),
+            (RtParam::MaxMemoryMb, ParamValue::Int(params::max_memory_to_raw(self.max_memory_mb))),
+        ]
+        .map(|(param, value)| (param.name(), value));
+        self.filter.launch(color, output, albedo, normal, shared, &params)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Filter state shared by RtFilter, RtLightmapFilter and Filter
+// ---------------------------------------------------------------------------
+
+/// An OIDN filter reference with its device, progress monitor and in-flight execution.
+///
+/// Dropping it waits for a leaked async execution and then releases the filter.
+struct FilterHandle {
+    device: OidnDevice,
+    raw: sys::OIDNFilter,
+    progress: ProgressMonitor,
+    in_flight: InFlight,
+}
+
+impl FilterHandle {
+    /// Creates a filter of type `type_name` on `device`.
+    fn new(device: &OidnDevice, type_name: &str) -> Result<Self, Error> {
+        let c_name = CString::new(type_name).map_err(|_| Error::FilterCreationFailed)?;
+        let raw = unsafe { sys::oidnNewFilter(device.raw(), c_name.as_ptr()) };
+        if raw.is_null() {
+            return Err(device.take_error().unwrap_or(Error::FilterCreationFailed));
+        }
+        Ok(Self {
+            device: device.clone(),
+            raw,
+            progress: ProgressMonitor::default(),
+            in_flight: InFlight::default(),
+        })
+    }
+
+    fn set_progress_monitor(&mut self, monitor: Box<dyn FnMut(f64) -> bool + Send>) {
+        self.in_flight.settle(&self.device);
+        self.progress.set_callback(self.raw, monitor);
+    }
+
+    fn set_cancellation_token(&mut self, token: &CancellationToken) {
+        self.in_flight.settle(&self.device);
+        self.progress.set_token(self.raw, token.clone());
+    }
+
+    fn clear_progress_monitor(&mut self) {
+        self.in_flight.settle(&self.device);
+        self.progress.clear(self.raw);
+    }
+}
+
+impl Drop for FilterHandle {
+    fn drop(&mut self) {
+        self.in_flight.settle(&self.device);
+        // The OIDN filter may outlive this handle (see `retain`); detach the monitor state first.
+        self.progress.clear(self.raw);
+        unsafe { sys::oidnReleaseFilter(self.raw) }
+    }
+}
+
+// OIDN filters may be used from any thread, one at a time.
+unsafe impl Send for FilterHandle {}
+
+/// Image binding, weights and commit logic of the typed filters ([`RtFilter`] and
+/// [`RtLightmapFilter`]), which differ only in their parameters.
+struct ImageFilter {
+    handle: FilterHandle,
+    width: u32,
+    height: u32,
+    /// Persistent color/output/albedo/normal buffers and what is bound on the OIDN filter.
+    images: ImageBindings,
+    /// Parameters changed since the last commit.
+    params_dirty: bool,
+    execution_mode: ExecutionMode,
+    /// Whether the device can access host memory (`systemMemorySupported`), queried once.
+    system_memory_supported: bool,
+    /// Custom weights bound as shared data.
+    weights: WeightsBinding,
+    /// Number of `oidnCommitFilter` calls.
+    #[cfg(test)]
+    commits: usize,
+}
+
+impl ImageFilter {
+    fn new(device: &OidnDevice, type_name: &str) -> Result<Self, Error> {
+        Ok(Self {
+            handle: FilterHandle::new(device, type_name)?,
+            width: 0,
+            height: 0,
+            images: ImageBindings::default(),
+            params_dirty: true,
+            execution_mode: ExecutionMode::Auto,
+            system_memory_supported: device.system_memory_supported(),
+            weights: WeightsBinding::default(),
+            #[cfg(test)]
+            commits: 0,
+        })
+    }
+
+    fn set_dimensions(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    fn uses_shared_images(&self) -> bool {
+        match self.execution_mode {
+            ExecutionMode::Auto => self.system_memory_supported,
+            ExecutionMode::Buffered => false,
+            ExecutionMode::Shared => true,
+        }
+    }
+
+    fn set_weights(&mut self, weights: Weights) {
+        self.handle.in_flight.settle(&self.handle.device);
+        self.weights.set(self.handle.raw, weights);
+        self.params_dirty = true;
+    }
+
+    fn clear_weights(&mut self) {
+        self.handle.in_flight.settle(&self.handle.device);
+        if self.weights.clear(self.handle.raw) {
+            self.params_dirty = true;
+        }
+    }
+
+    /// Reads parameter `name` of `value_type` from OIDN.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidParameter`] if `name` contains a NUL byte.
+    fn get_param(&self, name: &str, value_type: ParamType) -> Result<ParamValue, Error> {
+        Ok(params::get_filter_param(self.handle.raw, &params::c_name(name)?, value_type))
+    }
+
+    /// Binds the images and starts executing. With `shared`, the images' memory is bound directly,
+    /// so the caller must wait on the result before the borrows end; otherwise only the filter's
+    /// own buffers are used.
+    ///
+    /// `params` are the filter's parameter values, set on OIDN only when they changed.
+    fn launch<'a>(
+        &'a mut self,
+        color: ColorSource<'a>,
+        mut output: ImageMut<'a>,
+        albedo: Option<ImageRef<'a>>,
+        normal: Option<ImageRef<'a>>,
+        shared: bool,
+        params: &[(&str, ParamValue)],
+    ) -> Result<PendingExecution<'a>, Error> {
+        self.handle.in_flight.settle(&self.handle.device);
+        let (w, h) = (self.width, self.height);
+        if w == 0 || h == 0 {
+            return Err(Error::InvalidDimensions);
+        }
+        let color_image = match color {
+            ColorSource::Image(c) => Some(c),
+            _ => None,
+        };
+        if output.width() != w
+            || output.height() != h
+            || [color_image, albedo, normal]
+                .into_iter()
+                .flatten()
+                .any(|input| input.width() != w || input.height() != h)
+        {
+            return Err(Error::InvalidDimensions);
+        }
+
+        let raw = self.handle.raw;
+        let bound = if shared {
+            self.images.bind_shared(raw, color, &mut output, albedo, normal)
         } else {
-            None
+            self.images.bind_buffered(&self.handle.device, raw, color, &output, albedo, normal)
         };
-        let out_buf = unsafe { sys::oidnNewBuffer(device, n * std::mem::size_of::<f32>()) };
-        if out_buf.is_null() {
-            if let Some(b) = color_buf {
-                unsafe { sys::oidnReleaseBuffer(b) };
-            }
-            return Err(self.device.take_error().unwrap_or(Error::OutOfMemory));
-        }
-        if color.is_none() {
-            unsafe {
-                sys::oidnWriteBuffer(
-                    out_buf,
-                    0,
-                    n * std::mem::size_of::<f32>(),
-                    output.as_ptr() as *const _,
-                );
-            }
+        if let Err(e) = bound {
+            self.images.invalidate();
+            self.params_dirty = true;
+            return Err(e);
         }
+        self.commit_if_needed(params)?;
+        unsafe { sys::oidnExecuteFilterAsync(raw) };
+        self.handle.in_flight.start();
 
-        // Validate and create optional albedo/normal buffers
-        if let Some(a) = albedo {
-            if a.len() != n {
-                if let Some(b) = color_buf {
-                    unsafe { sys::oidnReleaseBuffer(b) };
+        let device = self.handle.device.clone();
+        Ok(PendingExecution::new(
+            device,
+            Box::new(move || {
+                self.handle.in_flight.finish();
+                if !shared {
+                    self.images.read(ImageSlot::Output, output.span_mut());
                 }
-                unsafe { sys::oidnReleaseBuffer(out_buf) };
-                return Err(Error::InvalidDimensions);
+                match self.handle.device.take_error() {
+                    Some(e) => {
+                        // Force a full rebind and commit on the next call.
+                        self.images.invalidate();
+                        self.params_dirty = true;
+                        Err(e)
+                    }
+                    None => Ok(()),
+                }
+            }),
+        ))
+    }
+
+    /// Sets `params` if they changed and commits if they or the image bindings changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidParameter`] if a name contains a NUL byte; nothing is committed.
+    fn commit_if_needed(&mut self, params: &[(&str, ParamValue)]) -> Result<(), Error> {
+        if self.params_dirty {
+            for &(name, value) in params {
+                params::set_filter_param(self.handle.raw, &params::c_name(name)?, value);
             }
         }
-        if let Some(norm) = normal {
-            if norm.len() != n {
-                if let Some(b) = color_buf {
-                    unsafe { sys::oidnReleaseBuffer(b) };
-                }
-                unsafe { sys::oidnReleaseBuffer(out_buf) };
-                return Err(Error::InvalidDimensions);
+        let rebound = self.images.take_changed();
+        if self.params_dirty || rebound {
+            unsafe { sys::oidnCommitFilter(self.handle.raw) };
+            self.params_dirty = false;
+            #[cfg(test)]
+            {
+                self.commits += 1;
             }
         }
+        Ok(())
+    }
+}
 
-        let albedo_buf = albedo.map(|a| {
-            let buf = unsafe { sys::oidnNewBuffer(device, n * std::mem::size_of::<f32>()) };
-            if !buf.is_null() {
-                unsafe {
-                    sys::oidnWriteBuffer(buf, 0, n * std::mem::size_of::<f32>(), a.as_ptr() as *const _);
-                }
-            }
-            buf
-        });
-        let normal_buf = normal.map(|norm| {
-            let buf = unsafe { sys::oidnNewBuffer(device, n * std::mem::size_of::<f32>()) };
-            if !buf.is_null() {
-                unsafe {
-                    sys::oidnWriteBuffer(buf, 0, n * std::mem::size_of::<f32>(), norm.as_ptr() as *const _);
-                }
-            }
-            buf
-        });
+// ---------------------------------------------------------------------------
+// Persistent filter images (buffers reused across executions)
+// ---------------------------------------------------------------------------
 
-        if albedo_buf.is_some_and(|p| p.is_null())
-            || normal_buf.is_some_and(|p| p.is_null())
-        {
-            if let Some(b) = color_buf {
-                unsafe { sys::oidnReleaseBuffer(b) };
-            }
-            unsafe { sys::oidnReleaseBuffer(out_buf) };
-            for b in albedo_buf.into_iter().chain(normal_buf) {
-                if !b.is_null() {
-                    unsafe { sys::oidnReleaseBuffer(b) };
+/// Color input of an RT filter execution.
+#[derive(Clone, Copy)]
+enum ColorSource<'a> {
+    Image(ImageRef<'a>),
+    /// Denoise the output image in place.
+    InPlace,
+    /// No color: prefilter the albedo or normal image.
+    None,
+}
+
+/// Image parameter of the RT and RTLightmap filters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ImageSlot {
+    Color = 0,
+    Output = 1,
+    Albedo = 2,
+    Normal = 3,
+}
+
+impl ImageSlot {
+    /// OIDN image parameter name.
+    fn c_name(self) -> Result<CString, Error> {
+        params::c_name(match self {
+            ImageSlot::Color => "color",
+            ImageSlot::Output => "output",
+            ImageSlot::Albedo => "albedo",
+            ImageSlot::Normal => "normal",
+        })
+    }
+}
+
+/// Memory an image slot is bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ImageSource {
+    /// The persistent buffer of a slot (color may use the output buffer for in-place denoising).
+    Buffer(ImageSlot),
+    /// Host memory shared with OIDN (address only; the memory is borrowed per execution).
+    Host(usize),
+}
+
+/// What is currently set for an image slot on the OIDN filter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Binding {
+    source: ImageSource,
+    layout: ImageLayout,
+}
+
+/// Persistent OIDN buffers for the RT/RTLightmap image slots.
+///
+/// Buffers are reallocated only when the required size changes, and images are only set on the
+/// OIDN filter when their binding changes, so repeated executions at the same size skip both
+/// allocation and commit.
+#[derive(Default)]
+struct ImageBindings {
+    buffers: [Option<OidnBuffer>; 4],
+    bound: [Option<Binding>; 4],
+    changed: bool,
+    /// Whether the caller guarantees albedo/normal stay the same between executions.
+    aux_pinned: bool,
+    /// Whether the albedo/normal buffers hold the pinned images.
+    aux_resident: bool,
+    /// Number of albedo/normal uploads.
+    #[cfg(test)]
+    aux_uploads: usize,
+}
+
+impl ImageBindings {
+    /// Returns the buffer for `slot`, (re)allocating it if its size differs from `byte_size`.
+    fn buffer(
+        &mut self,
+        device: &OidnDevice,
+        slot: ImageSlot,
+        byte_size: usize,
+    ) -> Result<&OidnBuffer, Error> {
+        let idx = slot as usize;
+        if self.buffers[idx].as_ref().map(|b| b.size()) != Some(byte_size) {
+            self.buffers[idx] = None;
+            // Anything bound to the old buffer must be set again.
+            for b in self.bound.iter_mut() {
+                if b.is_some_and(|b| b.source == ImageSource::Buffer(slot)) {
+                    *b = None;
                 }
             }
-            return Err(self.device.take_error().unwrap_or(Error::OutOfMemory));
+            self.buffers[idx] = Some(OidnBuffer::new(device, byte_size)?);
         }
+        Ok(self.buffers[idx].as_ref().unwrap())
+    }
 
-        let color_ptr = color_buf.unwrap_or(out_buf);
-        unsafe {
-            let c_color = CString::new("color").unwrap();
-            let c_output = CString::new("output").unwrap();
-            let c_albedo = CString::new("albedo").unwrap();
-            let c_normal = CString::new("normal").unwrap();
-            let c_hdr = CString::new("hdr").unwrap();
-            let c_srgb = CString::new("srgb").unwrap();
-            let c_clean_aux = CString::new("cleanAux").unwrap();
-            let c_input_scale = CString::new("inputScale").unwrap();
-            let c_quality = CString::new("quality").unwrap();
+    /// Copies `data` into the buffer for `slot`, (re)allocating it if needed.
+    fn write(&mut self, device: &OidnDevice, slot: ImageSlot, data: &[u8]) -> Result<(), Error> {
+        let buffer = self.buffer(device, slot, data.len())?;
+        unsafe { buffer.write(0, data.len(), data.as_ptr() as *const _) };
+        Ok(())
+    }
+
+    /// Copies the buffer for `slot` into `dst`. The buffer must exist and be `dst.len()` bytes.
+    fn read(&self, slot: ImageSlot, dst: &mut [u8]) {
+        let buffer = self.buffers[slot as usize]
+            .as_ref()
+            .expect("image buffer allocated before read");
+        unsafe { buffer.read(0, dst.len(), dst.as_mut_ptr() as *mut _) };
+    }
 
+    /// Sets `slot` on the filter to the buffer of `owner`, unless it is already bound that way.
+    fn bind(
+        &mut self,
+        filter: sys::OIDNFilter,
+        slot: ImageSlot,
+        owner: ImageSlot,
+        layout: ImageLayout,
+    ) -> Result<(), Error> {
+        let binding = Binding {
+            source: ImageSource::Buffer(owner),
+            layout,
+        };
+        if self.bound[slot as usize] == Some(binding) {
+            return Ok(());
+        }
+        let buffer = self.buffers[owner as usize]
+            .as_ref()
+            .expect("image buffer allocated before bind");
+        let c_name = slot.c_name()?;
+        unsafe {
             sys::oidnSetFilterImage(
-                self.raw,
-                c_color.as_ptr(),
-                color_ptr,
-                sys::OIDNFormat::Float3,
-                w,
-                h,
-                0,
-                0,
+                filter,
+                c_name.as_ptr(),
+                buffer.raw(),
+                layout.format,
+                layout.width,
+                layout.height,
                 0,
+                layout.pixel_byte_stride,
+                layout.row_byte_stride,
             );
-            sys::oidnSetFilterImage(
-                self.raw,
-                c_output.as_ptr(),
-                out_buf,
-                sys::OIDNFormat::Float3,
-                w,
-                h,
-                0,
-                0,
+        }
+        self.bound[slot as usize] = Some(binding);
+        self.changed = true;
+        Ok(())
+    }
+
+    /// Sets `slot` on the filter to host memory at `ptr` (zero-copy), unless it is already bound there.
+    ///
+    /// The caller must keep `layout.byte_size()` bytes at `ptr` valid until the filter has finished
+    /// executing; the address stays set on the filter afterwards but is not accessed until rebound.
+    fn bind_host(
+        &mut self,
+        filter: sys::OIDNFilter,
+        slot: ImageSlot,
+        ptr: *mut u8,
+        layout: ImageLayout,
+    ) -> Result<(), Error> {
+        let binding = Binding {
+            source: ImageSource::Host(ptr as usize),
+            layout,
+        };
+        if self.bound[slot as usize] == Some(binding) {
+            return Ok(());
+        }
+        let c_name = slot.c_name()?;
+        unsafe {
+            sys::oidnSetSharedFilterImage(
+                filter,
+                c_name.as_ptr(),
+                ptr as *mut std::ffi::c_void,
+                layout.format,
+                layout.width,
+                layout.height,
                 0,
+                layout.pixel_byte_stride,
+                layout.row_byte_stride,
             );
-            if let Some(ab) = albedo_buf {
-                if !ab.is_null() {
-                    sys::oidnSetFilterImage(
-                        self.raw,
-                        c_albedo.as_ptr(),
-                        ab,
-                        sys::OIDNFormat::Float3,
-                        w,
-                        h,
-                        0,
-                        0,
-                        0,
-                    );
-                }
+        }
+        self.bound[slot as usize] = Some(binding);
+        self.changed = true;
+        Ok(())
+    }
+
+    /// Copies the images into the persistent buffers and binds them.
+    fn bind_buffered(
+        &mut self,
+        device: &OidnDevice,
+        filter: sys::OIDNFilter,
+        color: ColorSource<'_>,
+        output: &ImageMut<'_>,
+        albedo: Option<ImageRef<'_>>,
+        normal: Option<ImageRef<'_>>,
+    ) -> Result<(), Error> {
+        let output_layout = output.layout();
+        // OIDN does not write alpha or padding, so upload the output too when it has any; the
+        // readback then leaves those bytes unchanged.
+        if matches!(color, ColorSource::InPlace) || !output_layout.is_dense() {
+            self.write(device, ImageSlot::Output, output.as_image_ref().span())?;
+        } else {
+            self.buffer(device, ImageSlot::Output, output_layout.byte_size())?;
+        }
+        match color {
+            ColorSource::Image(c) => {
+                self.write(device, ImageSlot::Color, c.span())?;
+                self.bind(filter, ImageSlot::Color, ImageSlot::Color, c.layout())?;
             }
-            if let Some(nb) = normal_buf {
-                if !nb.is_null() {
-                    sys::oidnSetFilterImage(
-                        self.raw,
-                        c_normal.as_ptr(),
-                        nb,
-                        sys::OIDNFormat::Float3,
-                        w,
-                        h,
-                        0,
-                        0,
-                        0,
-                    );
+            ColorSource::InPlace => self.bind(filter, ImageSlot::Color, ImageSlot::Output, output_layout)?,
+            ColorSource::None => self.unbind(filter, ImageSlot::Color)?,
+        }
+        self.bind(filter, ImageSlot::Output, ImageSlot::Output, output_layout)?;
+        for (slot, image) in [(ImageSlot::Albedo, albedo), (ImageSlot::Normal, normal)] {
+            match image {
+                Some(image) => {
+                    let resident = self.aux_resident
+                        && self.buffers[slot as usize]
+                            .as_ref()
+                            .is_some_and(|b| b.size() == image.span().len());
+                    if !resident {
+                        self.write(device, slot, image.span())?;
+                        #[cfg(test)]
+                        {
+                            self.aux_uploads += 1;
+                        }
+                    }
+                    self.bind(filter, slot, slot, image.layout())?;
                 }
+                None => self.unbind(filter, slot)?,
             }
-            sys::oidnSetFilterBool(self.raw, c_hdr.as_ptr(), self.hdr);
-            sys::oidnSetFilterBool(self.raw, c_srgb.as_ptr(), self.srgb);
-            sys::oidnSetFilterBool(self.raw, c_clean_aux.as_ptr(), self.clean_aux);
-            sys::oidnSetFilterFloat(self.raw, c_input_scale.as_ptr(), self.input_scale);
-            sys::oidnSetFilterInt(self.raw, c_quality.as_ptr(), self.quality.to_raw() as i32);
-
-            sys::oidnCommitFilter(self.raw);
-            sys::oidnExecuteFilter(self.raw);
         }
+        self.aux_resident = self.aux_pinned;
+        Ok(())
+    }
 
-        // Required for GPU (e.g. CUDA) where execute is async; ensures result is ready before readback.
-        self.device.sync();
-
-        if let Some(b) = albedo_buf {
-            if !b.is_null() {
-                unsafe { sys::oidnReleaseBuffer(b) };
-            }
+    /// Binds the images' memory directly as shared images.
+    fn bind_shared(
+        &mut self,
+        filter: sys::OIDNFilter,
+        color: ColorSource<'_>,
+        output: &mut ImageMut<'_>,
+        albedo: Option<ImageRef<'_>>,
+        normal: Option<ImageRef<'_>>,
+    ) -> Result<(), Error> {
+        // OIDN only reads inputs through these pointers; the casts to *mut follow the C API.
+        let output_layout = output.layout();
+        let output_ptr = output.span_mut().as_mut_ptr();
+        self.release_buffers();
+        match color {
+            ColorSource::Image(c) => self.bind_host(filter, ImageSlot::Color, c.span().as_ptr() as *mut u8, c.layout())?,
+            ColorSource::InPlace => self.bind_host(filter, ImageSlot::Color, output_ptr, output_layout)?,
+            ColorSource::None => self.unbind(filter, ImageSlot::Color)?,
         }
-        if let Some(b) = normal_buf {
-            if !b.is_null() {
-                unsafe { sys::oidnReleaseBuffer(b) };
+        self.bind_host(filter, ImageSlot::Output, output_ptr, output_layout)?;
+        for (slot, image) in [(ImageSlot::Albedo, albedo), (ImageSlot::Normal, normal)] {
+            match image {
+                Some(image) => self.bind_host(filter, slot, image.span().as_ptr() as *mut u8, image.layout())?,
+                None => self.unbind(filter, slot)?,
             }
         }
+        Ok(())
+    }
 
-        unsafe {
-            sys::oidnReadBuffer(
-                out_buf,
-                0,
-                n * std::mem::size_of::<f32>(),
-                output.as_mut_ptr() as *mut _,
-            );
+    /// Releases all persistent buffers (e.g. when switching to shared host images).
+    fn release_buffers(&mut self) {
+        if self.buffers.iter().all(Option::is_none) {
+            return;
         }
-
-        if let Some(b) = color_buf {
-            unsafe { sys::oidnReleaseBuffer(b) };
+        self.buffers = Default::default();
+        self.aux_resident = false;
+        for b in self.bound.iter_mut() {
+            if b.is_some_and(|b| matches!(b.source, ImageSource::Buffer(_))) {
+                *b = None;
+            }
         }
-        unsafe { sys::oidnReleaseBuffer(out_buf) };
+    }
 
-        if let Some(e) = self.device.take_error() {
-            return Err(e);
+    /// Unsets `slot` on the filter if it is bound.
+    fn unbind(&mut self, filter: sys::OIDNFilter, slot: ImageSlot) -> Result<(), Error> {
+        if self.bound[slot as usize].is_some() {
+            let c_name = slot.c_name()?;
+            unsafe { sys::oidnUnsetFilterImage(filter, c_name.as_ptr()) };
+            self.bound[slot as usize] = None;
+            self.changed = true;
         }
         Ok(())
     }
-}
 
-impl Drop for RtFilter<'_> {
-    fn drop(&mut self) {
-        unsafe { sys::oidnReleaseFilter(self.raw) }
+    /// Returns whether any binding changed since the last call.
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
     }
-}
 
-unsafe impl Send for RtFilter<'_> {}
+    /// Forgets all bindings so every image is set again on the next execution.
+    fn invalidate(&mut self) {
+        self.bound = [None; 4];
+        self.changed = true;
+        self.aux_resident = false;
+    }
+
+    /// Uploads albedo/normal only on the first buffered execution while pinned.
+    fn pin_aux(&mut self, pinned: bool) {
+        self.aux_pinned = pinned;
+        self.aux_resident = false;
+    }
+}
 
 // ---------------------------------------------------------------------------
 // RTLightmap filter (lightmap denoising; requires OIDN built with RTLightmap)
@@ -411,70 +1076,196 @@ unsafe impl Send for RtFilter<'_> {}
 /// Ray-traced lightmap denoising filter (OIDN "RTLightmap").
 ///
 /// Use for denoising baked lightmaps. Requires OIDN built with `OIDN_FILTER_RTLIGHTMAP`.
-pub struct RtLightmapFilter<'a> {
-    device: &'a OidnDevice,
-    raw: sys::OIDNFilter,
-    width: u32,
-    height: u32,
+/// Holds its own [`OidnDevice`] handle, like [`RtFilter`], and reuses its images across
+/// executions the same way. RTLightmap takes no albedo/normal images.
+pub struct RtLightmapFilter {
+    /// OIDN filter, images and commit tracking (shared with [`RtFilter`]).
+    filter: ImageFilter,
     directional: bool,
+    input_scale: f32,
+    quality: Quality,
+    max_memory_mb: Option<u32>,
 }
 
-impl std::fmt::Debug for RtLightmapFilter<'_> {
+impl std::fmt::Debug for RtLightmapFilter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("RtLightmapFilter")
-            .field("width", &self.width)
-            .field("height", &self.height)
+            .field("width", &self.filter.width)
+            .field("height", &self.filter.height)
             .field("directional", &self.directional)
+            .field("input_scale", &self.input_scale)
+            .field("quality", &self.quality)
+            .field("max_memory_mb", &self.max_memory_mb)
+            .field("execution_mode", &self.filter.execution_mode)
             .finish_non_exhaustive()
     }
 }
 
-impl<'a> RtLightmapFilter<'a> {
+impl RtLightmapFilter {
     /// Creates a new RTLightmap filter. Returns an error if OIDN was not built with RTLightmap support.
-    pub fn new(device: &'a OidnDevice) -> Result<Self, Error> {
-        let type_name = CString::new("RTLightmap").unwrap();
-        let raw = unsafe { sys::oidnNewFilter(device.raw(), type_name.as_ptr()) };
-        if raw.is_null() {
-            return Err(device.take_error().unwrap_or(Error::FilterCreationFailed));
-        }
+    pub fn new(device: &OidnDevice) -> Result<Self, Error> {
         Ok(Self {
-            device,
-            raw,
-            width: 0,
-            height: 0,
+            filter: ImageFilter::new(device, "RTLightmap")?,
             directional: false,
+            input_scale: f32::NAN,
+            quality: Quality::Default,
+            max_memory_mb: None,
         })
     }
 
     /// Image dimensions (must be set before execute).
     pub fn set_dimensions(&mut self, width: u32, height: u32) -> &mut Self {
-        self.width = width;
-        self.height = height;
+        self.filter.set_dimensions(width, height);
         self
     }
 
+    /// Underlying device (for sync/error).
+    pub fn device(&self) -> &OidnDevice {
+        &self.filter.handle.device
+    }
+
     /// If true, use directional lightmap model; if false, HDR. Default: false.
     pub fn set_directional(&mut self, directional: bool) -> &mut Self {
+        self.filter.params_dirty |= self.directional != directional;
         self.directional = directional;
         self
     }
 
+    /// Input scale for the lightmap values (NaN = auto). Default: NaN.
+    pub fn set_input_scale(&mut self, scale: f32) -> &mut Self {
+        self.filter.params_dirty |= self.input_scale.to_bits() != scale.to_bits();
+        self.input_scale = scale;
+        self
+    }
+
+    /// Filter quality. RTLightmap only supports [`Quality::Default`] and [`Quality::High`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedQuality`] for [`Quality::Fast`] and [`Quality::Balanced`].
+    pub fn set_quality(&mut self, quality: Quality) -> Result<&mut Self, Error> {
+        if !matches!(quality, Quality::Default | Quality::High) {
+            return Err(Error::UnsupportedQuality(quality));
+        }
+        self.filter.params_dirty |= self.quality != quality;
+        self.quality = quality;
+        Ok(self)
+    }
+
+    /// Approximate maximum memory the filter may use, in megabytes (`None` = no limit, the default).
+    pub fn set_max_memory_mb(&mut self, max_memory_mb: Option<u32>) -> &mut Self {
+        self.filter.params_dirty |= self.max_memory_mb != max_memory_mb;
+        self.max_memory_mb = max_memory_mb;
+        self
+    }
+
+    /// Replaces the built-in weights with custom trained ones (the `weights` data parameter).
+    ///
+    /// The filter keeps `weights` alive while they are bound; they take effect on the next
+    /// execution, which returns an OIDN error if the network cannot be built from them (e.g.
+    /// weights for a different filter).
+    pub fn set_weights(&mut self, weights: Weights) -> &mut Self {
+        self.filter.set_weights(weights);
+        self
+    }
+
+    /// Restores the built-in weights.
+    pub fn clear_weights(&mut self) -> &mut Self {
+        self.filter.clear_weights();
+        self
+    }
+
+    /// Custom weights currently bound, if any.
+    pub fn weights(&self) -> Option<&Weights> {
+        self.filter.weights.get()
+    }
+
+    /// How slices are passed to OIDN (see [`RtFilter::set_execution_mode`]).
+    pub fn set_execution_mode(&mut self, mode: ExecutionMode) -> &mut Self {
+        self.filter.execution_mode = mode;
+        self
+    }
+
+    /// Whether the next blocking execution binds slices directly as shared images.
+    pub fn uses_shared_images(&self) -> bool {
+        self.filter.uses_shared_images()
+    }
+
+    /// Sets a parameter by [`RtLightmapParam`], like the typed setters (see [`RtFilter::set_param`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ReadOnlyParameter`] for `tileAlignment`/`tileOverlap`,
+    /// [`Error::InvalidParameterValue`] if `value` has the wrong type or is not a valid quality, or
+    /// [`Error::UnsupportedQuality`] as [`Self::set_quality`] does.
+    pub fn set_param(
+        &mut self,
+        param: RtLightmapParam,
+        value: impl Into<ParamValue>,
+    ) -> Result<&mut Self, Error> {
+        let value = value.into();
+        params::check_value(param.name(), param.value_type(), param.is_read_only(), value)?;
+        match (param, value) {
+            (RtLightmapParam::Directional, ParamValue::Bool(v)) => self.set_directional(v),
+            (RtLightmapParam::InputScale, ParamValue::Float(v)) => self.set_input_scale(v),
+            (RtLightmapParam::Quality, _) => self.set_quality(params::quality_from_value(value)?)?,
+            (RtLightmapParam::MaxMemoryMb, ParamValue::Int(v)) => {
+                self.set_max_memory_mb(params::max_memory_from_raw(v))
+            }
+            _ => unreachable!("checked by check_value"),
+        };
+        Ok(self)
+    }
+
+    /// Reads a parameter back from OIDN (see [`RtFilter::get_param`]).
+    pub fn get_param(&self, param: RtLightmapParam) -> ParamValue {
+        self.filter
+            .get_param(param.name(), param.value_type())
+            .unwrap_or_else(|_| param.value_type().zero())
+    }
+
     /// Gets a boolean filter parameter (e.g. `"directional"`).
+    #[deprecated(note = "use get_param with an RtLightmapParam")]
     pub fn get_bool(&self, name: &str) -> bool {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnGetFilterBool(self.raw, c_name.as_ptr()) }
+        self.get_named(name, ParamType::Bool).as_bool().unwrap_or_default()
     }
 
     /// Gets an integer filter parameter.
+    #[deprecated(note = "use get_param with an RtLightmapParam")]
     pub fn get_int(&self, name: &str) -> i32 {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnGetFilterInt(self.raw, c_name.as_ptr()) }
+        self.get_named(name, ParamType::Int).as_int().unwrap_or_default()
     }
 
     /// Gets a float filter parameter.
+    #[deprecated(note = "use get_param with an RtLightmapParam")]
     pub fn get_float(&self, name: &str) -> f32 {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnGetFilterFloat(self.raw, c_name.as_ptr()) }
+        self.get_named(name, ParamType::Float).as_float().unwrap_or_default()
+    }
+
+    /// Same as `RtFilter::get_named`.
+    fn get_named(&self, name: &str, value_type: ParamType) -> ParamValue {
+        match RtLightmapParam::from_name(name) {
+            Some(param) if param.value_type() == value_type => self.get_param(param),
+            _ => self
+                .filter
+                .get_param(name, value_type)
+                .unwrap_or_else(|_| value_type.zero()),
+        }
+    }
+
+    /// Snapshot of all parameters as OIDN has committed them (see [`RtFilter::params`]).
+    pub fn params(&self) -> RtLightmapParams {
+        let get = |param| self.get_param(param);
+        RtLightmapParams {
+            directional: get(RtLightmapParam::Directional).as_bool().unwrap_or_default(),
+            input_scale: get(RtLightmapParam::InputScale).as_float().unwrap_or(f32::NAN),
+            quality: get(RtLightmapParam::Quality).as_int().and_then(Quality::from_raw),
+            max_memory_mb: get(RtLightmapParam::MaxMemoryMb)
+                .as_int()
+                .and_then(params::max_memory_from_raw),
+            tile_alignment: get(RtLightmapParam::TileAlignment).as_int().unwrap_or_default().max(0) as u32,
+            tile_overlap: get(RtLightmapParam::TileOverlap).as_int().unwrap_or_default().max(0) as u32,
+        }
     }
 
     /// Sets the progress monitor callback. Call with `(None, null)` to clear.
@@ -487,126 +1278,90 @@ impl<'a> RtLightmapFilter<'a> {
         func: sys::OIDNProgressMonitorFunction,
         user_ptr: *mut std::ffi::c_void,
     ) {
-        sys::oidnSetFilterProgressMonitorFunction(self.raw, func, user_ptr);
+        sys::oidnSetFilterProgressMonitorFunction(self.filter.handle.raw, func, user_ptr);
+    }
+
+    /// Calls `monitor` with the execution progress in `[0, 1]`; returning `false` cancels the
+    /// execution, which then fails with [`Error::Cancelled`]. Replaces any raw progress callback.
+    pub fn set_progress_monitor(
+        &mut self,
+        monitor: impl FnMut(f64) -> bool + Send + 'static,
+    ) -> &mut Self {
+        self.filter.handle.set_progress_monitor(Box::new(monitor));
+        self
+    }
+
+    /// Cancels executions once `token` (or a clone of it) is cancelled; see [`CancellationToken`].
+    pub fn set_cancellation_token(&mut self, token: &CancellationToken) -> &mut Self {
+        self.filter.handle.set_cancellation_token(token);
+        self
+    }
+
+    /// Removes the progress monitor and cancellation token.
+    pub fn clear_progress_monitor(&mut self) -> &mut Self {
+        self.filter.handle.clear_progress_monitor();
+        self
     }
 
     /// Denoises lightmap in-place. `color` must be `width * height * 3` floats (RGB).
-    pub fn execute_in_place(&self, color: &mut [f32]) -> Result<(), Error> {
+    pub fn execute_in_place(&mut self, color: &mut [f32]) -> Result<(), Error> {
         self.execute(None, color)
     }
 
     /// Denoises lightmap: reads from `color` (if provided) or uses `output` as input, writes to `output`.
     /// All buffers must be `width * height * 3` floats (RGB).
-    pub fn execute(
-        &self,
-        color: Option<&[f32]>,
-        output: &mut [f32],
-    ) -> Result<(), Error> {
-        let w = self.width as usize;
-        let h = self.height as usize;
+    pub fn execute(&mut self, color: Option<&[f32]>, output: &mut [f32]) -> Result<(), Error> {
+        let (w, h) = (self.filter.width, self.filter.height);
         if w == 0 || h == 0 {
             return Err(Error::InvalidDimensions);
         }
-        let n = w * h * 3;
-        if output.len() != n {
+        let n = w as usize * h as usize * 3;
+        if output.len() != n || color.is_some_and(|c| c.len() != n) {
             return Err(Error::InvalidDimensions);
         }
-        if let Some(c) = color {
-            if c.len() != n {
-                return Err(Error::InvalidDimensions);
-            }
-        }
+        let color = color
+            .map(|c| ImageRef::from_f32(c, sys::OIDNFormat::Float3, w, h))
+            .transpose()?;
+        let mut output = ImageMut::from_f32(output, sys::OIDNFormat::Float3, w, h)?;
+        self.execute_image(color, &mut output)
+    }
 
-        let device = self.device.raw();
-        let color_buf = if let Some(c) = color {
-            let buf = unsafe { sys::oidnNewBuffer(device, n * std::mem::size_of::<f32>()) };
-            if buf.is_null() {
-                return Err(self.device.take_error().unwrap_or(Error::OutOfMemory));
-            }
-            unsafe {
-                sys::oidnWriteBuffer(buf, 0, n * std::mem::size_of::<f32>(), c.as_ptr() as *const _);
-            }
-            Some(buf)
-        } else {
-            None
-        };
-        let out_buf = unsafe { sys::oidnNewBuffer(device, n * std::mem::size_of::<f32>()) };
-        if out_buf.is_null() {
-            if let Some(b) = color_buf {
-                unsafe { sys::oidnReleaseBuffer(b) };
-            }
-            return Err(self.device.take_error().unwrap_or(Error::OutOfMemory));
-        }
-        if color.is_none() {
-            unsafe {
-                sys::oidnWriteBuffer(
-                    out_buf,
-                    0,
-                    n * std::mem::size_of::<f32>(),
-                    output.as_ptr() as *const _,
-                );
-            }
-        }
+    /// Denoises a lightmap image view (`Float3/4` or `Half3/4`, any strides) into `output`, or
+    /// `output` in place if `color` is `None`. See [`RtFilter::execute_image_with_aux`].
+    pub fn execute_image(&mut self, color: Option<ImageRef<'_>>, output: &mut ImageMut<'_>) -> Result<(), Error> {
+        let shared = self.uses_shared_images();
+        self.launch(color, output.reborrow(), shared)?.wait()
+    }
 
-        let color_ptr = color_buf.unwrap_or(out_buf);
-        unsafe {
-            let c_color = CString::new("color").unwrap();
-            let c_output = CString::new("output").unwrap();
-            let c_directional = CString::new("directional").unwrap();
-            sys::oidnSetFilterImage(
-                self.raw,
-                c_color.as_ptr(),
-                color_ptr,
-                sys::OIDNFormat::Float3,
-                w,
-                h,
-                0,
-                0,
-                0,
-            );
-            sys::oidnSetFilterImage(
-                self.raw,
-                c_output.as_ptr(),
-                out_buf,
-                sys::OIDNFormat::Float3,
-                w,
-                h,
-                0,
-                0,
-                0,
-            );
-            sys::oidnSetFilterInt(self.raw, c_directional.as_ptr(), self.directional as i32);
-            sys::oidnCommitFilter(self.raw);
-            sys::oidnExecuteFilter(self.raw);
-        }
-        self.device.sync();
-        unsafe {
-            sys::oidnReadBuffer(
-                out_buf,
-                0,
-                n * std::mem::size_of::<f32>(),
-                output.as_mut_ptr() as *mut _,
-            );
-        }
-        if let Some(b) = color_buf {
-            unsafe { sys::oidnReleaseBuffer(b) };
-        }
-        unsafe { sys::oidnReleaseBuffer(out_buf) };
-        if let Some(e) = self.device.take_error() {
-            return Err(e);
-        }
-        Ok(())
+    /// Starts denoising a lightmap image view without waiting. Like
+    /// [`RtFilter::execute_image_async`], this always runs on the filter's own OIDN buffers.
+    pub fn execute_image_async<'a>(
+        &'a mut self,
+        color: Option<ImageRef<'a>>,
+        output: ImageMut<'a>,
+    ) -> Result<PendingExecution<'a>, Error> {
+        self.launch(color, output, false)
     }
-}
 
-impl Drop for RtLightmapFilter<'_> {
-    fn drop(&mut self) {
-        unsafe { sys::oidnReleaseFilter(self.raw) }
+    /// Binds the images and starts executing (see [`ImageFilter::launch`]).
+    fn launch<'a>(
+        &'a mut self,
+        color: Option<ImageRef<'a>>,
+        output: ImageMut<'a>,
+        shared: bool,
+    ) -> Result<PendingExecution<'a>, Error> {
+        let params = [
+            (RtLightmapParam::Directional, ParamValue::Bool(self.directional)),
+            (RtLightmapParam::InputScale, ParamValue::Float(self.input_scale)),
+            (RtLightmapParam::Quality, ParamValue::Int(self.quality.to_raw() as i32)),
+            (RtLightmapParam::MaxMemoryMb, ParamValue::Int(params::max_memory_to_raw(self.max_memory_mb))),
+        ]
+        .map(|(param, value)| (param.name(), value));
+        let color = color.map_or(ColorSource::InPlace, ColorSource::Image);
+        self.filter.launch(color, output, None, None, shared, &params)
     }
 }
 
-unsafe impl Send for RtLightmapFilter<'_> {}
-
 // ---------------------------------------------------------------------------
 // Generic filter (full OIDN filter API: any type, shared images/data, async)
 // ---------------------------------------------------------------------------
@@ -619,29 +1374,48 @@ pub type ImageFormat = sys::OIDNFormat;
 /// Generic filter created by type name (e.g. `"RT"`, `"RTLightmap"`).
 ///
 /// Exposes the full OIDN filter API: buffer or shared image/data, progress monitor, async execute.
-pub struct Filter<'a> {
-    device: &'a OidnDevice,
-    raw: sys::OIDNFilter,
+/// Holds its own [`OidnDevice`] handle, like [`RtFilter`].
+pub struct Filter {
+    handle: FilterHandle,
+    type_name: String,
 }
 
-impl std::fmt::Debug for Filter<'_> {
+impl std::fmt::Debug for Filter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Filter").finish_non_exhaustive()
+        f.debug_struct("Filter")
+            .field("type_name", &self.type_name)
+            .finish_non_exhaustive()
     }
 }
 
-impl<'a> Filter<'a> {
+impl Filter {
     /// Creates a filter of the given type (e.g. `"RT"`, `"RTLightmap"`).
-    pub fn new(device: &'a OidnDevice, type_name: &str) -> Result<Self, Error> {
-        let c_name = CString::new(type_name).map_err(|_| Error::FilterCreationFailed)?;
-        let raw = unsafe { sys::oidnNewFilter(device.raw(), c_name.as_ptr()) };
-        if raw.is_null() {
-            return Err(device.take_error().unwrap_or(Error::FilterCreationFailed));
+    pub fn new(device: &OidnDevice, type_name: &str) -> Result<Self, Error> {
+        Ok(Self {
+            handle: FilterHandle::new(device, type_name)?,
+            type_name: type_name.to_owned(),
+        })
+    }
+
+    /// Filter type name this filter was created with (e.g. `"RT"`).
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    /// Rejects image parameters the filter type does not accept (RTLightmap takes no aux images).
+    fn check_image_name(&self, name: &str) -> Result<(), Error> {
+        if self.type_name == "RTLightmap" && matches!(name, "albedo" | "normal") {
+            return Err(Error::UnsupportedImage(name.to_owned()));
         }
-        Ok(Self { device, raw })
+        Ok(())
     }
 
     /// Sets an image parameter from an OIDN buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DeviceMismatch`] if `buffer` was allocated from a different device than
+    /// this filter, or [`Error::UnsupportedImage`] for `albedo`/`normal` on an RTLightmap filter.
     #[allow(clippy::too_many_arguments)]
     pub fn set_image(
         &self,
@@ -653,11 +1427,15 @@ impl<'a> Filter<'a> {
         byte_offset: usize,
         pixel_byte_stride: usize,
         row_byte_stride: usize,
-    ) {
-        let c_name = CString::new(name).unwrap();
+    ) -> Result<(), Error> {
+        if !buffer.device().same_device(&self.handle.device) {
+            return Err(Error::DeviceMismatch);
+        }
+        self.check_image_name(name)?;
+        let c_name = params::c_name(name)?;
         unsafe {
             sys::oidnSetFilterImage(
-                self.raw,
+                self.handle.raw,
                 c_name.as_ptr(),
                 buffer.raw(),
                 format,
@@ -668,10 +1446,15 @@ impl<'a> Filter<'a> {
                 row_byte_stride,
             );
         }
+        Ok(())
     }
 
     /// Sets an image parameter from a raw device pointer (zero-copy). Caller keeps ownership.
     ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedImage`] for `albedo`/`normal` on an RTLightmap filter.
+    ///
     /// # Safety
     ///
     /// `dev_ptr` must point to valid device memory of at least `row_byte_stride * height` bytes; the memory must remain valid and unchanged until the filter is executed or the image is unset.
@@ -686,10 +1469,11 @@ impl<'a> Filter<'a> {
         byte_offset: usize,
         pixel_byte_stride: usize,
         row_byte_stride: usize,
-    ) {
-        let c_name = CString::new(name).unwrap();
+    ) -> Result<(), Error> {
+        self.check_image_name(name)?;
+        let c_name = params::c_name(name)?;
         sys::oidnSetSharedFilterImage(
-            self.raw,
+            self.handle.raw,
             c_name.as_ptr(),
             dev_ptr,
             format,
@@ -699,70 +1483,158 @@ impl<'a> Filter<'a> {
             pixel_byte_stride,
             row_byte_stride,
         );
+        Ok(())
     }
 
     /// Unsets a previously set image parameter.
-    pub fn unset_image(&self, name: &str) {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnUnsetFilterImage(self.raw, c_name.as_ptr()) };
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidParameter`] if `name` contains a NUL byte.
+    pub fn unset_image(&self, name: &str) -> Result<(), Error> {
+        let c_name = params::c_name(name)?;
+        unsafe { sys::oidnUnsetFilterImage(self.handle.raw, c_name.as_ptr()) };
+        Ok(())
     }
 
     /// Sets an opaque data parameter (host pointer). Caller keeps ownership.
     ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidParameter`] if `name` contains a NUL byte.
+    ///
     /// # Safety
     ///
     /// `host_ptr` must point to valid, readable host memory of at least `byte_size` bytes; it must remain valid until the filter is executed or the data is unset.
-    pub unsafe fn set_shared_data(&self, name: &str, host_ptr: *mut std::ffi::c_void, byte_size: usize) {
-        let c_name = CString::new(name).unwrap();
-        sys::oidnSetSharedFilterData(self.raw, c_name.as_ptr(), host_ptr, byte_size);
+    pub unsafe fn set_shared_data(
+        &self,
+        name: &str,
+        host_ptr: *mut std::ffi::c_void,
+        byte_size: usize,
+    ) -> Result<(), Error> {
+        let c_name = params::c_name(name)?;
+        sys::oidnSetSharedFilterData(self.handle.raw, c_name.as_ptr(), host_ptr, byte_size);
+        Ok(())
     }
 
     /// Notifies the filter that the contents of an opaque data parameter have changed.
-    pub fn update_data(&self, name: &str) {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnUpdateFilterData(self.raw, c_name.as_ptr()) };
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidParameter`] if `name` contains a NUL byte.
+    pub fn update_data(&self, name: &str) -> Result<(), Error> {
+        let c_name = params::c_name(name)?;
+        unsafe { sys::oidnUpdateFilterData(self.handle.raw, c_name.as_ptr()) };
+        Ok(())
     }
 
     /// Unsets a previously set opaque data parameter.
-    pub fn unset_data(&self, name: &str) {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnUnsetFilterData(self.raw, c_name.as_ptr()) };
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidParameter`] if `name` contains a NUL byte.
+    pub fn unset_data(&self, name: &str) -> Result<(), Error> {
+        let c_name = params::c_name(name)?;
+        unsafe { sys::oidnUnsetFilterData(self.handle.raw, c_name.as_ptr()) };
+        Ok(())
+    }
+
+    /// Sets a parameter. On `"RT"` and `"RTLightmap"` filters the name and value type are checked
+    /// against [`RtParam`] / [`RtLightmapParam`]; other filter types pass the name through.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidParameter`] for an unknown name (or one containing a NUL byte),
+    /// [`Error::ReadOnlyParameter`] for read-only parameters, or [`Error::InvalidParameterValue`]
+    /// if the value has the wrong type.
+    pub fn set_param(&self, name: &str, value: impl Into<ParamValue>) -> Result<(), Error> {
+        let value = value.into();
+        if let Some((value_type, read_only)) = params::lookup(&self.type_name, name)? {
+            params::check_value(name, value_type, read_only, value)?;
+        }
+        params::set_filter_param(self.handle.raw, &params::c_name(name)?, value);
+        Ok(())
     }
 
-    /// Sets a boolean parameter.
-    pub fn set_bool(&self, name: &str, value: bool) {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnSetFilterBool(self.raw, c_name.as_ptr(), value) };
+    /// Gets a parameter of the given type (checked like [`Self::set_param`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidParameter`] for an unknown name, or
+    /// [`Error::InvalidParameterValue`] if the parameter has a different type.
+    pub fn get_param(&self, name: &str, value_type: ParamType) -> Result<ParamValue, Error> {
+        if let Some((expected, _)) = params::lookup(&self.type_name, name)? {
+            if expected != value_type {
+                return Err(Error::InvalidParameterValue(format!(
+                    "`{}` is {:?}, not {:?}",
+                    name, expected, value_type
+                )));
+            }
+        }
+        Ok(params::get_filter_param(self.handle.raw, &params::c_name(name)?, value_type))
     }
 
-    /// Gets a boolean parameter.
+    /// Sets a boolean parameter (see [`Self::set_param`]).
+    pub fn set_bool(&self, name: &str, value: bool) -> Result<(), Error> {
+        self.set_param(name, value)
+    }
+
+    /// Gets a boolean parameter (see [`Self::get_param`]).
+    pub fn try_get_bool(&self, name: &str) -> Result<bool, Error> {
+        Ok(self.get_param(name, ParamType::Bool)?.as_bool().unwrap_or_default())
+    }
+
+    /// Gets a boolean parameter without checking the name or type. A name containing NUL reads as
+    /// `false`.
+    #[deprecated(note = "use try_get_bool, which rejects unknown names and mismatched types")]
     pub fn get_bool(&self, name: &str) -> bool {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnGetFilterBool(self.raw, c_name.as_ptr()) }
+        params::c_name(name)
+            .map(|c_name| params::get_filter_param(self.handle.raw, &c_name, ParamType::Bool))
+            .unwrap_or_else(|_| ParamType::Bool.zero())
+            .as_bool()
+            .unwrap_or_default()
     }
 
-    /// Sets an integer parameter.
-    pub fn set_int(&self, name: &str, value: i32) {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnSetFilterInt(self.raw, c_name.as_ptr(), value) };
+    /// Sets an integer parameter (see [`Self::set_param`]).
+    pub fn set_int(&self, name: &str, value: i32) -> Result<(), Error> {
+        self.set_param(name, value)
     }
 
-    /// Gets an integer parameter.
+    /// Gets an integer parameter (see [`Self::get_param`]).
+    pub fn try_get_int(&self, name: &str) -> Result<i32, Error> {
+        Ok(self.get_param(name, ParamType::Int)?.as_int().unwrap_or_default())
+    }
+
+    /// Gets an integer parameter without checking the name or type. A name containing NUL reads as
+    /// `0`.
+    #[deprecated(note = "use try_get_int, which rejects unknown names and mismatched types")]
     pub fn get_int(&self, name: &str) -> i32 {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnGetFilterInt(self.raw, c_name.as_ptr()) }
+        params::c_name(name)
+            .map(|c_name| params::get_filter_param(self.handle.raw, &c_name, ParamType::Int))
+            .unwrap_or_else(|_| ParamType::Int.zero())
+            .as_int()
+            .unwrap_or_default()
+    }
+
+    /// Sets a float parameter (see [`Self::set_param`]).
+    pub fn set_float(&self, name: &str, value: f32) -> Result<(), Error> {
+        self.set_param(name, value)
     }
 
-    /// Sets a float parameter.
-    pub fn set_float(&self, name: &str, value: f32) {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnSetFilterFloat(self.raw, c_name.as_ptr(), value) };
+    /// Gets a float parameter (see [`Self::get_param`]).
+    pub fn try_get_float(&self, name: &str) -> Result<f32, Error> {
+        Ok(self.get_param(name, ParamType::Float)?.as_float().unwrap_or_default())
     }
 
-    /// Gets a float parameter.
+    /// Gets a float parameter without checking the name or type. A name containing NUL reads as
+    /// `0.0`.
+    #[deprecated(note = "use try_get_float, which rejects unknown names and mismatched types")]
     pub fn get_float(&self, name: &str) -> f32 {
-        let c_name = CString::new(name).unwrap();
-        unsafe { sys::oidnGetFilterFloat(self.raw, c_name.as_ptr()) }
+        params::c_name(name)
+            .map(|c_name| params::get_filter_param(self.handle.raw, &c_name, ParamType::Float))
+            .unwrap_or_else(|_| ParamType::Float.zero())
+            .as_float()
+            .unwrap_or_default()
     }
 
     /// Sets the progress monitor callback. Call with `(None, null)` to clear.
@@ -775,39 +1647,64 @@ impl<'a> Filter<'a> {
         func: sys::OIDNProgressMonitorFunction,
         user_ptr: *mut std::ffi::c_void,
     ) {
-        sys::oidnSetFilterProgressMonitorFunction(self.raw, func, user_ptr);
+        sys::oidnSetFilterProgressMonitorFunction(self.handle.raw, func, user_ptr);
+    }
+
+    /// Calls `monitor` with the execution progress in `[0, 1]`; returning `false` cancels the
+    /// execution, which then reports [`Error::Cancelled`] through [`OidnDevice::take_error`].
+    /// Replaces any raw progress callback.
+    pub fn set_progress_monitor(&mut self, monitor: impl FnMut(f64) -> bool + Send + 'static) {
+        self.handle.set_progress_monitor(Box::new(monitor));
+    }
+
+    /// Cancels executions once `token` (or a clone of it) is cancelled; see [`CancellationToken`].
+    pub fn set_cancellation_token(&mut self, token: &CancellationToken) {
+        self.handle.set_cancellation_token(token);
+    }
+
+    /// Removes the progress monitor and cancellation token.
+    pub fn clear_progress_monitor(&mut self) {
+        self.handle.clear_progress_monitor();
     }
 
     /// Commits all previous filter parameter changes. Must be called before execute.
     pub fn commit(&self) {
-        unsafe { sys::oidnCommitFilter(self.raw) };
+        unsafe { sys::oidnCommitFilter(self.handle.raw) };
     }
 
     /// Executes the filter (synchronous). Call `device.sync()` after if using a GPU device.
     pub fn execute(&self) {
-        unsafe { sys::oidnExecuteFilter(self.raw) };
+        unsafe { sys::oidnExecuteFilter(self.handle.raw) };
     }
 
-    /// Executes the filter asynchronously. Call `device.sync()` before reading output.
-    pub fn execute_async(&self) {
-        unsafe { sys::oidnExecuteFilterAsync(self.raw) };
+    /// Executes the filter asynchronously.
+    ///
+    /// The returned [`PendingExecution`] borrows the filter until it completes, so images cannot be
+    /// rebound meanwhile; wait on it (or `.await` it) before reading outputs. Buffers set with
+    /// [`Self::set_image`] are retained by OIDN; memory passed to [`Self::set_shared_image`] must
+    /// stay valid until completion (if the handle is leaked, until [`OidnDevice::sync`] returns;
+    /// the filter itself syncs before it changes its progress monitor or is dropped).
+    pub fn execute_async(&mut self) -> PendingExecution<'_> {
+        let handle = &mut self.handle;
+        unsafe { sys::oidnExecuteFilterAsync(handle.raw) };
+        handle.in_flight.start();
+        let device = handle.device.clone();
+        PendingExecution::new(
+            device,
+            Box::new(move || {
+                handle.in_flight.finish();
+                handle.device.take_error().map_or(Ok(()), Err)
+            }),
+        )
     }
 
     /// Underlying device (for sync/error).
-    pub fn device(&self) -> &'a OidnDevice {
-        self.device
+    pub fn device(&self) -> &OidnDevice {
+        &self.handle.device
     }
 
     /// Retains the filter (increments OIDN reference count). For advanced interop when sharing the filter.
     pub fn retain(&self) {
-        unsafe { sys::oidnRetainFilter(self.raw) };
+        unsafe { sys::oidnRetainFilter(self.handle.raw) };
     }
 }
-
-impl Drop for Filter<'_> {
-    fn drop(&mut self) {
-        unsafe { sys::oidnReleaseFilter(self.raw) }
-    }
-}
-
-unsafe impl Send for Filter<'_> {}
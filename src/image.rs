@@ -0,0 +1,350 @@
+//! Borrowed image views with an explicit format and strides, for passing framebuffers to filters
+//! without repacking them into tightly packed RGB `f32`.
+//!
+//! See [`ImageRef`], [`ImageMut`] and [`RtFilter::execute_image_with_aux`](crate::RtFilter::execute_image_with_aux).
+
+use crate::sys;
+use crate::Error;
+use crate::ImageFormat;
+use bytemuck::{cast_slice, cast_slice_mut};
+
+/// Format and strides of an image bound to a filter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ImageLayout {
+    pub(crate) format: sys::OIDNFormat,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) pixel_byte_stride: usize,
+    pub(crate) row_byte_stride: usize,
+}
+
+impl ImageLayout {
+    /// Validates a layout for a color/AOV image (`Float3/4` or `Half3/4`).
+    ///
+    /// A stride of 0 means tightly packed, as in OIDN.
+    fn new(
+        format: ImageFormat,
+        width: usize,
+        height: usize,
+        pixel_byte_stride: usize,
+        row_byte_stride: usize,
+    ) -> Result<Self, Error> {
+        let pixel_size = pixel_size(format).ok_or(Error::UnsupportedFormat)?;
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidDimensions);
+        }
+        let pixel_byte_stride = if pixel_byte_stride == 0 {
+            pixel_size
+        } else {
+            pixel_byte_stride
+        };
+        let row_size = pixel_byte_stride
+            .checked_mul(width)
+            .ok_or(Error::InvalidDimensions)?;
+        let row_byte_stride = if row_byte_stride == 0 {
+            row_size
+        } else {
+            row_byte_stride
+        };
+        if pixel_byte_stride < pixel_size || row_byte_stride < row_size {
+            return Err(Error::InvalidDimensions);
+        }
+        let layout = Self {
+            format,
+            width,
+            height,
+            pixel_byte_stride,
+            row_byte_stride,
+        };
+        // Strides this large cannot describe memory, but must not wrap around to a small size.
+        layout.checked_byte_size().ok_or(Error::InvalidDimensions)?;
+        Ok(layout)
+    }
+
+    /// Bytes spanned by the image, from the first pixel to the end of the last one.
+    pub(crate) fn byte_size(&self) -> usize {
+        self.checked_byte_size()
+            .expect("image size checked in ImageLayout::new")
+    }
+
+    fn checked_byte_size(&self) -> Option<usize> {
+        if self.width == 0 || self.height == 0 {
+            return Some(0);
+        }
+        (self.height - 1)
+            .checked_mul(self.row_byte_stride)?
+            .checked_add(self.width.checked_mul(self.pixel_byte_stride)?)
+    }
+
+    /// Whether filters write every byte of the image: three channels per pixel and no padding.
+    pub(crate) fn is_dense(&self) -> bool {
+        let rgb_size = match self.format {
+            sys::OIDNFormat::Half3 | sys::OIDNFormat::Half4 => 6,
+            _ => 12,
+        };
+        self.pixel_byte_stride == rgb_size && self.row_byte_stride == rgb_size * self.width
+    }
+}
+
+/// Size in bytes of one pixel of a color/AOV format, or `None` if filters do not accept it.
+fn pixel_size(format: ImageFormat) -> Option<usize> {
+    match format {
+        sys::OIDNFormat::Float3 => Some(12),
+        sys::OIDNFormat::Float4 => Some(16),
+        sys::OIDNFormat::Half3 => Some(6),
+        sys::OIDNFormat::Half4 => Some(8),
+        _ => None,
+    }
+}
+
+fn check_extent(layout: &ImageLayout, len: usize) -> Result<(), Error> {
+    if len < layout.byte_size() {
+        return Err(Error::InvalidDimensions);
+    }
+    Ok(())
+}
+
+fn check_float(format: ImageFormat) -> Result<(), Error> {
+    match format {
+        sys::OIDNFormat::Float3 | sys::OIDNFormat::Float4 => Ok(()),
+        _ => Err(Error::UnsupportedFormat),
+    }
+}
+
+fn check_half(format: ImageFormat) -> Result<(), Error> {
+    match format {
+        sys::OIDNFormat::Half3 | sys::OIDNFormat::Half4 => Ok(()),
+        _ => Err(Error::UnsupportedFormat),
+    }
+}
+
+/// Read-only image view: format (`Float3`, `Float4`, `Half3` or `Half4`), dimensions, byte strides
+/// and the backing bytes.
+///
+/// Channels beyond the third (e.g. alpha in `Float4`) and any padding are ignored by the filters.
+#[derive(Clone, Copy, Debug)]
+pub struct ImageRef<'a> {
+    data: &'a [u8],
+    layout: ImageLayout,
+}
+
+impl<'a> ImageRef<'a> {
+    /// Tightly packed image over raw bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedFormat`] for formats other than `Float3/4` and `Half3/4`, and
+    /// [`Error::InvalidDimensions`] if a dimension is 0 or `data` is too short.
+    pub fn new(
+        data: &'a [u8],
+        format: ImageFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Error> {
+        Self::with_strides(data, format, width, height, 0, 0)
+    }
+
+    /// Image over raw bytes with explicit pixel and row strides in bytes (0 = tightly packed).
+    ///
+    /// # Errors
+    ///
+    /// As [`Self::new`]; also [`Error::InvalidDimensions`] if a stride is smaller than the pixel
+    /// size or row width.
+    pub fn with_strides(
+        data: &'a [u8],
+        format: ImageFormat,
+        width: u32,
+        height: u32,
+        pixel_byte_stride: usize,
+        row_byte_stride: usize,
+    ) -> Result<Self, Error> {
+        let layout = ImageLayout::new(
+            format,
+            width as usize,
+            height as usize,
+            pixel_byte_stride,
+            row_byte_stride,
+        )?;
+        check_extent(&layout, data.len())?;
+        Ok(Self { data, layout })
+    }
+
+    /// Tightly packed `Float3` or `Float4` image.
+    pub fn from_f32(
+        data: &'a [f32],
+        format: ImageFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Error> {
+        check_float(format)?;
+        Self::new(cast_slice(data), format, width, height)
+    }
+
+    /// Tightly packed `Half3` or `Half4` image from IEEE half bit patterns (see `half::f16::to_bits`).
+    pub fn from_f16_bits(
+        data: &'a [u16],
+        format: ImageFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Error> {
+        check_half(format)?;
+        Self::new(cast_slice(data), format, width, height)
+    }
+
+    /// Pixel format.
+    pub fn format(&self) -> ImageFormat {
+        self.layout.format
+    }
+
+    /// Width in pixels.
+    pub fn width(&self) -> u32 {
+        self.layout.width as u32
+    }
+
+    /// Height in pixels.
+    pub fn height(&self) -> u32 {
+        self.layout.height as u32
+    }
+
+    /// Bytes between the starts of adjacent pixels.
+    pub fn pixel_byte_stride(&self) -> usize {
+        self.layout.pixel_byte_stride
+    }
+
+    /// Bytes between the starts of adjacent rows.
+    pub fn row_byte_stride(&self) -> usize {
+        self.layout.row_byte_stride
+    }
+
+    /// Backing bytes.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub(crate) fn layout(&self) -> ImageLayout {
+        self.layout
+    }
+
+    /// The bytes covered by the image (without trailing data past the last pixel).
+    pub(crate) fn span(&self) -> &'a [u8] {
+        &self.data[..self.layout.byte_size()]
+    }
+}
+
+/// Writable image view; see [`ImageRef`] for the accepted formats and strides.
+///
+/// Filters only write the first three channels of each pixel; alpha and padding bytes are preserved.
+#[derive(Debug)]
+pub struct ImageMut<'a> {
+    data: &'a mut [u8],
+    layout: ImageLayout,
+}
+
+impl<'a> ImageMut<'a> {
+    /// Tightly packed image over raw bytes. Errors as [`ImageRef::new`].
+    pub fn new(
+        data: &'a mut [u8],
+        format: ImageFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Error> {
+        Self::with_strides(data, format, width, height, 0, 0)
+    }
+
+    /// Image over raw bytes with explicit byte strides. Errors as [`ImageRef::with_strides`].
+    pub fn with_strides(
+        data: &'a mut [u8],
+        format: ImageFormat,
+        width: u32,
+        height: u32,
+        pixel_byte_stride: usize,
+        row_byte_stride: usize,
+    ) -> Result<Self, Error> {
+        let layout = ImageLayout::new(
+            format,
+            width as usize,
+            height as usize,
+            pixel_byte_stride,
+            row_byte_stride,
+        )?;
+        check_extent(&layout, data.len())?;
+        Ok(Self { data, layout })
+    }
+
+    /// Tightly packed `Float3` or `Float4` image.
+    pub fn from_f32(
+        data: &'a mut [f32],
+        format: ImageFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Error> {
+        check_float(format)?;
+        Self::new(cast_slice_mut(data), format, width, height)
+    }
+
+    /// Tightly packed `Half3` or `Half4` image from IEEE half bit patterns.
+    pub fn from_f16_bits(
+        data: &'a mut [u16],
+        format: ImageFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Error> {
+        check_half(format)?;
+        Self::new(cast_slice_mut(data), format, width, height)
+    }
+
+    /// Read-only view of the same image.
+    pub fn as_image_ref(&self) -> ImageRef<'_> {
+        ImageRef {
+            data: self.data,
+            layout: self.layout,
+        }
+    }
+
+    /// Pixel format.
+    pub fn format(&self) -> ImageFormat {
+        self.layout.format
+    }
+
+    /// Width in pixels.
+    pub fn width(&self) -> u32 {
+        self.layout.width as u32
+    }
+
+    /// Height in pixels.
+    pub fn height(&self) -> u32 {
+        self.layout.height as u32
+    }
+
+    /// Bytes between the starts of adjacent pixels.
+    pub fn pixel_byte_stride(&self) -> usize {
+        self.layout.pixel_byte_stride
+    }
+
+    /// Bytes between the starts of adjacent rows.
+    pub fn row_byte_stride(&self) -> usize {
+        self.layout.row_byte_stride
+    }
+
+    /// Backing bytes.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        self.data
+    }
+
+    pub(crate) fn layout(&self) -> ImageLayout {
+        self.layout
+    }
+
+    /// Shorter-lived view of the same image.
+    pub(crate) fn reborrow(&mut self) -> ImageMut<'_> {
+        ImageMut {
+            data: self.data,
+            layout: self.layout,
+        }
+    }
+
+    /// The bytes covered by the image (without trailing data past the last pixel).
+    pub(crate) fn span_mut(&mut self) -> &mut [u8] {
+        &mut self.data[..self.layout.byte_size()]
+    }
+}
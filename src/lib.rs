@@ -49,11 +49,25 @@
 //! # }
 //! ```
 
+pub mod adapter;
+pub mod async_map;
+pub mod batch;
 pub mod buffer;
 pub mod device;
 pub mod error;
 pub mod filter;
+pub mod image;
+pub mod memory;
+pub mod params;
+pub mod pending;
+pub mod prefilter;
+pub mod progress;
 mod sys;
+pub mod texture_denoiser;
+pub mod texture_format;
+pub mod tiled;
+pub mod weights;
+pub mod wgpu_buffer;
 pub mod wgpu_integration;
 
 #[cfg(test)]
@@ -64,15 +78,35 @@ pub const OIDN_UUID_SIZE: usize = 16;
 /// LUID size for physical device (bytes). Use with [`OidnDevice::new_by_luid`].
 pub const OIDN_LUID_SIZE: usize = 8;
 
+pub use adapter::{AdapterFallback, AdapterMatch, AdapterMatchOptions};
+pub use async_map::Poller;
+pub use batch::BatchDenoiser;
 pub use buffer::{BufferStorage, ExternalMemoryTypeFlag, OidnBuffer};
 pub use device::{
     get_physical_device_bool, get_physical_device_data, get_physical_device_int,
     get_physical_device_string, is_cpu_device_supported, is_cuda_device_supported,
-    is_hip_device_supported, is_metal_device_supported, num_physical_devices, OidnDevice,
-    OidnDeviceType, take_global_error,
+    is_hip_device_supported, is_metal_device_supported, num_physical_devices, physical_devices,
+    OidnDevice, OidnDeviceBuilder, OidnDeviceType, PciAddress, PhysicalDeviceInfo,
+    take_global_error,
+};
+pub use error::{DeviceError, Error, ErrorCode};
+pub use filter::{
+    ExecutionMode, Filter, ImageFormat, OIDNFormat, Quality, RtFilter, RtLightmapFilter,
+};
+pub use image::{ImageMut, ImageRef};
+pub use memory::{estimate_memory, AuxImages, MemoryEstimate};
+pub use params::{ParamType, ParamValue, RtLightmapParam, RtLightmapParams, RtParam, RtParams};
+pub use pending::PendingExecution;
+pub use prefilter::PrefilteredRtDenoiser;
+pub use progress::CancellationToken;
+pub use texture_denoiser::TextureDenoiser;
+pub use tiled::{RowReader, RowWriter, TiledDenoiser};
+pub use weights::Weights;
+pub use wgpu_buffer::{
+    denoise_buffer, denoise_buffer_with_aux, BufferDenoiser, BufferLayout, BufferTarget,
 };
-pub use error::Error;
-pub use filter::{Filter, ImageFormat, OIDNFormat, Quality, RtFilter, RtLightmapFilter};
 pub use wgpu_integration::{
-    denoise_texture, denoise_texture_with_aux, DenoiseOptions, DenoiseTextureFormat,
+    denoise_texture, denoise_texture_async, denoise_texture_regions, denoise_texture_with_aux,
+    denoise_texture_with_aux_async, DenoiseOptions, DenoiseTextureFormat, TextureRegion,
+    TextureTarget,
 };
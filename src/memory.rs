@@ -0,0 +1,105 @@
+//! Memory bounds for scheduling denoise jobs within a budget.
+//!
+//! See [`estimate_memory`] and [`RtFilter::set_max_memory_mb`](crate::RtFilter::set_max_memory_mb).
+
+use crate::device::OidnDevice;
+use crate::filter::{Quality, RtFilter};
+use crate::Error;
+
+/// Bytes of one RGB `f32` pixel.
+const PIXEL_BYTES: u64 = 12;
+/// Bytes per megabyte in `maxMemoryMB`.
+const MB: u64 = 1024 * 1024;
+
+/// Auxiliary images denoised together with color (OIDN requires albedo whenever normal is used).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AuxImages {
+    /// Color only.
+    #[default]
+    None,
+    /// Color and albedo.
+    Albedo,
+    /// Color, albedo and normal.
+    AlbedoNormal,
+}
+
+impl AuxImages {
+    /// Number of aux images.
+    pub(crate) fn count(self) -> u64 {
+        match self {
+            AuxImages::None => 0,
+            AuxImages::Albedo => 1,
+            AuxImages::AlbedoNormal => 2,
+        }
+    }
+}
+
+/// Memory of an RT denoise job run with a `maxMemoryMB` limit (see [`estimate_memory`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    /// The `maxMemoryMB` limit in bytes: the device scratch memory OIDN keeps the filter within
+    /// by tiling.
+    pub scratch_bytes: u64,
+    /// Input and output images (RGB `f32`) held in OIDN buffers by
+    /// [`ExecutionMode::Buffered`](crate::ExecutionMode::Buffered): color, output and the aux
+    /// images. In-place execution shares the color and output buffer, and zero-copy execution on
+    /// devices with system memory access allocates none, so this is an upper bound.
+    pub image_bytes: u64,
+    /// `tileAlignment` of the committed filter.
+    pub tile_alignment: u32,
+    /// `tileOverlap` of the committed filter.
+    pub tile_overlap: u32,
+}
+
+impl MemoryEstimate {
+    /// Scratch plus image bytes.
+    pub fn total_bytes(&self) -> u64 {
+        self.scratch_bytes + self.image_bytes
+    }
+
+    /// [`Self::total_bytes`] in megabytes, rounded up (the unit of `maxMemoryMB`).
+    pub fn total_mb(&self) -> u64 {
+        (self.total_bytes() + MB - 1) / MB
+    }
+}
+
+/// Bounds the memory an RT filter uses to denoise a `width` x `height` image on `device` with
+/// [`RtFilter::set_max_memory_mb`] set to `max_memory_mb`, so a scheduler can pack jobs.
+///
+/// Commits a throwaway filter with the given quality, aux images and limit, which checks the
+/// configuration on the device and reports its tiling. OIDN does not report the scratch size
+/// itself; instead it tiles the image (tiles aligned to `tileAlignment`, overlapping by
+/// `tileOverlap`) to keep scratch within `maxMemoryMB`, so the limit is the scratch bound. OIDN
+/// documents the limit as approximate, and a limit too small for a single minimal tile is
+/// exceeded, so leave some headroom. Run the job's filter with the same limit.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidDimensions`] if a dimension is zero, or the device's error if the
+/// filter cannot be created or committed.
+pub fn estimate_memory(
+    device: &OidnDevice,
+    width: u32,
+    height: u32,
+    quality: Quality,
+    aux: AuxImages,
+    max_memory_mb: u32,
+) -> Result<MemoryEstimate, Error> {
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidDimensions);
+    }
+    let mut filter = RtFilter::new(device)?;
+    filter
+        .set_quality(quality)
+        .set_max_memory_mb(Some(max_memory_mb));
+    let (tile_alignment, tile_overlap) =
+        filter.probe_tiling(aux != AuxImages::None, aux == AuxImages::AlbedoNormal)?;
+    let pixels = u64::from(width) * u64::from(height);
+    Ok(MemoryEstimate {
+        scratch_bytes: u64::from(max_memory_mb) * MB,
+        // Color, output and the aux images.
+        image_bytes: (2 + aux.count()) * pixels * PIXEL_BYTES,
+        tile_alignment,
+        tile_overlap,
+    })
+}
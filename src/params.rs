@@ -0,0 +1,353 @@
+//! Typed filter parameters: names, value types and read-back snapshots.
+//!
+//! See [`RtParam`], [`RtLightmapParam`], [`RtFilter::set_param`](crate::RtFilter::set_param) and
+//! [`RtFilter::params`](crate::RtFilter::params).
+
+use crate::filter::Quality;
+use crate::sys;
+use crate::Error;
+use std::ffi::CString;
+
+/// Value type of a filter parameter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParamType {
+    Bool,
+    Int,
+    Float,
+}
+
+impl ParamType {
+    /// The zero value of this type, which OIDN also reads for unknown parameters.
+    pub(crate) fn zero(self) -> ParamValue {
+        match self {
+            ParamType::Bool => ParamValue::Bool(false),
+            ParamType::Int => ParamValue::Int(0),
+            ParamType::Float => ParamValue::Float(0.0),
+        }
+    }
+}
+
+/// Value of a filter parameter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamValue {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+}
+
+impl ParamValue {
+    /// Type of this value.
+    pub fn value_type(self) -> ParamType {
+        match self {
+            ParamValue::Bool(_) => ParamType::Bool,
+            ParamValue::Int(_) => ParamType::Int,
+            ParamValue::Float(_) => ParamType::Float,
+        }
+    }
+
+    /// The value if it is a bool.
+    pub fn as_bool(self) -> Option<bool> {
+        match self {
+            ParamValue::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// The value if it is an int.
+    pub fn as_int(self) -> Option<i32> {
+        match self {
+            ParamValue::Int(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// The value if it is a float.
+    pub fn as_float(self) -> Option<f32> {
+        match self {
+            ParamValue::Float(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for ParamValue {
+    fn from(v: bool) -> Self {
+        ParamValue::Bool(v)
+    }
+}
+
+impl From<i32> for ParamValue {
+    fn from(v: i32) -> Self {
+        ParamValue::Int(v)
+    }
+}
+
+impl From<f32> for ParamValue {
+    fn from(v: f32) -> Self {
+        ParamValue::Float(v)
+    }
+}
+
+impl From<Quality> for ParamValue {
+    fn from(q: Quality) -> Self {
+        ParamValue::Int(q.to_raw() as i32)
+    }
+}
+
+/// Parameters of the RT filter (see [`RtFilter`](crate::RtFilter)).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RtParam {
+    /// `hdr` (bool).
+    Hdr,
+    /// `srgb` (bool).
+    Srgb,
+    /// `cleanAux` (bool).
+    CleanAux,
+    /// `inputScale` (float; NaN = auto).
+    InputScale,
+    /// `quality` (int; a [`Quality`]).
+    Quality,
+    /// `maxMemoryMB` (int; -1 = no limit).
+    MaxMemoryMb,
+    /// `tileAlignment` (int, read-only).
+    TileAlignment,
+    /// `tileOverlap` (int, read-only).
+    TileOverlap,
+}
+
+/// Parameters of the RTLightmap filter (see [`RtLightmapFilter`](crate::RtLightmapFilter)).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RtLightmapParam {
+    /// `directional` (bool).
+    Directional,
+    /// `inputScale` (float; NaN = auto).
+    InputScale,
+    /// `quality` (int; [`Quality::Default`] or [`Quality::High`]).
+    Quality,
+    /// `maxMemoryMB` (int; -1 = no limit).
+    MaxMemoryMb,
+    /// `tileAlignment` (int, read-only).
+    TileAlignment,
+    /// `tileOverlap` (int, read-only).
+    TileOverlap,
+}
+
+impl RtParam {
+    /// All RT filter parameters.
+    pub const ALL: [RtParam; 8] = [
+        RtParam::Hdr,
+        RtParam::Srgb,
+        RtParam::CleanAux,
+        RtParam::InputScale,
+        RtParam::Quality,
+        RtParam::MaxMemoryMb,
+        RtParam::TileAlignment,
+        RtParam::TileOverlap,
+    ];
+
+    /// OIDN parameter name.
+    pub fn name(self) -> &'static str {
+        match self {
+            RtParam::Hdr => "hdr",
+            RtParam::Srgb => "srgb",
+            RtParam::CleanAux => "cleanAux",
+            RtParam::InputScale => "inputScale",
+            RtParam::Quality => "quality",
+            RtParam::MaxMemoryMb => "maxMemoryMB",
+            RtParam::TileAlignment => "tileAlignment",
+            RtParam::TileOverlap => "tileOverlap",
+        }
+    }
+
+    /// Parameter with the given OIDN name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    /// Value type of the parameter.
+    pub fn value_type(self) -> ParamType {
+        match self {
+            RtParam::Hdr | RtParam::Srgb | RtParam::CleanAux => ParamType::Bool,
+            RtParam::InputScale => ParamType::Float,
+            RtParam::Quality
+            | RtParam::MaxMemoryMb
+            | RtParam::TileAlignment
+            | RtParam::TileOverlap => ParamType::Int,
+        }
+    }
+
+    /// Whether the parameter can only be read.
+    pub fn is_read_only(self) -> bool {
+        matches!(self, RtParam::TileAlignment | RtParam::TileOverlap)
+    }
+}
+
+impl RtLightmapParam {
+    /// All RTLightmap filter parameters.
+    pub const ALL: [RtLightmapParam; 6] = [
+        RtLightmapParam::Directional,
+        RtLightmapParam::InputScale,
+        RtLightmapParam::Quality,
+        RtLightmapParam::MaxMemoryMb,
+        RtLightmapParam::TileAlignment,
+        RtLightmapParam::TileOverlap,
+    ];
+
+    /// OIDN parameter name.
+    pub fn name(self) -> &'static str {
+        match self {
+            RtLightmapParam::Directional => "directional",
+            RtLightmapParam::InputScale => "inputScale",
+            RtLightmapParam::Quality => "quality",
+            RtLightmapParam::MaxMemoryMb => "maxMemoryMB",
+            RtLightmapParam::TileAlignment => "tileAlignment",
+            RtLightmapParam::TileOverlap => "tileOverlap",
+        }
+    }
+
+    /// Parameter with the given OIDN name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    /// Value type of the parameter.
+    pub fn value_type(self) -> ParamType {
+        match self {
+            RtLightmapParam::Directional => ParamType::Bool,
+            RtLightmapParam::InputScale => ParamType::Float,
+            RtLightmapParam::Quality
+            | RtLightmapParam::MaxMemoryMb
+            | RtLightmapParam::TileAlignment
+            | RtLightmapParam::TileOverlap => ParamType::Int,
+        }
+    }
+
+    /// Whether the parameter can only be read.
+    pub fn is_read_only(self) -> bool {
+        matches!(
+            self,
+            RtLightmapParam::TileAlignment | RtLightmapParam::TileOverlap
+        )
+    }
+}
+
+/// RT filter parameters as read back from OIDN (see [`RtFilter::params`](crate::RtFilter::params)).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RtParams {
+    pub hdr: bool,
+    pub srgb: bool,
+    pub clean_aux: bool,
+    pub input_scale: f32,
+    /// `None` if OIDN reports a value outside [`Quality`].
+    pub quality: Option<Quality>,
+    /// `None` = no limit.
+    pub max_memory_mb: Option<u32>,
+    pub tile_alignment: u32,
+    pub tile_overlap: u32,
+}
+
+/// RTLightmap filter parameters as read back from OIDN
+/// (see [`RtLightmapFilter::params`](crate::RtLightmapFilter::params)).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RtLightmapParams {
+    pub directional: bool,
+    pub input_scale: f32,
+    /// `None` if OIDN reports a value outside [`Quality`].
+    pub quality: Option<Quality>,
+    /// `None` = no limit.
+    pub max_memory_mb: Option<u32>,
+    pub tile_alignment: u32,
+    pub tile_overlap: u32,
+}
+
+/// Looks up a parameter of a known filter type: `Ok(Some((type, read_only)))`, or `Ok(None)` for
+/// filter types without a schema.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidParameter`] if `name` is not a parameter of an RT/RTLightmap filter.
+pub(crate) fn lookup(type_name: &str, name: &str) -> Result<Option<(ParamType, bool)>, Error> {
+    let found = match type_name {
+        "RT" => RtParam::from_name(name).map(|p| (p.value_type(), p.is_read_only())),
+        "RTLightmap" => {
+            RtLightmapParam::from_name(name).map(|p| (p.value_type(), p.is_read_only()))
+        }
+        _ => return Ok(None),
+    };
+    found
+        .map(Some)
+        .ok_or_else(|| Error::InvalidParameter(name.to_owned()))
+}
+
+/// Checks that `value` has the parameter's type and the parameter is writable.
+pub(crate) fn check_value(
+    name: &str,
+    value_type: ParamType,
+    read_only: bool,
+    value: ParamValue,
+) -> Result<(), Error> {
+    if read_only {
+        return Err(Error::ReadOnlyParameter(name.to_owned()));
+    }
+    if value.value_type() != value_type {
+        return Err(Error::InvalidParameterValue(format!(
+            "`{}` expects {:?}, got {:?}",
+            name, value_type, value
+        )));
+    }
+    Ok(())
+}
+
+/// Converts a parameter name for the C API.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidParameter`] if `name` contains a NUL byte.
+pub(crate) fn c_name(name: &str) -> Result<CString, Error> {
+    CString::new(name).map_err(|_| Error::InvalidParameter(name.escape_default().to_string()))
+}
+
+/// Reads a parameter of `value_type` from a filter.
+pub(crate) fn get_filter_param(
+    filter: sys::OIDNFilter,
+    name: &CString,
+    value_type: ParamType,
+) -> ParamValue {
+    let name = name.as_ptr();
+    unsafe {
+        match value_type {
+            ParamType::Bool => ParamValue::Bool(sys::oidnGetFilterBool(filter, name)),
+            ParamType::Int => ParamValue::Int(sys::oidnGetFilterInt(filter, name)),
+            ParamType::Float => ParamValue::Float(sys::oidnGetFilterFloat(filter, name)),
+        }
+    }
+}
+
+/// Writes a parameter to a filter (the type is taken from `value`).
+pub(crate) fn set_filter_param(filter: sys::OIDNFilter, name: &CString, value: ParamValue) {
+    let name = name.as_ptr();
+    unsafe {
+        match value {
+            ParamValue::Bool(v) => sys::oidnSetFilterBool(filter, name, v),
+            ParamValue::Int(v) => sys::oidnSetFilterInt(filter, name, v),
+            ParamValue::Float(v) => sys::oidnSetFilterFloat(filter, name, v),
+        }
+    }
+}
+
+/// `quality` int value as a [`Quality`].
+pub(crate) fn quality_from_value(value: ParamValue) -> Result<Quality, Error> {
+    value.as_int().and_then(Quality::from_raw).ok_or_else(|| {
+        Error::InvalidParameterValue(format!("`quality` does not accept {:?}", value))
+    })
+}
+
+/// `maxMemoryMB` int value (negative = no limit).
+pub(crate) fn max_memory_from_raw(value: i32) -> Option<u32> {
+    u32::try_from(value).ok()
+}
+
+/// `maxMemoryMB` value for OIDN (-1 = no limit).
+pub(crate) fn max_memory_to_raw(max_memory_mb: Option<u32>) -> i32 {
+    max_memory_mb.map_or(-1, |mb| mb.min(i32::MAX as u32) as i32)
+}
@@ -0,0 +1,216 @@
+//! Handle for an asynchronous filter execution.
+//!
+//! See [`PendingExecution`], returned by [`Filter::execute_async`](crate::Filter::execute_async)
+//! and [`RtFilter::execute_image_async`](crate::RtFilter::execute_image_async).
+
+use crate::device::OidnDevice;
+use crate::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+type Finish<'a> = Box<dyn FnOnce() -> Result<(), Error> + Send + 'a>;
+
+/// How long a device's waiter thread waits for a new execution before exiting (and releasing
+/// its handle to the device).
+const WAITER_IDLE: Duration = Duration::from_secs(1);
+
+/// Completion flag set by the device's waiter thread.
+#[derive(Default)]
+struct SyncSignal {
+    done: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl SyncSignal {
+    fn set_done(&self) {
+        self.done.store(true, Ordering::Release);
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Signals of executions waiting for a device's waiter thread, and the signal that more arrived.
+type Waiting = (Mutex<Vec<Arc<SyncSignal>>>, Condvar);
+
+/// The running waiter threads, one per device. Lock before a thread's list.
+static WAITERS: Mutex<Vec<(OidnDevice, Arc<Waiting>)>> = Mutex::new(Vec::new());
+
+/// Sets `signal` once `device` has finished the work queued so far, on the device's waiter
+/// thread (started if the device has none).
+fn wait_in_background(device: &OidnDevice, signal: Arc<SyncSignal>) {
+    let mut waiters = WAITERS.lock().unwrap();
+    if let Some((_, waiting)) = waiters.iter().find(|(other, _)| other.raw() == device.raw()) {
+        waiting.0.lock().unwrap().push(signal);
+        waiting.1.notify_one();
+        return;
+    }
+    let waiting = Arc::new((Mutex::new(vec![signal]), Condvar::new()));
+    waiters.push((device.clone(), Arc::clone(&waiting)));
+    let device = device.clone();
+    std::thread::spawn(move || waiter_thread(device, waiting));
+}
+
+/// Syncs `device` for each batch of waiting executions; exits (unregistering itself) after
+/// [`WAITER_IDLE`] without any.
+fn waiter_thread(device: OidnDevice, waiting: Arc<Waiting>) {
+    loop {
+        let signals = waiting.0.lock().unwrap();
+        let (mut signals, wait) = waiting
+            .1
+            .wait_timeout_while(signals, WAITER_IDLE, |signals| signals.is_empty())
+            .unwrap();
+        if wait.timed_out() {
+            // Re-check under the registry lock, so an execution cannot register with an exiting
+            // thread.
+            drop(signals);
+            let mut waiters = WAITERS.lock().unwrap();
+            if waiting.0.lock().unwrap().is_empty() {
+                waiters.retain(|(_, other)| !Arc::ptr_eq(other, &waiting));
+                return;
+            }
+            continue;
+        }
+        // Every execution in the batch was launched before this sync, so it waits for all.
+        let batch = std::mem::take(&mut *signals);
+        drop(signals);
+        device.sync();
+        // Results are collected by each execution's `finish` on its own thread; errors queued
+        // for this thread would only pile up while it lives.
+        drop(device.take_error());
+        for signal in batch {
+            signal.set_done();
+        }
+    }
+}
+
+/// An in-flight filter execution.
+///
+/// Borrows the filter and the images passed to it for `'a`, so outputs cannot be read and inputs
+/// cannot be freed or rebound while OIDN may still be working on them. Complete it with
+/// [`Self::wait`] or by `.await`ing it; [`Self::is_done`] polls without blocking.
+///
+/// Completion waits for the whole device (`oidnSyncDevice`), so work queued on the same device
+/// after this execution is waited on as well. Dropping the handle without waiting blocks until
+/// the device is idle.
+///
+/// Leaking the handle (e.g. with [`std::mem::forget`]) is safe but loses the result: async
+/// executions of [`RtFilter`](crate::RtFilter) and [`RtLightmapFilter`](crate::RtLightmapFilter)
+/// only run on the filter's own OIDN buffers (never on borrowed slices), so OIDN cannot touch
+/// the borrowed images after the borrow ends, and the output is then not written. The filters
+/// wait for the device before they change their buffers, weights or callbacks again.
+#[must_use = "outputs are not ready until the execution is waited on"]
+pub struct PendingExecution<'a> {
+    device: OidnDevice,
+    /// Reads back results and collects errors; `None` once completed.
+    finish: Option<Finish<'a>>,
+    /// Created on the first `is_done`/`poll`, when it is handed to the device's waiter thread.
+    signal: OnceLock<Arc<SyncSignal>>,
+}
+
+impl std::fmt::Debug for PendingExecution<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingExecution")
+            .field("completed", &self.finish.is_none())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> PendingExecution<'a> {
+    /// `finish` runs after the device is synced (e.g. output readback, error collection).
+    pub(crate) fn new(device: OidnDevice, finish: Finish<'a>) -> Self {
+        Self {
+            device,
+            finish: Some(finish),
+            signal: OnceLock::new(),
+        }
+    }
+
+    /// Blocks until the execution has finished and returns its result.
+    ///
+    /// # Errors
+    ///
+    /// Returns the device's error if execution failed (e.g. [`Error::Cancelled`]).
+    pub fn wait(mut self) -> Result<(), Error> {
+        self.complete()
+    }
+
+    /// Whether the execution has finished, without blocking. Call [`Self::wait`] (which then
+    /// returns immediately) to get the result.
+    ///
+    /// The first call hands the execution to the device's waiter thread, which syncs the device
+    /// in the background. There is one such thread per device, shared by all its pending
+    /// executions; it exits once none has been pending for a second.
+    pub fn is_done(&self) -> bool {
+        self.finish.is_none() || self.signal().done.load(Ordering::Acquire)
+    }
+
+    fn signal(&self) -> &Arc<SyncSignal> {
+        self.signal.get_or_init(|| {
+            let signal = Arc::new(SyncSignal::default());
+            wait_in_background(&self.device, Arc::clone(&signal));
+            signal
+        })
+    }
+
+    fn complete(&mut self) -> Result<(), Error> {
+        let Some(finish) = self.finish.take() else {
+            return Ok(());
+        };
+        self.device.sync();
+        finish()
+    }
+}
+
+impl Future for PendingExecution<'_> {
+    type Output = Result<(), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.finish.is_none() {
+            return Poll::Ready(Ok(()));
+        }
+        let signal = Arc::clone(this.signal());
+        // Register before checking, so a completion in between still wakes this task.
+        *signal.waker.lock().unwrap() = Some(cx.waker().clone());
+        if signal.done.load(Ordering::Acquire) {
+            Poll::Ready(this.complete())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for PendingExecution<'_> {
+    fn drop(&mut self) {
+        // The borrowed images must not be released while OIDN may still access them.
+        let _ = self.complete();
+    }
+}
+
+/// Whether a filter's async execution may still be running because its [`PendingExecution`] was
+/// leaked instead of completed.
+#[derive(Debug, Default)]
+pub(crate) struct InFlight(bool);
+
+impl InFlight {
+    /// Marks an execution as started; [`Self::finish`] clears it when the handle completes.
+    pub(crate) fn start(&mut self) {
+        self.0 = true;
+    }
+
+    pub(crate) fn finish(&mut self) {
+        self.0 = false;
+    }
+
+    /// Waits for a leaked execution, before anything it may still use is changed or freed.
+    pub(crate) fn settle(&mut self, device: &OidnDevice) {
+        if std::mem::take(&mut self.0) {
+            device.sync();
+        }
+    }
+}
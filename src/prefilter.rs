@@ -0,0 +1,304 @@
+//! Highest-quality RT denoising: prefilter noisy albedo/normal, then denoise color with `cleanAux`.
+//!
+//! See [`PrefilteredRtDenoiser`].
+
+use crate::device::OidnDevice;
+use crate::filter::{ExecutionMode, Quality, RtFilter};
+use crate::image::{ImageMut, ImageRef};
+use crate::sys;
+use crate::wgpu_integration::{
+    read_texture_to_rgba_f32, upload_rgba_to_texture, DenoiseTextureFormat,
+};
+use crate::Error;
+
+/// Three RT filters that implement OIDN's prefiltering pipeline: noisy albedo and normal AOVs are
+/// denoised first, then color is denoised with `cleanAux = true` using the cleaned AOVs.
+///
+/// The filters and the cleaned AOV buffers are kept across frames. Turn prefiltering off per AOV
+/// with [`Self::set_prefilter_albedo`] / [`Self::set_prefilter_normal`] when that AOV is already
+/// noise-free (e.g. rasterized); it is then passed to the color filter as is.
+#[derive(Debug)]
+pub struct PrefilteredRtDenoiser {
+    color: RtFilter,
+    albedo: RtFilter,
+    normal: RtFilter,
+    width: u32,
+    height: u32,
+    /// Color encoding set with [`Self::set_hdr`] / [`Self::set_srgb`]; `None` = from the texture
+    /// format, or linear HDR for slices.
+    hdr: Option<bool>,
+    srgb: Option<bool>,
+    prefilter_albedo: bool,
+    prefilter_normal: bool,
+    clean_albedo: Vec<f32>,
+    clean_normal: Vec<f32>,
+    /// Whether `clean_albedo` / `clean_normal` hold the AOVs of the last execution.
+    albedo_cleaned: bool,
+    normal_cleaned: bool,
+}
+
+impl PrefilteredRtDenoiser {
+    /// Creates the color, albedo and normal filters on `device`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::FilterCreationFailed`] or the device's error if a filter cannot be created.
+    pub fn new(device: &OidnDevice) -> Result<Self, Error> {
+        let mut color = RtFilter::new(device)?;
+        color.set_clean_aux(true);
+        // Auxiliary images are not HDR color; the flag only applies to color inputs.
+        let mut albedo = RtFilter::new(device)?;
+        albedo.set_hdr(false);
+        let mut normal = RtFilter::new(device)?;
+        normal.set_hdr(false);
+        Ok(Self {
+            color,
+            albedo,
+            normal,
+            width: 0,
+            height: 0,
+            hdr: None,
+            srgb: None,
+            prefilter_albedo: true,
+            prefilter_normal: true,
+            clean_albedo: Vec::new(),
+            clean_normal: Vec::new(),
+            albedo_cleaned: false,
+            normal_cleaned: false,
+        })
+    }
+
+    /// Image dimensions (must be set before execute).
+    pub fn set_dimensions(&mut self, width: u32, height: u32) -> &mut Self {
+        self.width = width;
+        self.height = height;
+        for filter in self.filters_mut() {
+            filter.set_dimensions(width, height);
+        }
+        self
+    }
+
+    /// Underlying device (for sync/error).
+    pub fn device(&self) -> &OidnDevice {
+        self.color.device()
+    }
+
+    /// HDR color input (see [`RtFilter::set_hdr`]). Default: true for slices, from the texture
+    /// format ([`DenoiseTextureFormat::is_hdr`]) for [`Self::denoise_texture`].
+    pub fn set_hdr(&mut self, hdr: bool) -> &mut Self {
+        self.hdr = Some(hdr);
+        self
+    }
+
+    /// sRGB-encoded LDR color input (see [`RtFilter::set_srgb`]). Default: false for slices, from
+    /// the texture format ([`DenoiseTextureFormat::is_srgb`]) for [`Self::denoise_texture`].
+    pub fn set_srgb(&mut self, srgb: bool) -> &mut Self {
+        self.srgb = Some(srgb);
+        self
+    }
+
+    /// Input scale for HDR color (see [`RtFilter::set_input_scale`]).
+    pub fn set_input_scale(&mut self, scale: f32) -> &mut Self {
+        self.color.set_input_scale(scale);
+        self
+    }
+
+    /// Quality of all three filters. Default: [`Quality::Default`].
+    pub fn set_quality(&mut self, quality: Quality) -> &mut Self {
+        for filter in self.filters_mut() {
+            filter.set_quality(quality);
+        }
+        self
+    }
+
+    /// Execution mode of all three filters (see [`ExecutionMode`]).
+    pub fn set_execution_mode(&mut self, mode: ExecutionMode) -> &mut Self {
+        for filter in self.filters_mut() {
+            filter.set_execution_mode(mode);
+        }
+        self
+    }
+
+    /// Whether to denoise the albedo AOV before using it. Default: true.
+    pub fn set_prefilter_albedo(&mut self, prefilter: bool) -> &mut Self {
+        self.prefilter_albedo = prefilter;
+        self
+    }
+
+    /// Whether to denoise the normal AOV before using it. Default: true.
+    pub fn set_prefilter_normal(&mut self, prefilter: bool) -> &mut Self {
+        self.prefilter_normal = prefilter;
+        self
+    }
+
+    /// Cleaned albedo (`width * height * 3` floats) from the last execution, or `None` if albedo
+    /// was not prefiltered.
+    pub fn clean_albedo(&self) -> Option<&[f32]> {
+        self.albedo_cleaned.then_some(&self.clean_albedo[..])
+    }
+
+    /// Cleaned normal from the last execution, or `None` if no normal was prefiltered.
+    pub fn clean_normal(&self) -> Option<&[f32]> {
+        self.normal_cleaned.then_some(&self.clean_normal[..])
+    }
+
+    fn filters_mut(&mut self) -> [&mut RtFilter; 3] {
+        [&mut self.color, &mut self.albedo, &mut self.normal]
+    }
+
+    /// Denoises color in place. See [`Self::execute`].
+    pub fn execute_in_place(
+        &mut self,
+        color: &mut [f32],
+        albedo: &[f32],
+        normal: Option<&[f32]>,
+    ) -> Result<(), Error> {
+        self.execute(None, color, albedo, normal)
+    }
+
+    /// Prefilters the AOVs (as configured), then denoises `color` (or `output` in place if `color`
+    /// is `None`) into `output`. All slices are `width * height * 3` floats (RGB).
+    ///
+    /// OIDN requires albedo whenever normal is used, so albedo is mandatory here.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDimensions`] if dimensions are unset or slice sizes do not match,
+    /// or an OIDN error from the device.
+    pub fn execute(
+        &mut self,
+        color: Option<&[f32]>,
+        output: &mut [f32],
+        albedo: &[f32],
+        normal: Option<&[f32]>,
+    ) -> Result<(), Error> {
+        self.set_color_encoding(None);
+        self.run(color, output, albedo, normal)
+    }
+
+    /// Sets the color filter's `hdr`/`srgb` to the values set on this denoiser, otherwise to
+    /// those of `format` (like [`DenoiseOptions`](crate::DenoiseOptions)), or linear HDR without
+    /// a format.
+    fn set_color_encoding(&mut self, format: Option<DenoiseTextureFormat>) {
+        let hdr = self.hdr.or(format.map(DenoiseTextureFormat::is_hdr)).unwrap_or(true);
+        let srgb = self.srgb.or(format.map(DenoiseTextureFormat::is_srgb)).unwrap_or(false);
+        self.color.set_hdr(hdr).set_srgb(srgb);
+    }
+
+    /// [`Self::execute`] with the color encoding already set.
+    fn run(
+        &mut self,
+        color: Option<&[f32]>,
+        output: &mut [f32],
+        albedo: &[f32],
+        normal: Option<&[f32]>,
+    ) -> Result<(), Error> {
+        let (w, h) = (self.width, self.height);
+        if w == 0 || h == 0 {
+            return Err(Error::InvalidDimensions);
+        }
+        let n = w as usize * h as usize * 3;
+        if albedo.len() != n || normal.is_some_and(|normal| normal.len() != n) {
+            return Err(Error::InvalidDimensions);
+        }
+        self.albedo_cleaned = false;
+        self.normal_cleaned = false;
+        let format = sys::OIDNFormat::Float3;
+
+        if self.prefilter_albedo {
+            self.clean_albedo.resize(n, 0.0);
+            let input = ImageRef::from_f32(albedo, format, w, h)?;
+            let mut clean = ImageMut::from_f32(&mut self.clean_albedo, format, w, h)?;
+            self.albedo.prefilter_aux(Some(input), None, &mut clean)?;
+            self.albedo_cleaned = true;
+        }
+        if let (Some(normal), true) = (normal, self.prefilter_normal) {
+            self.clean_normal.resize(n, 0.0);
+            let input = ImageRef::from_f32(normal, format, w, h)?;
+            let mut clean = ImageMut::from_f32(&mut self.clean_normal, format, w, h)?;
+            self.normal.prefilter_aux(None, Some(input), &mut clean)?;
+            self.normal_cleaned = true;
+        }
+
+        let albedo = if self.albedo_cleaned {
+            &self.clean_albedo[..]
+        } else {
+            albedo
+        };
+        let normal = if self.normal_cleaned {
+            Some(&self.clean_normal[..])
+        } else {
+            normal
+        };
+        self.color
+            .execute_with_aux(color, output, Some(albedo), normal)
+    }
+
+    /// Denoises a wgpu color texture with albedo and optional normal textures (same size and
+    /// format) through the prefiltering pipeline, like
+    /// [`denoise_texture_with_aux`](crate::denoise_texture_with_aux).
+    ///
+    /// Sets the dimensions from the texture; the filters and buffers are reused while they stay
+    /// the same. Unless set with [`Self::set_hdr`] / [`Self::set_srgb`], the color filter's
+    /// `hdr`/`srgb` flags follow `format`, as in [`DenoiseOptions`](crate::DenoiseOptions). The
+    /// cleaned AOVs are available from [`Self::clean_albedo`] / [`Self::clean_normal`]
+    /// afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDimensions`] if texture sizes or array layers are incompatible,
+    /// [`Error::UnsupportedFormat`] if `normal` is passed and `format` cannot hold its negative
+    /// values (see [`DenoiseTextureFormat::is_signed`]), [`Error::BufferMapFailed`] if readback
+    /// fails, or an OIDN error.
+    #[allow(clippy::too_many_arguments)]
+    pub fn denoise_texture(
+        &mut self,
+        wgpu_device: &wgpu::Device,
+        wgpu_queue: &wgpu::Queue,
+        input: &wgpu::Texture,
+        output: &wgpu::Texture,
+        format: DenoiseTextureFormat,
+        albedo: &wgpu::Texture,
+        normal: Option<&wgpu::Texture>,
+    ) -> Result<(), Error> {
+        let size = input.size();
+        let same_size = |t: &wgpu::Texture| {
+            let s = t.size();
+            s.width == size.width && s.height == size.height && s.depth_or_array_layers == 1
+        };
+        if !same_size(input)
+            || !same_size(output)
+            || !same_size(albedo)
+            || normal.is_some_and(|t| !same_size(t))
+        {
+            return Err(Error::InvalidDimensions);
+        }
+        if normal.is_some() && !format.is_signed() {
+            return Err(Error::UnsupportedFormat);
+        }
+
+        let (mut color_rgb, alpha) =
+            read_texture_to_rgba_f32(wgpu_device, wgpu_queue, input, format)?;
+        let (albedo_rgb, _) = read_texture_to_rgba_f32(wgpu_device, wgpu_queue, albedo, format)?;
+        let normal_rgb = normal
+            .map(|t| {
+                read_texture_to_rgba_f32(wgpu_device, wgpu_queue, t, format).map(|(rgb, _)| rgb)
+            })
+            .transpose()?;
+
+        self.set_dimensions(size.width, size.height);
+        self.set_color_encoding(Some(format));
+        self.run(None, &mut color_rgb, &albedo_rgb, normal_rgb.as_deref())?;
+
+        upload_rgba_to_texture(
+            wgpu_device,
+            wgpu_queue,
+            output,
+            format,
+            size.width,
+            size.height,
+            &color_rgb,
+            &alpha,
+        )
+    }
+}
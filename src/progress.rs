@@ -0,0 +1,118 @@
+//! Safe progress monitoring and cancellation for filter execution.
+//!
+//! See [`CancellationToken`] and `set_progress_monitor` on [`RtFilter`](crate::RtFilter),
+//! [`RtLightmapFilter`](crate::RtLightmapFilter) and [`Filter`](crate::Filter).
+
+use crate::sys;
+use std::ffi::c_void;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Cloneable flag that cancels running filter executions from any thread.
+///
+/// Attach it to a filter with `set_cancellation_token`; after [`Self::cancel`] the filter's next
+/// progress check aborts the execution, which then fails with [`Error::Cancelled`](crate::Error::Cancelled).
+/// The token stays cancelled (also failing later executions) until [`Self::reset`].
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation of executions using this token (or a clone of it).
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    /// Whether [`Self::cancel`] was called since creation or the last [`Self::reset`].
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Clears the cancellation request so the token can be reused for the next execution.
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::Release);
+    }
+}
+
+type ProgressCallback = dyn FnMut(f64) -> bool + Send + 'static;
+
+/// Callback and token invoked by OIDN through [`progress_trampoline`].
+struct ProgressState {
+    callback: Option<Mutex<Box<ProgressCallback>>>,
+    token: Option<CancellationToken>,
+}
+
+/// Safe progress monitor of one filter. Keeps the state OIDN points to alive and boxed (stable
+/// address) until it is replaced or the filter is dropped.
+#[derive(Default)]
+pub(crate) struct ProgressMonitor {
+    state: Option<Box<ProgressState>>,
+}
+
+impl ProgressMonitor {
+    pub(crate) fn set_callback(
+        &mut self,
+        filter: sys::OIDNFilter,
+        callback: Box<ProgressCallback>,
+    ) {
+        let old = self.state.take();
+        let token = old.as_ref().and_then(|s| s.token.clone());
+        self.install(filter, Some(Mutex::new(callback)), token);
+    }
+
+    pub(crate) fn set_token(&mut self, filter: sys::OIDNFilter, token: CancellationToken) {
+        let mut old = self.state.take();
+        let callback = old.as_mut().and_then(|s| s.callback.take());
+        self.install(filter, callback, Some(token));
+    }
+
+    pub(crate) fn clear(&mut self, filter: sys::OIDNFilter) {
+        if self.state.is_some() {
+            unsafe {
+                sys::oidnSetFilterProgressMonitorFunction(filter, None, std::ptr::null_mut())
+            };
+            self.state = None;
+        }
+    }
+
+    fn install(
+        &mut self,
+        filter: sys::OIDNFilter,
+        callback: Option<Mutex<Box<ProgressCallback>>>,
+        token: Option<CancellationToken>,
+    ) {
+        let state = Box::new(ProgressState { callback, token });
+        let user_ptr = &*state as *const ProgressState as *mut c_void;
+        // Callers drop the previous state only after OIDN points at the new one.
+        unsafe {
+            sys::oidnSetFilterProgressMonitorFunction(filter, Some(progress_trampoline), user_ptr)
+        };
+        self.state = Some(state);
+    }
+}
+
+unsafe extern "C" fn progress_trampoline(user_ptr: *mut c_void, n: f64) -> bool {
+    if user_ptr.is_null() {
+        return true;
+    }
+    let state = &*(user_ptr as *const ProgressState);
+    if state.token.as_ref().is_some_and(|t| t.is_cancelled()) {
+        return false;
+    }
+    let Some(callback) = &state.callback else {
+        return true;
+    };
+    // A panic must not unwind into OIDN; treat it as a cancellation request.
+    catch_unwind(AssertUnwindSafe(|| match callback.lock() {
+        Ok(mut f) => f(n),
+        Err(_) => false,
+    }))
+    .unwrap_or(false)
+}
@@ -1,6 +1,8 @@
 //! Unit tests (require OIDN to be built and linked).
 
-use crate::{OidnDevice, Quality, RtFilter, RtLightmapFilter};
+use crate::{
+    physical_devices, OidnDevice, OidnDeviceType, PciAddress, Quality, RtFilter, RtLightmapFilter,
+};
 
 #[test]
 fn test_rt_filter_dimensions_and_execute_in_place() {
@@ -36,7 +38,7 @@ fn test_rt_filter_invalid_dimensions() {
 #[test]
 fn test_rt_filter_zero_dimensions() {
     let device = OidnDevice::new().expect("OIDN device");
-    let filter = RtFilter::new(&device).expect("RT filter");
+    let mut filter = RtFilter::new(&device).expect("RT filter");
     // dimensions left at default 0,0
     let mut output = vec![0.0f32; 3];
     let err = filter.execute(None, &mut output).expect_err("should error");
@@ -56,3 +58,1916 @@ fn test_rt_lightmap_filter_new_or_unsupported() {
         Err(e) => assert!(matches!(e, crate::Error::FilterCreationFailed)),
     }
 }
+
+#[test]
+fn test_device_builder_applies_pre_commit_params() {
+    let device = OidnDevice::builder()
+        .device_type(OidnDeviceType::Cpu)
+        .num_threads(2)
+        .set_affinity(false)
+        .verbose(0)
+        .build()
+        .expect("OIDN device via builder");
+    assert_eq!(device.get_int("numThreads"), 2);
+    assert!(!device.get_bool("setAffinity"));
+    let mut filter = RtFilter::new(&device).expect("RT filter");
+    filter.set_dimensions(2, 2);
+    let mut color = vec![0.5f32; 2 * 2 * 3];
+    filter.execute_in_place(&mut color).expect("execute_in_place");
+}
+
+#[test]
+fn test_physical_devices_owned_info() {
+    let devices = physical_devices();
+    assert_eq!(devices.len() as i32, crate::num_physical_devices());
+    for (i, info) in devices.iter().enumerate() {
+        assert_eq!(info.id, i as i32);
+        assert!(info.to_string().starts_with(&format!("[{}] ", i)));
+    }
+    if let Some(info) = devices.first() {
+        let device = info.create_device().expect("device from physical device");
+        assert!(device.take_error().is_none());
+    }
+}
+
+#[test]
+fn test_pci_address_display() {
+    let pci = PciAddress {
+        domain: 0,
+        bus: 0x2b,
+        device: 0,
+        function: 1,
+    };
+    assert_eq!(pci.to_string(), "0000:2b:00.1");
+}
+
+fn adapter_info(name: &str, vendor: u32, device_type: wgpu::DeviceType) -> wgpu::AdapterInfo {
+    wgpu::AdapterInfo {
+        name: name.to_string(),
+        vendor,
+        device: 0,
+        device_type,
+        driver: String::new(),
+        driver_info: String::new(),
+        backend: wgpu::Backend::Vulkan,
+    }
+}
+
+fn physical_device(id: i32, name: &str, device_type: OidnDeviceType) -> crate::PhysicalDeviceInfo {
+    crate::PhysicalDeviceInfo {
+        id,
+        name: name.to_string(),
+        device_type: Some(device_type),
+        uuid: None,
+        luid: None,
+        node_mask: None,
+        pci_address: None,
+    }
+}
+
+#[test]
+fn test_adapter_match_strategies() {
+    use crate::adapter::{match_adapter, AdapterTarget};
+    use crate::{AdapterMatch, AdapterMatchOptions};
+
+    let devices = vec![
+        physical_device(0, "Intel(R) Core(TM) i9", OidnDeviceType::Cpu),
+        physical_device(1, "NVIDIA GeForce RTX 4090", OidnDeviceType::Cuda),
+        physical_device(2, "NVIDIA  GeForce RTX 3060", OidnDeviceType::Cuda),
+        physical_device(3, "AMD Radeon RX 7900 XTX", OidnDeviceType::Hip),
+    ];
+    let opts = AdapterMatchOptions::default();
+
+    let sw = adapter_info("llvmpipe (LLVM 17.0.6, 256 bits)", 0x10005, wgpu::DeviceType::Cpu);
+    assert_eq!(
+        match_adapter(&sw, &opts, &devices),
+        Some((AdapterTarget::Cpu, AdapterMatch::Software))
+    );
+
+    let by_name = adapter_info("NVIDIA GeForce RTX 3060", 0x10de, wgpu::DeviceType::DiscreteGpu);
+    assert_eq!(
+        match_adapter(&by_name, &opts, &devices),
+        Some((AdapterTarget::Physical(2), AdapterMatch::Name))
+    );
+
+    let by_vendor = adapter_info("Radeon RX 7900 XTX (RADV NAVI31)", 0x1002, wgpu::DeviceType::DiscreteGpu);
+    assert_eq!(
+        match_adapter(&by_vendor, &opts, &devices),
+        Some((AdapterTarget::Physical(3), AdapterMatch::Vendor))
+    );
+
+    // 0x1022 is AMD's CPU/chipset vendor ID, not one wgpu reports for Radeon GPUs.
+    let amd_chipset = adapter_info("Radeon Graphics", 0x1022, wgpu::DeviceType::IntegratedGpu);
+    assert_eq!(match_adapter(&amd_chipset, &opts, &devices), None);
+
+    // Two CUDA devices: vendor alone is ambiguous.
+    let ambiguous = adapter_info("NVIDIA RTX A6000", 0x10de, wgpu::DeviceType::DiscreteGpu);
+    assert_eq!(match_adapter(&ambiguous, &opts, &devices), None);
+
+    let mut with_pci = devices.clone();
+    let pci = PciAddress {
+        domain: 0,
+        bus: 1,
+        device: 0,
+        function: 0,
+    };
+    with_pci[1].pci_address = Some(pci);
+    let opts_pci = AdapterMatchOptions {
+        pci_address: Some(pci),
+        ..Default::default()
+    };
+    assert_eq!(
+        match_adapter(&ambiguous, &opts_pci, &with_pci),
+        Some((AdapterTarget::Physical(1), AdapterMatch::PciAddress))
+    );
+}
+
+#[test]
+fn test_for_wgpu_adapter_software_is_cpu() {
+    let sw = adapter_info("lavapipe", 0x10005, wgpu::DeviceType::Cpu);
+    let (device, m) = OidnDevice::for_wgpu_adapter(&sw).expect("device for software adapter");
+    assert_eq!(m, crate::AdapterMatch::Software);
+    assert!(device.take_error().is_none());
+}
+
+#[test]
+fn test_error_handler_closure_receives_errors() {
+    use std::sync::{Arc, Mutex};
+
+    let device = OidnDevice::new().expect("OIDN device");
+    let seen: Arc<Mutex<Vec<(crate::ErrorCode, String)>>> = Arc::default();
+    let sink = seen.clone();
+    device.set_error_handler(move |code, msg| sink.lock().unwrap().push((code, msg.to_string())));
+
+    let clone = device.clone();
+    drop(device);
+    assert!(crate::Filter::new(&clone, "NoSuchFilter").is_err());
+    {
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].0, crate::ErrorCode::InvalidArgument);
+    }
+
+    // A panicking handler must not unwind across the FFI boundary.
+    clone.set_error_handler(|_, _| panic!("handler panic"));
+    assert!(crate::Filter::new(&clone, "NoSuchFilter").is_err());
+
+    clone.clear_error_handler();
+    assert!(crate::Filter::new(&clone, "NoSuchFilter").is_err());
+    assert_eq!(seen.lock().unwrap().len(), 1);
+}
+
+fn denoise_small(device: &OidnDevice) {
+    let mut filter = RtFilter::new(device).expect("RT filter");
+    filter.set_dimensions(4, 4);
+    let mut color = vec![0.25f32; 4 * 4 * 3];
+    filter.execute_in_place(&mut color).expect("execute_in_place");
+}
+
+#[test]
+fn test_device_clones_across_threads() {
+    let device = OidnDevice::new().expect("OIDN device");
+    let mut filter = RtFilter::new(&device).expect("RT filter");
+    filter.set_dimensions(4, 4);
+
+    // Clones are created on this thread and dropped on worker threads in arbitrary order.
+    let workers: Vec<_> = (0..4)
+        .map(|i| {
+            let clone = device.clone();
+            std::thread::spawn(move || {
+                for _ in 0..i {
+                    denoise_small(&clone);
+                }
+                drop(clone);
+            })
+        })
+        .collect();
+    let keep = device.clone();
+    for w in workers {
+        w.join().expect("worker thread");
+    }
+
+    let mut color = vec![0.5f32; 4 * 4 * 3];
+    filter.execute_in_place(&mut color).expect("filter after clones dropped");
+    drop(filter);
+    drop(device);
+    // The last remaining clone still owns a live device.
+    denoise_small(&keep);
+    assert!(keep.take_error().is_none());
+}
+
+#[test]
+fn test_buffer_keeps_device_alive() {
+    let device = OidnDevice::new().expect("OIDN device");
+    let buffer = crate::OidnBuffer::new(&device, 64).expect("buffer");
+    drop(device);
+    let data = [1u8; 64];
+    let mut out = [0u8; 64];
+    unsafe {
+        buffer.write(0, 64, data.as_ptr() as *const _);
+        buffer.read(0, 64, out.as_mut_ptr() as *mut _);
+    }
+    assert_eq!(out, data);
+}
+
+#[test]
+fn test_filter_rejects_buffer_from_other_device() {
+    let device_a = OidnDevice::new().expect("OIDN device A");
+    let device_b = OidnDevice::new().expect("OIDN device B");
+    let n = 4 * 4 * 3 * std::mem::size_of::<f32>();
+    let buf_a = crate::OidnBuffer::new(&device_a, n).expect("buffer A");
+    let buf_b = crate::OidnBuffer::new(&device_b, n).expect("buffer B");
+    assert!(buf_a.device().same_device(&device_a.clone()));
+    assert!(!buf_b.device().same_device(&device_a));
+
+    let filter = crate::Filter::new(&device_a, "RT").expect("filter");
+    filter
+        .set_image("color", &buf_a, crate::OIDNFormat::Float3, 4, 4, 0, 0, 0)
+        .expect("same-device buffer");
+    let err = filter
+        .set_image("output", &buf_b, crate::OIDNFormat::Float3, 4, 4, 0, 0, 0)
+        .expect_err("buffer from another device");
+    assert!(matches!(err, crate::Error::DeviceMismatch));
+}
+
+#[test]
+fn test_owned_filter_stored_with_device_and_moved_to_thread() {
+    struct Denoiser {
+        device: OidnDevice,
+        filter: RtFilter,
+    }
+
+    let device = OidnDevice::new().expect("OIDN device");
+    let mut filter = RtFilter::new(&device).expect("RT filter");
+    filter.set_dimensions(4, 4);
+    let denoiser = Denoiser { device, filter };
+
+    let handle = std::thread::spawn(move || {
+        let mut denoiser = denoiser;
+        let mut color = vec![0.5f32; 4 * 4 * 3];
+        denoiser.filter.execute_in_place(&mut color).expect("execute");
+        assert!(denoiser.device.take_error().is_none());
+        assert!(denoiser.filter.device().same_device(&denoiser.device));
+    });
+    handle.join().expect("worker thread");
+}
+
+#[test]
+fn test_rt_filter_reuse_across_frames() {
+    use crate::ExecutionMode;
+
+    let device = OidnDevice::new().expect("OIDN device");
+    let mut filter = RtFilter::new(&device).expect("RT filter");
+    filter.set_dimensions(8, 4);
+    let n = 8 * 4 * 3;
+    let color = vec![0.5f32; n];
+    let albedo = vec![0.8f32; n];
+    let normal = vec![0.0f32; n];
+    let mut output = vec![0.0f32; n];
+
+    // Same size, toggling aux images and parameters between frames.
+    for frame in 0..4 {
+        let aux = frame % 2 == 1;
+        filter.set_quality(if frame < 2 { Quality::Fast } else { Quality::High });
+        filter
+            .execute_with_aux(
+                Some(&color),
+                &mut output,
+                aux.then_some(&albedo[..]),
+                aux.then_some(&normal[..]),
+            )
+            .expect("execute_with_aux");
+        let mut in_place = color.clone();
+        filter.execute_in_place(&mut in_place).expect("execute_in_place");
+    }
+
+    // Re-executing with unchanged parameters and images skips the commit.
+    for mode in [ExecutionMode::Shared, ExecutionMode::Buffered] {
+        filter.set_execution_mode(mode).set_quality(Quality::High);
+        filter.execute(Some(&color), &mut output).expect("execute");
+        let commits = filter.commits();
+        filter.execute(Some(&color), &mut output).expect("execute");
+        filter.execute(Some(&color), &mut output).expect("execute");
+        assert_eq!(filter.commits(), commits, "{mode:?}");
+        filter.set_quality(Quality::Fast);
+        filter.execute(Some(&color), &mut output).expect("execute");
+        assert_eq!(filter.commits(), commits + 1, "{mode:?}");
+    }
+
+    // Resizing reallocates the persistent buffers.
+    filter.set_dimensions(4, 4);
+    let mut small = vec![0.5f32; 4 * 4 * 3];
+    filter.execute_in_place(&mut small).expect("execute after resize");
+    assert!(device.take_error().is_none());
+}
+
+#[test]
+fn test_rt_filter_execution_modes() {
+    use crate::ExecutionMode;
+
+    let device = OidnDevice::cpu().expect("OIDN CPU device");
+    let mut filter = RtFilter::new(&device).expect("RT filter");
+    filter.set_dimensions(4, 4);
+    assert_eq!(filter.uses_shared_images(), device.system_memory_supported());
+
+    let n = 4 * 4 * 3;
+    let color = vec![0.5f32; n];
+    let albedo = vec![0.8f32; n];
+    for mode in [ExecutionMode::Shared, ExecutionMode::Buffered, ExecutionMode::Shared] {
+        filter.set_execution_mode(mode);
+        let mut output = vec![0.0f32; n];
+        filter
+            .execute_with_aux(Some(&color), &mut output, Some(&albedo), None)
+            .expect("execute_with_aux");
+        let mut in_place = color.clone();
+        filter.execute_in_place(&mut in_place).expect("execute_in_place");
+    }
+    assert!(device.take_error().is_none());
+}
+
+/// Asserts that two denoised images agree within a small tolerance: runs of the same filter on the
+/// same data may differ in rounding (e.g. tiling or another memory layout), never in content.
+fn assert_close(actual: &[f32], expected: &[f32], context: &str) {
+    assert_eq!(actual.len(), expected.len(), "{context}: length");
+    for (i, (&a, &e)) in actual.iter().zip(expected).enumerate() {
+        assert!(a.is_finite(), "{context}: value {i} is {a}");
+        assert!((a - e).abs() <= 1e-3 + 1e-2 * e.abs(), "{context}: value {i} is {a}, expected {e}");
+    }
+}
+
+/// [`assert_close`] for RGB read back from a texture of `format`: `expected` is stored in the
+/// format first, and may round one step differently.
+fn assert_close_texels(actual: &[f32], expected: &[f32], format: crate::DenoiseTextureFormat, context: &str) {
+    use crate::DenoiseTextureFormat as F;
+    let n = expected.len() / 3;
+    let mut bytes = vec![0u8; n * format.bytes_per_pixel() as usize];
+    format.encode(expected, &vec![1.0; n], &mut bytes);
+    let mut stored = vec![0.0f32; expected.len()];
+    format.decode(&bytes, &mut stored, &mut vec![0.0; n]);
+    let step = match format {
+        F::Rgba8Unorm | F::Rgba8UnormSrgb | F::Bgra8Unorm | F::Bgra8UnormSrgb => 1.0 / 255.0,
+        F::Rgb10a2Unorm => 1.0 / 1023.0,
+        F::Rgba16Unorm => 1.0 / 65535.0,
+        _ => 0.0,
+    };
+    assert_eq!(actual.len(), stored.len(), "{context}: length");
+    for (i, (&a, &e)) in actual.iter().zip(&stored).enumerate() {
+        assert!(
+            (a - e).abs() <= step + 1e-3 + 1e-2 * e.abs(),
+            "{context}: value {i} is {a}, expected {e}"
+        );
+    }
+}
+
+/// [`assert_close`] for IEEE half bit patterns.
+fn assert_close_f16(actual: &[u16], expected: &[u16], context: &str) {
+    let to_f32 = |v: &[u16]| -> Vec<f32> { v.iter().map(|&b| half::f16::from_bits(b).to_f32()).collect() };
+    assert_close(&to_f32(actual), &to_f32(expected), context);
+}
+
+#[test]
+fn test_rt_filter_strided_half4_images() {
+    use crate::{Error, ExecutionMode, ImageMut, ImageRef, OIDNFormat};
+
+    let device = OidnDevice::cpu().expect("OIDN CPU device");
+    let mut filter = RtFilter::new(&device).expect("RT filter");
+    let (w, h) = (4u32, 3u32);
+    filter.set_dimensions(w, h);
+
+    // RGBA f16 rows padded to 48 bytes (4 pixels * 8 bytes + 16 bytes padding).
+    let row_stride = 48;
+    let pad = half::f16::from_f32(-1.0).to_bits();
+    let mut frame = vec![pad; row_stride / 2 * h as usize];
+    for y in 0..h as usize {
+        for x in 0..w as usize {
+            let i = y * row_stride / 2 + x * 4;
+            frame[i..i + 4].copy_from_slice(&[0.25f32, 0.5, 0.75, 1.0].map(|v| half::f16::from_f32(v).to_bits()));
+        }
+    }
+    let bytes: &[u8] = bytemuck::cast_slice(&frame);
+
+    for mode in [ExecutionMode::Shared, ExecutionMode::Buffered] {
+        filter.set_execution_mode(mode);
+        let color = ImageRef::with_strides(bytes, OIDNFormat::Half4, w, h, 0, row_stride).unwrap();
+        let mut out_frame = frame.clone();
+        for v in out_frame.iter_mut().step_by(4) {
+            *v = 0;
+        }
+        let expected_padding = out_frame.clone();
+        let out_bytes: &mut [u8] = bytemuck::cast_slice_mut(&mut out_frame);
+        let mut output = ImageMut::with_strides(out_bytes, OIDNFormat::Half4, w, h, 0, row_stride).unwrap();
+        filter.execute_image(Some(color), &mut output).expect("execute_image");
+
+        // The same pixels, tightly packed as Half3.
+        let dense: Vec<u16> = (0..h as usize)
+            .flat_map(|y| (0..w as usize).map(move |x| y * row_stride / 2 + x * 4))
+            .flat_map(|i| frame[i..i + 3].to_vec())
+            .collect();
+        let mut dense_out = vec![0u16; dense.len()];
+        filter
+            .execute_image(
+                Some(ImageRef::from_f16_bits(&dense, OIDNFormat::Half3, w, h).unwrap()),
+                &mut ImageMut::from_f16_bits(&mut dense_out, OIDNFormat::Half3, w, h).unwrap(),
+            )
+            .expect("dense execute_image");
+
+        for y in 0..h as usize {
+            let row = &out_frame[y * row_stride / 2..(y + 1) * row_stride / 2];
+            for x in 0..w as usize {
+                let rgb = &row[x * 4..x * 4 + 3];
+                let expected = &dense_out[(y * w as usize + x) * 3..][..3];
+                assert_close_f16(rgb, expected, &format!("{mode:?} pixel ({x}, {y})"));
+                // Alpha and row padding are untouched.
+                assert_eq!(row[x * 4 + 3], half::f16::from_f32(1.0).to_bits());
+            }
+            assert_eq!(&row[w as usize * 4..], &expected_padding[y * row_stride / 2 + w as usize * 4..(y + 1) * row_stride / 2]);
+        }
+    }
+
+    // Too short, bad stride, unsupported format, mismatched dimensions.
+    assert!(matches!(
+        ImageRef::with_strides(&bytes[..bytes.len() - 20], OIDNFormat::Half4, w, h, 0, row_stride),
+        Err(Error::InvalidDimensions)
+    ));
+    assert!(matches!(
+        ImageRef::with_strides(bytes, OIDNFormat::Half4, w, h, 4, 0),
+        Err(Error::InvalidDimensions)
+    ));
+    assert!(matches!(ImageRef::new(bytes, OIDNFormat::Float2, w, h), Err(Error::UnsupportedFormat)));
+    // Strides whose size computation would overflow (and wrap to a small size).
+    let huge = usize::MAX / 2;
+    assert!(matches!(
+        ImageRef::with_strides(bytes, OIDNFormat::Half4, w, h, huge, 0),
+        Err(Error::InvalidDimensions)
+    ));
+    assert!(matches!(
+        ImageRef::with_strides(bytes, OIDNFormat::Half4, w, h, 0, huge),
+        Err(Error::InvalidDimensions)
+    ));
+    let mut scratch = frame.clone();
+    assert!(matches!(
+        ImageMut::with_strides(bytemuck::cast_slice_mut(&mut scratch), OIDNFormat::Half4, w, h, huge, huge),
+        Err(Error::InvalidDimensions)
+    ));
+    let small = vec![0.0f32; 2 * 2 * 3];
+    let mut small_out = small.clone();
+    let mut output = ImageMut::from_f32(&mut small_out, OIDNFormat::Float3, 2, 2).unwrap();
+    assert!(matches!(
+        filter.execute_image(Some(ImageRef::from_f32(&small, OIDNFormat::Float3, 2, 2).unwrap()), &mut output),
+        Err(Error::InvalidDimensions)
+    ));
+}
+
+#[test]
+fn test_progress_monitor_and_cancellation() {
+    use crate::{CancellationToken, Error, Filter};
+    use std::sync::{Arc, Mutex};
+
+    let device = OidnDevice::cpu().expect("OIDN CPU device");
+    let mut filter = RtFilter::new(&device).expect("RT filter");
+    filter.set_dimensions(4, 4);
+    let mut color = vec![0.5f32; 4 * 4 * 3];
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen_in_monitor = Arc::clone(&seen);
+    filter.set_progress_monitor(move |p| {
+        seen_in_monitor.lock().unwrap().push(p);
+        true
+    });
+    filter.execute_in_place(&mut color).expect("execute with monitor");
+    let seen = seen.lock().unwrap().clone();
+    assert!(!seen.is_empty());
+    assert!(seen.iter().all(|p| (0.0..=1.0).contains(p)));
+
+    // Monitor returning false cancels.
+    filter.set_progress_monitor(|_| false);
+    assert!(matches!(filter.execute_in_place(&mut color), Err(Error::Cancelled)));
+
+    // Token cancelled from another thread cancels; reset allows the next execution.
+    let token = CancellationToken::new();
+    filter.clear_progress_monitor().set_cancellation_token(&token);
+    let remote = token.clone();
+    std::thread::spawn(move || remote.cancel()).join().unwrap();
+    assert!(matches!(filter.execute_in_place(&mut color), Err(Error::Cancelled)));
+    token.reset();
+    filter.execute_in_place(&mut color).expect("execute after reset");
+
+    // Generic filter: the cancellation surfaces through take_error.
+    let mut generic = Filter::new(&device, "RT").expect("generic filter");
+    let cancelled = CancellationToken::new();
+    cancelled.cancel();
+    generic.set_cancellation_token(&cancelled);
+    let mut out = vec![0.0f32; 4 * 4 * 3];
+    unsafe {
+        generic.set_shared_image("color", color.as_mut_ptr() as *mut _, crate::OIDNFormat::Float3, 4, 4, 0, 0, 0).unwrap();
+        generic.set_shared_image("output", out.as_mut_ptr() as *mut _, crate::OIDNFormat::Float3, 4, 4, 0, 0, 0).unwrap();
+    }
+    generic.commit();
+    generic.execute();
+    assert!(matches!(device.take_error(), Some(Error::Cancelled)));
+}
+
+#[test]
+fn test_pending_execution() {
+    use crate::{ExecutionMode, Filter, OIDNFormat};
+
+    let device = OidnDevice::cpu().expect("OIDN CPU device");
+    let mut filter = RtFilter::new(&device).expect("RT filter");
+    filter.set_dimensions(4, 4);
+    let color: Vec<f32> = (0..4 * 4 * 3).map(|i| i as f32 / 48.0).collect();
+    let mut expected = vec![0.0f32; color.len()];
+    filter.execute(Some(&color), &mut expected).expect("blocking execute");
+
+    for mode in [ExecutionMode::Shared, ExecutionMode::Buffered] {
+        filter.set_execution_mode(mode);
+
+        // wait()
+        let mut output = vec![0.0f32; color.len()];
+        let pending = filter.execute_async(Some(&color), &mut output).expect("execute_async");
+        pending.wait().expect("wait");
+        assert_close(&output, &expected, &format!("{mode:?} wait"));
+
+        // is_done() then wait()
+        let mut output = vec![0.0f32; color.len()];
+        let pending = filter.execute_async(Some(&color), &mut output).expect("execute_async");
+        while !pending.is_done() {
+            std::thread::yield_now();
+        }
+        pending.wait().expect("wait after is_done");
+        assert_close(&output, &expected, &format!("{mode:?} is_done"));
+
+        // .await
+        let mut output = vec![0.0f32; color.len()];
+        let pending = filter.execute_async(Some(&color), &mut output).expect("execute_async");
+        pollster::block_on(pending).expect("await");
+        assert_close(&output, &expected, &format!("{mode:?} await"));
+
+        // Dropping without waiting still completes the execution.
+        let mut output = vec![0.0f32; color.len()];
+        drop(filter.execute_async(Some(&color), &mut output).expect("execute_async"));
+        assert_close(&output, &expected, &format!("{mode:?} drop"));
+
+        // Leaking the handle is safe: OIDN only used the filter's buffers, so the slices can be
+        // freed right away, and the next execution waits for the leaked one.
+        let mut input = color.clone();
+        let mut output = vec![0.0f32; color.len()];
+        std::mem::forget(filter.execute_async_with_aux(None, &mut output, Some(&input), None).expect("execute_async"));
+        input.fill(f32::NAN);
+        drop((input, output));
+        let mut output = vec![0.0f32; color.len()];
+        filter.execute(Some(&color), &mut output).expect("execute after leak");
+        assert_close(&output, &expected, &format!("{mode:?} after leak"));
+    }
+
+    let mut generic = Filter::new(&device, "RT").expect("generic filter");
+    generic.set_bool("hdr", true).unwrap(); // RtFilter's default
+    let mut input = color.clone();
+    let mut output = vec![0.0f32; color.len()];
+    unsafe {
+        generic.set_shared_image("color", input.as_mut_ptr() as *mut _, OIDNFormat::Float3, 4, 4, 0, 0, 0).unwrap();
+        generic.set_shared_image("output", output.as_mut_ptr() as *mut _, OIDNFormat::Float3, 4, 4, 0, 0, 0).unwrap();
+    }
+    generic.commit();
+    pollster::block_on(generic.execute_async()).expect("generic execute_async");
+    assert_close(&output, &expected, "generic filter");
+}
+
+#[test]
+fn test_prefiltered_rt_denoiser() {
+    use crate::{ImageMut, ImageRef, OIDNFormat, PrefilteredRtDenoiser};
+
+    let device = OidnDevice::cpu().expect("OIDN CPU device");
+    let mut denoiser = PrefilteredRtDenoiser::new(&device).expect("prefiltered denoiser");
+    denoiser.set_dimensions(4, 4);
+    let n = 4 * 4 * 3;
+    let albedo: Vec<f32> = (0..n).map(|i| i as f32 / n as f32).collect();
+    let normal: Vec<f32> = (0..n).map(|i| 1.0 - i as f32 / n as f32).collect();
+    let color = vec![0.5f32; n];
+
+    // The pipeline by hand: albedo and normal denoised alone, then color with cleanAux.
+    let prefilter = |aux: &[f32], is_normal: bool| {
+        let mut filter = RtFilter::new(&device).expect("aux filter");
+        filter.set_dimensions(4, 4).set_hdr(false);
+        let image = ImageRef::from_f32(aux, OIDNFormat::Float3, 4, 4).unwrap();
+        let mut clean = vec![0.0f32; n];
+        let mut output = ImageMut::from_f32(&mut clean, OIDNFormat::Float3, 4, 4).unwrap();
+        let (albedo, normal) = if is_normal { (None, Some(image)) } else { (Some(image), None) };
+        filter.prefilter_aux(albedo, normal, &mut output).expect("prefilter aux");
+        clean
+    };
+    let (clean_albedo, clean_normal) = (prefilter(&albedo, false), prefilter(&normal, true));
+    let mut reference = RtFilter::new(&device).expect("color filter");
+    reference.set_dimensions(4, 4).set_clean_aux(true);
+    let mut expected = color.clone();
+    reference
+        .execute_in_place_with_aux(&mut expected, Some(&clean_albedo), Some(&clean_normal))
+        .expect("reference execute");
+
+    for frame in 0..2 {
+        let mut output = color.clone();
+        denoiser
+            .execute_in_place(&mut output, &albedo, Some(&normal))
+            .expect("prefiltered execute");
+        assert_close(&output, &expected, &format!("frame {frame}"));
+        assert_close(denoiser.clean_albedo().unwrap(), &clean_albedo, "clean albedo");
+        assert_close(denoiser.clean_normal().unwrap(), &clean_normal, "clean normal");
+    }
+
+    // Unfiltered normal: passed to the color filter as is.
+    denoiser.set_prefilter_normal(false);
+    let mut output = vec![0.0f32; n];
+    denoiser
+        .execute(Some(&color), &mut output, &albedo, Some(&normal))
+        .expect("execute without normal prefilter");
+    assert!(denoiser.clean_albedo().is_some());
+    assert!(denoiser.clean_normal().is_none());
+    reference
+        .execute_with_aux(Some(&color), &mut expected, Some(&clean_albedo), Some(&normal))
+        .expect("reference execute");
+    assert_close(&output, &expected, "normal not prefiltered");
+
+    assert!(matches!(
+        denoiser.execute(Some(&color), &mut output, &albedo[..3], None),
+        Err(crate::Error::InvalidDimensions)
+    ));
+}
+
+#[test]
+fn test_rt_lightmap_filter_parameters_and_images() {
+    use crate::{Error, ExecutionMode, Filter, ImageMut, ImageRef, OIDNFormat};
+
+    let device = OidnDevice::cpu().expect("OIDN CPU device");
+    let Ok(mut filter) = RtLightmapFilter::new(&device) else {
+        return; // OIDN built without RTLightmap
+    };
+    assert!(matches!(filter.set_quality(Quality::Fast), Err(Error::UnsupportedQuality(Quality::Fast))));
+    assert!(matches!(
+        filter.set_quality(Quality::Balanced),
+        Err(Error::UnsupportedQuality(Quality::Balanced))
+    ));
+    filter
+        .set_quality(Quality::High)
+        .expect("high quality")
+        .set_dimensions(4, 2)
+        .set_directional(true)
+        .set_input_scale(1.0)
+        .set_max_memory_mb(Some(256));
+
+    // Reference: the same filter on a dense Float3 copy. Values are exact in f16.
+    let color: Vec<f32> = (0..4 * 2 * 3).map(|i| (i % 7) as f32 / 8.0 + 0.125).collect();
+    let mut expected = vec![0.0f32; color.len()];
+    filter.execute(Some(&color), &mut expected).expect("reference execute");
+
+    // RGBA f16 lightmap, denoised in place; alpha is kept.
+    let lightmap: Vec<u16> = color
+        .chunks(3)
+        .enumerate()
+        .flat_map(|(i, rgb)| [rgb[0], rgb[1], rgb[2], i as f32 / 8.0])
+        .map(|v| half::f16::from_f32(v).to_bits())
+        .collect();
+    for mode in [ExecutionMode::Shared, ExecutionMode::Buffered] {
+        filter.set_execution_mode(mode);
+        for frame in 0..2 {
+            let mut data = lightmap.clone();
+            let mut image = ImageMut::from_f16_bits(&mut data, OIDNFormat::Half4, 4, 2).unwrap();
+            filter.execute_image(None, &mut image).expect("execute_image");
+            let rgb: Vec<f32> = data
+                .chunks(4)
+                .flat_map(|texel| texel[..3].iter().map(|&b| half::f16::from_bits(b).to_f32()))
+                .collect();
+            assert_close(&rgb, &expected, &format!("{mode:?} frame {frame}"));
+            for (texel, original) in data.chunks(4).zip(lightmap.chunks(4)) {
+                assert_eq!(texel[3], original[3], "{mode:?}: alpha");
+            }
+        }
+        let mut output = vec![0.0f32; color.len()];
+        filter.execute(Some(&color), &mut output).expect("execute");
+        assert_close(&output, &expected, &format!("{mode:?} execute"));
+    }
+    let params = filter.params();
+    assert!(params.directional);
+    assert_eq!(params.max_memory_mb, Some(256));
+    assert_eq!(params.quality, Some(Quality::High));
+
+    let small = vec![0.0f32; 2 * 2 * 3];
+    let mut out = vec![0.0f32; 4 * 2 * 3];
+    let mut out = ImageMut::from_f32(&mut out, OIDNFormat::Float3, 4, 2).unwrap();
+    assert!(matches!(
+        filter.execute_image(Some(ImageRef::from_f32(&small, OIDNFormat::Float3, 2, 2).unwrap()), &mut out),
+        Err(Error::InvalidDimensions)
+    ));
+
+    // The generic filter rejects aux images on RTLightmap with a typed error.
+    let generic = Filter::new(&device, "RTLightmap").expect("generic RTLightmap");
+    let buffer = crate::OidnBuffer::new(&device, 4 * 2 * 12).expect("buffer");
+    for name in ["albedo", "normal"] {
+        let result = generic.set_image(name, &buffer, OIDNFormat::Float3, 4, 2, 0, 0, 0);
+        assert!(matches!(result, Err(Error::UnsupportedImage(ref n)) if n == name));
+    }
+    generic
+        .set_image("color", &buffer, OIDNFormat::Float3, 4, 2, 0, 0, 0)
+        .expect("color image");
+}
+
+/// Smallest valid TZA file: header pointing at an empty tensor table.
+fn empty_tza(major: u8) -> Vec<u8> {
+    let mut tza = Vec::new();
+    tza.extend_from_slice(&0x41D7u16.to_le_bytes());
+    tza.extend_from_slice(&[major, 0]);
+    tza.extend_from_slice(&12u64.to_le_bytes());
+    tza.extend_from_slice(&0u32.to_le_bytes());
+    tza
+}
+
+#[test]
+fn test_weights_validation_and_binding() {
+    use crate::{Error, Weights};
+
+    let weights = Weights::from_bytes(&empty_tza(2)).expect("valid TZA");
+    assert_eq!(weights.version(), (2, 0));
+
+    assert!(matches!(Weights::from_bytes(&[0xD7, 0x41]), Err(Error::InvalidWeights(_))));
+    let mut bad_magic = empty_tza(2);
+    bad_magic[0] = 0;
+    assert!(matches!(Weights::from_vec(bad_magic), Err(Error::InvalidWeights(_))));
+    assert!(matches!(Weights::from_vec(empty_tza(1)), Err(Error::InvalidWeights(_))));
+    let mut bad_offset = empty_tza(2);
+    bad_offset[4..12].copy_from_slice(&1000u64.to_le_bytes());
+    assert!(matches!(Weights::from_vec(bad_offset), Err(Error::InvalidWeights(_))));
+    assert!(matches!(
+        Weights::from_file("/nonexistent/oidn-wgpu/weights.tza"),
+        Err(Error::Io(_))
+    ));
+
+    let path = std::env::temp_dir().join(format!("oidn-wgpu-test-{}.tza", std::process::id()));
+    std::fs::write(&path, empty_tza(2)).unwrap();
+    let from_file = Weights::from_file(&path).expect("weights from file");
+    std::fs::remove_file(&path).ok();
+    assert_eq!(from_file.as_bytes(), weights.as_bytes());
+
+    let device = OidnDevice::cpu().expect("OIDN CPU device");
+    let mut filter = RtFilter::new(&device).expect("RT filter");
+    filter.set_dimensions(2, 2).set_weights(weights.clone());
+    drop(weights); // the filter keeps its own handle
+    assert_eq!(filter.weights().map(|w| w.as_bytes().len()), Some(16));
+    // Header-valid but without tensors: OIDN rejects it when the filter is committed.
+    let mut color = vec![0.5f32; 2 * 2 * 3];
+    assert!(matches!(
+        filter.execute_in_place(&mut color),
+        Err(Error::OidnError { code: crate::ErrorCode::InvalidArgument, .. })
+    ));
+    filter.clear_weights();
+    assert!(filter.weights().is_none());
+    filter.execute_in_place(&mut color).expect("execute with built-in weights");
+}
+
+#[test]
+fn test_typed_filter_parameters() {
+    use crate::{Error, Filter, ParamType, ParamValue, RtLightmapParam, RtParam};
+
+    assert_eq!(RtParam::from_name("cleanAux"), Some(RtParam::CleanAux));
+    assert_eq!(RtParam::from_name("cleanaux"), None);
+    assert_eq!(RtParam::MaxMemoryMb.name(), "maxMemoryMB");
+    assert_eq!(RtParam::InputScale.value_type(), ParamType::Float);
+    assert!(RtLightmapParam::TileOverlap.is_read_only());
+
+    let device = OidnDevice::cpu().expect("OIDN CPU device");
+    let mut filter = RtFilter::new(&device).expect("RT filter");
+    filter
+        .set_param(RtParam::Srgb, true)
+        .and_then(|f| f.set_param(RtParam::Hdr, false))
+        .and_then(|f| f.set_param(RtParam::Quality, Quality::Balanced))
+        .and_then(|f| f.set_param(RtParam::MaxMemoryMb, 512))
+        .expect("typed setters");
+    assert!(matches!(
+        filter.set_param(RtParam::Hdr, 1.0f32),
+        Err(Error::InvalidParameterValue(_))
+    ));
+    assert!(matches!(
+        filter.set_param(RtParam::Quality, 3),
+        Err(Error::InvalidParameterValue(_))
+    ));
+    assert!(matches!(
+        filter.set_param(RtParam::TileAlignment, 8),
+        Err(Error::ReadOnlyParameter(ref n)) if n == "tileAlignment"
+    ));
+
+    // Nothing reaches OIDN until an execution commits the parameters.
+    assert_ne!(filter.get_param(RtParam::Srgb), ParamValue::Bool(true));
+    let mut color = vec![0.5f32; 2 * 2 * 3];
+    filter.set_dimensions(2, 2).execute_in_place(&mut color).expect("execute");
+    let params = filter.params();
+    assert!(params.srgb && !params.hdr && !params.clean_aux);
+    assert_eq!(params.quality, Some(Quality::Balanced));
+    assert_eq!(params.max_memory_mb, Some(512));
+    assert!(params.tile_alignment >= 1);
+    #[allow(deprecated)]
+    {
+        assert!(filter.get_bool("srgb") && !filter.get_bool("hdr"));
+        assert_eq!(filter.get_int("quality"), Quality::Balanced.to_raw() as i32);
+        assert_eq!(filter.get_int("maxMemoryMB"), 512);
+        let input_scale = filter.params().input_scale;
+        assert_eq!(filter.get_float("inputScale").to_bits(), input_scale.to_bits());
+        // Names OIDN cannot take read as zero instead of panicking.
+        assert!(!filter.get_bool("srgb\0"));
+        assert_eq!(filter.get_int("quality\0"), 0);
+        assert_eq!(filter.get_float("input\0Scale"), 0.0);
+    }
+
+    let mut lightmap = RtLightmapFilter::new(&device).expect("RTLightmap filter");
+    assert!(matches!(
+        lightmap.set_param(RtLightmapParam::Quality, Quality::Fast),
+        Err(Error::UnsupportedQuality(Quality::Fast))
+    ));
+    lightmap
+        .set_param(RtLightmapParam::Directional, true)
+        .expect("directional");
+
+    // The generic filter checks names and types for known filter types.
+    let generic = Filter::new(&device, "RT").expect("generic RT");
+    generic.set_bool("hdr", true).expect("hdr");
+    generic.set_int("quality", 6).expect("quality");
+    assert!(matches!(generic.set_bool("hrd", true), Err(Error::InvalidParameter(_))));
+    assert!(matches!(generic.set_bool("hdr\0", true), Err(Error::InvalidParameter(_))));
+    assert!(matches!(generic.set_float("hdr", 1.0), Err(Error::InvalidParameterValue(_))));
+    assert!(matches!(generic.try_get_int("srgb"), Err(Error::InvalidParameterValue(_))));
+    assert!(matches!(generic.set_int("tileOverlap", 0), Err(Error::ReadOnlyParameter(_))));
+    assert!(generic.try_get_bool("hdr").expect("get hdr"));
+    assert_eq!(generic.try_get_int("quality").expect("get quality"), 6);
+    #[allow(deprecated)]
+    {
+        assert!(generic.get_bool("hdr"));
+        assert_eq!(generic.get_int("quality"), 6);
+        assert!(!generic.get_bool("hdr\0"));
+        assert_eq!(generic.get_int("quality\0"), 0);
+        assert_eq!(generic.get_float("inputScale\0"), 0.0);
+    }
+    assert!(matches!(generic.try_get_bool("hdr\0"), Err(Error::InvalidParameter(_))));
+}
+
+#[test]
+fn test_tiled_denoiser_matches_whole_image() {
+    use crate::TiledDenoiser;
+
+    let device = OidnDevice::cpu().expect("OIDN CPU device");
+    // The filter's tiling, as committed for an image with both aux images.
+    let mut filter = RtFilter::new(&device).expect("RT filter");
+    let probe = vec![0.0f32; 8 * 8 * 3];
+    let mut probe_out = probe.clone();
+    filter
+        .set_dimensions(8, 8)
+        .execute_with_aux(Some(&probe), &mut probe_out, Some(&probe), Some(&probe))
+        .expect("probe");
+    let params = filter.params();
+    let (alignment, overlap) = (params.tile_alignment.max(1), params.tile_overlap);
+    assert!(overlap > 0, "tiles must overlap to hide seams");
+
+    // Several tiles (with their overlap) per axis, and sizes that are not tile multiples.
+    let tile_size = 2 * alignment;
+    let span = tile_size + 2 * overlap;
+    let (w, h) = (3 * span + 5, 2 * span + 7);
+    let n = (w * h * 3) as usize;
+    let color: Vec<f32> = (0..n).map(|i| ((i * 7919) % 101) as f32 / 100.0).collect();
+    let albedo: Vec<f32> = (0..n).map(|i| ((i * 31) % 17) as f32 / 16.0).collect();
+    let normal = vec![0.0f32; n];
+
+    let mut whole = vec![0.0f32; n];
+    filter
+        .set_dimensions(w, h)
+        .execute_with_aux(Some(&color), &mut whole, Some(&albedo), Some(&normal))
+        .expect("whole image");
+
+    // 1 is rounded up to the alignment; u32::MAX is clamped to the image (one tile).
+    for tile_size in [1, tile_size, span, u32::MAX] {
+        let mut tiled = TiledDenoiser::new(&device).expect("tiled denoiser");
+        tiled.set_tile_size(tile_size);
+        let mut output = Vec::new();
+        tiled
+            .denoise(
+                w,
+                h,
+                &mut &color[..],
+                Some(&mut &albedo[..]),
+                Some(&mut &normal[..]),
+                &mut output,
+            )
+            .expect("tiled");
+        assert_eq!(output.len(), n, "tile size {tile_size}");
+        assert_close(&output, &whole, &format!("tile size {tile_size}"));
+    }
+
+    // Readers that run out of rows fail instead of producing a partial image.
+    let mut tiled = TiledDenoiser::new(&device).expect("tiled denoiser");
+    let mut output = Vec::new();
+    let result = tiled.denoise(w, h + 1, &mut &color[..], None, None, &mut output);
+    assert!(matches!(result, Err(crate::Error::InvalidDimensions)));
+}
+
+#[test]
+fn test_memory_budget_and_estimate() {
+    use crate::{estimate_memory, AuxImages, Error, RtParam};
+
+    let device = OidnDevice::cpu().expect("OIDN CPU device");
+    let mut filter = RtFilter::new(&device).expect("RT filter");
+    filter.set_dimensions(2, 2).set_max_memory_mb(Some(64));
+    let mut color = vec![0.5f32; 2 * 2 * 3];
+    filter.execute_in_place(&mut color).expect("execute");
+    assert_eq!(filter.params().max_memory_mb, Some(64));
+    filter.set_max_memory_mb(None).execute_in_place(&mut color).expect("execute");
+    assert_eq!(filter.params().max_memory_mb, None);
+    assert_eq!(filter.get_param(RtParam::MaxMemoryMb).as_int(), Some(-1));
+
+    let estimate = |w, h, quality, aux, mb| estimate_memory(&device, w, h, quality, aux, mb);
+    let small = estimate(64, 64, Quality::High, AuxImages::None, 256).expect("estimate");
+    let large = estimate(1920, 1080, Quality::High, AuxImages::None, 1024).expect("estimate");
+    let aux = estimate(1920, 1080, Quality::Fast, AuxImages::AlbedoNormal, 1024).expect("estimate");
+    assert!(small.tile_alignment >= 1);
+    assert_eq!(small.scratch_bytes, 256 * 1024 * 1024);
+    assert_eq!(large.image_bytes, 2 * 1920 * 1080 * 12);
+    assert_eq!(aux.image_bytes, 4 * 1920 * 1080 * 12);
+    assert_eq!(large.total_bytes(), 1024 * 1024 * 1024 + 2 * 1920 * 1080 * 12);
+    assert!(large.total_mb() * 1024 * 1024 >= large.total_bytes());
+    assert!(matches!(
+        estimate(0, 8, Quality::High, AuxImages::None, 256),
+        Err(Error::InvalidDimensions)
+    ));
+
+    // The job run with the estimate's limit.
+    let (w, h) = (256, 192);
+    let bound = estimate(w, h, Quality::High, AuxImages::None, 64).expect("estimate");
+    filter.set_dimensions(w, h).set_max_memory_mb(Some(64));
+    let mut color = vec![0.5f32; (w * h * 3) as usize];
+    filter.execute_in_place(&mut color).expect("execute within limit");
+    assert_eq!(filter.params().tile_alignment.max(1), bound.tile_alignment);
+}
+
+#[test]
+fn test_batch_denoiser_shares_aux() {
+    use crate::{BatchDenoiser, Error, ExecutionMode};
+
+    let device = OidnDevice::cpu().expect("OIDN CPU device");
+    let n = 4 * 3 * 3;
+    let albedo: Vec<f32> = (0..n).map(|i| (i % 5) as f32 / 4.0).collect();
+    let normal = vec![0.0f32; n];
+    let layers: Vec<Vec<f32>> = (0..5)
+        .map(|l| (0..n).map(|i| ((i * 7 + l * 3) % 11) as f32 / 10.0).collect())
+        .collect();
+
+    // Reference: each layer denoised on its own by a plain filter.
+    let mut reference = RtFilter::new(&device).expect("RT filter");
+    reference.set_dimensions(4, 3).set_hdr(true);
+    let mut expected = |normal: Option<&[f32]>| -> Vec<Vec<f32>> {
+        layers
+            .iter()
+            .map(|layer| {
+                let mut output = vec![0.0f32; n];
+                reference
+                    .execute_with_aux(Some(layer), &mut output, Some(&albedo), normal)
+                    .expect("reference");
+                output
+            })
+            .collect()
+    };
+    let (expected_normal, expected_albedo) = (expected(Some(&normal)), expected(None));
+
+    for (parallelism, mode) in [(1, ExecutionMode::Buffered), (3, ExecutionMode::Buffered), (2, ExecutionMode::Shared)] {
+        let mut batch = BatchDenoiser::new(&device, parallelism).expect("batch");
+        assert_eq!(batch.parallelism(), parallelism);
+        batch.set_dimensions(4, 3).configure(|f| {
+            f.set_hdr(true).set_execution_mode(mode);
+        });
+
+        let mut in_place = layers.clone();
+        let mut refs: Vec<&mut [f32]> = in_place.iter_mut().map(|l| &mut l[..]).collect();
+        let results = batch
+            .denoise_in_place(&mut refs, Some(&albedo), Some(&normal))
+            .expect("batch in place");
+        assert_eq!(results.len(), layers.len());
+        assert!(results.iter().all(Result::is_ok));
+        for (i, (layer, expected)) in in_place.iter().zip(&expected_normal).enumerate() {
+            assert_close(layer, expected, &format!("{mode:?} x{parallelism}: layer {i}"));
+        }
+        // Albedo and normal are uploaded once per filter, not once per layer.
+        let uploads = if mode == ExecutionMode::Buffered { 2 * parallelism } else { 0 };
+        assert_eq!(batch.aux_uploads(), uploads, "{mode:?} x{parallelism}");
+
+        // A bad layer fails on its own; the others are still denoised.
+        let short = vec![0.0f32; 3];
+        let inputs: Vec<&[f32]> = vec![&layers[1], &short, &layers[3]];
+        let mut outputs = vec![vec![0.0f32; n]; 3];
+        let mut output_refs: Vec<&mut [f32]> = outputs.iter_mut().map(|o| &mut o[..]).collect();
+        let results = batch
+            .denoise(&inputs, &mut output_refs, Some(&albedo), None)
+            .expect("batch");
+        assert!(results[0].is_ok() && results[2].is_ok());
+        assert!(matches!(results[1], Err(Error::InvalidDimensions)));
+        assert_close(&outputs[0], &expected_albedo[1], &format!("{mode:?} x{parallelism}: layer 1"));
+        assert_close(&outputs[2], &expected_albedo[3], &format!("{mode:?} x{parallelism}: layer 3"));
+    }
+
+    let mut batch = BatchDenoiser::new(&device, 1).expect("batch");
+    batch.set_dimensions(4, 3);
+    let mut layer = layers[0].clone();
+    assert!(matches!(
+        batch.denoise_in_place(&mut [&mut layer[..]], Some(&albedo[..3]), None),
+        Err(Error::InvalidDimensions)
+    ));
+}
+
+#[test]
+fn test_structured_errors() {
+    use crate::{DeviceError, Error, ErrorCode, Filter};
+    use std::error::Error as _;
+    use std::ffi::CString;
+
+    let device = OidnDevice::new().expect("OIDN device");
+    assert!(matches!(
+        Filter::new(&device, "NoSuchFilter"),
+        Err(Error::OidnError { code: ErrorCode::InvalidArgument, .. })
+    ));
+    // Two failures before querying: both are kept, in order.
+    let missing = CString::new("Missing").unwrap();
+    let also_missing = CString::new("AlsoMissing").unwrap();
+    unsafe { crate::sys::oidnNewFilter(device.raw(), missing.as_ptr()) };
+    unsafe { crate::sys::oidnNewFilter(device.raw(), also_missing.as_ptr()) };
+    let err = device.take_error().expect("queued errors");
+    assert_eq!(err.kind(), Some(ErrorCode::InvalidArgument));
+    let all = err.device_errors();
+    assert_eq!(all.len(), 2);
+    assert!(all.iter().all(|e| e.code == ErrorCode::InvalidArgument));
+    assert!(matches!(err, Error::OidnError { ref others, .. } if others.len() == 1));
+    assert!(err.to_string().contains("1 more"));
+    assert!(device.take_error().is_none());
+
+    // Errors stay with the thread whose call failed: while one thread's error is queued, another
+    // thread's successful execution reports none, and the error is still there afterwards.
+    let (failed, checked) = (std::sync::Barrier::new(2), std::sync::Barrier::new(2));
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            unsafe { crate::sys::oidnNewFilter(device.raw(), missing.as_ptr()) };
+            failed.wait();
+            checked.wait();
+            let err = device.take_error().expect("own error");
+            assert_eq!(err.device_errors().len(), 1);
+        });
+        scope.spawn(|| {
+            let mut filter = RtFilter::new(&device).expect("RT filter");
+            let mut color = vec![0.5f32; 2 * 2 * 3];
+            failed.wait();
+            filter
+                .set_dimensions(2, 2)
+                .execute_in_place(&mut color)
+                .expect("unaffected by the other thread's error");
+            assert!(device.take_error().is_none());
+            checked.wait();
+        });
+    });
+    assert!(device.take_error().is_none());
+
+    // A drained queue is removed, and so is the queue of a released device once this thread
+    // queues another error.
+    let released = OidnDevice::new().expect("OIDN device");
+    unsafe { crate::sys::oidnNewFilter(released.raw(), missing.as_ptr()) };
+    drop(released);
+    unsafe { crate::sys::oidnNewFilter(device.raw(), missing.as_ptr()) };
+    assert_eq!(crate::device::queued_error_devices(), 1);
+    assert!(device.take_error().is_some());
+    assert_eq!(crate::device::queued_error_devices(), 0);
+
+    assert_eq!(Error::Cancelled.kind(), Some(ErrorCode::Cancelled));
+    assert_eq!(Error::InvalidDimensions.kind(), None);
+    let cancelled = Error::from_device_errors(vec![DeviceError {
+        code: ErrorCode::Cancelled,
+        message: String::new(),
+    }]);
+    assert!(matches!(cancelled, Some(Error::Cancelled)));
+
+    let io = Error::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "missing"));
+    assert!(io.source().is_some());
+    assert!(Error::BufferMapFailed(wgpu::BufferAsyncError).source().is_some());
+    assert!(Error::InvalidDimensions.source().is_none());
+}
+
+#[test]
+fn test_texture_format_conversions_round_trip() {
+    use crate::DenoiseTextureFormat as F;
+
+    let all = [
+        F::Rgba32Float,
+        F::Rgba16Float,
+        F::Rgba8Unorm,
+        F::Rgba8UnormSrgb,
+        F::Bgra8Unorm,
+        F::Bgra8UnormSrgb,
+        F::Rgb10a2Unorm,
+        F::Rg11b10Ufloat,
+        F::Rgb9e5Ufloat,
+        F::Rgba16Unorm,
+    ];
+    // RGB + alpha test texels: in [0, 1] for every format, plus HDR values for float formats.
+    let ldr = [[0.0f32, 0.5, 1.0, 1.0], [0.25, 0.75, 0.125, 0.0], [1.0, 0.0, 0.333, 0.667]];
+    let hdr = [[4.0f32, 0.01, 100.0, 1.0], [0.0, 1024.0, 3.5, 1.0]];
+    for format in all {
+        assert_eq!(F::from_wgpu(format.to_wgpu()), Some(format));
+        assert!(!(format.is_hdr() && format.is_srgb()));
+        let texels: Vec<[f32; 4]> = if format.is_hdr() {
+            ldr.iter().chain(&hdr).copied().collect()
+        } else {
+            ldr.to_vec()
+        };
+        let rgb: Vec<f32> = texels.iter().flat_map(|t| [t[0], t[1], t[2]]).collect();
+        let alpha: Vec<f32> = texels.iter().map(|t| t[3]).collect();
+        let mut bytes = vec![0u8; texels.len() * format.bytes_per_pixel() as usize];
+        format.encode(&rgb, &alpha, &mut bytes);
+        let mut rgb2 = vec![0.0f32; rgb.len()];
+        let mut alpha2 = vec![0.0f32; alpha.len()];
+        format.decode(&bytes, &mut rgb2, &mut alpha2);
+
+        let (rel_tol, abs_tol): (f32, f32) = match format {
+            F::Rgba32Float => (0.0, 0.0),
+            F::Rgba16Float => (1e-3, 1e-4),
+            F::Rg11b10Ufloat => (1.0 / 32.0, 1e-4),
+            F::Rgba16Unorm => (0.0, 1.0 / 65535.0),
+            F::Rgb10a2Unorm => (0.0, 1.0 / 1023.0),
+            _ => (0.0, 1.0 / 255.0),
+        };
+        for (i, (a, b)) in rgb.iter().zip(&rgb2).enumerate() {
+            let tol = if format == F::Rgb9e5Ufloat {
+                // Shared exponent: precision is relative to the texel's largest channel.
+                let texel = texels[i / 3];
+                texel[0].max(texel[1]).max(texel[2]) / 256.0
+            } else {
+                abs_tol.max(a.abs() * rel_tol)
+            };
+            assert!((a - b).abs() <= tol, "{format:?} channel {i}: {a} -> {b}");
+        }
+        for (a, b) in alpha.iter().zip(&alpha2) {
+            match format {
+                F::Rg11b10Ufloat | F::Rgb9e5Ufloat => assert_eq!(*b, 1.0, "{format:?} alpha"),
+                F::Rgb10a2Unorm => assert!((a - b).abs() <= 1.0 / 6.0, "{format:?} alpha {a} -> {b}"),
+                _ => assert!((a - b).abs() <= 1e-3, "{format:?} alpha {a} -> {b}"),
+            }
+        }
+        // Decoded values encode back to the same bits.
+        let mut bytes2 = vec![0u8; bytes.len()];
+        format.encode(&rgb2, &alpha2, &mut bytes2);
+        assert_eq!(bytes, bytes2, "{format:?} re-encode");
+    }
+
+    // Byte order and packing of the 8-bit and packed formats.
+    let mut bgra = [0u8; 4];
+    F::Bgra8Unorm.encode(&[1.0, 0.0, 0.0], &[1.0], &mut bgra);
+    assert_eq!(bgra, [0, 0, 255, 255]);
+    let mut packed = [0u8; 4];
+    F::Rgb10a2Unorm.encode(&[1.0, 0.0, 0.0], &[1.0], &mut packed);
+    assert_eq!(u32::from_le_bytes(packed), 0x3FF | (3 << 30));
+    F::Rg11b10Ufloat.encode(&[1.0, 1.0, 1.0], &[1.0], &mut packed);
+    assert_eq!(u32::from_le_bytes(packed), 0x3C0 | (0x3C0 << 11) | (0x1E0 << 22));
+    F::Rgb9e5Ufloat.encode(&[1.0, 0.5, 0.0], &[1.0], &mut packed);
+    let mut rgb = [0.0f32; 3];
+    F::Rgb9e5Ufloat.decode(&packed, &mut rgb, &mut [0.0]);
+    assert_eq!(rgb, [1.0, 0.5, 0.0]);
+    // Out-of-range input is clamped rather than wrapped.
+    F::Rg11b10Ufloat.encode(&[-1.0, f32::NAN, 1e9], &[1.0], &mut packed);
+    F::Rg11b10Ufloat.decode(&packed, &mut rgb, &mut [0.0]);
+    assert_eq!(rgb[..2], [0.0, 0.0]);
+    assert!(rgb[2].is_finite() && rgb[2] > 60000.0);
+}
+
+/// Headless wgpu device, or `None` if the machine has no adapter.
+/// A wgpu device for the texture tests. Without a usable adapter, prints why the calling test is
+/// skipped and returns `None` (the test then returns early), or panics if
+/// `OIDN_WGPU_REQUIRE_ADAPTER` is set so CI cannot count a skip as a pass.
+fn wgpu_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let result = pollster::block_on(async {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions::default())
+            .await
+            .map_err(|e| e.to_string())?;
+        adapter
+            .request_device(&wgpu::DeviceDescriptor::default())
+            .await
+            .map_err(|e| e.to_string())
+    });
+    match result {
+        Ok(device) => Some(device),
+        Err(reason) if std::env::var_os("OIDN_WGPU_REQUIRE_ADAPTER").is_some() => {
+            panic!("no wgpu device: {reason}")
+        }
+        Err(reason) => {
+            let thread = std::thread::current();
+            eprintln!("skipped {}: no wgpu device: {reason}", thread.name().unwrap_or("test"));
+            None
+        }
+    }
+}
+
+/// `color` denoised on slices by an [`RtFilter`] set up as `options` sets it up for `format`:
+/// the reference for the wgpu paths.
+#[allow(clippy::too_many_arguments)]
+fn reference_denoise(
+    device: &OidnDevice,
+    width: u32,
+    height: u32,
+    format: crate::DenoiseTextureFormat,
+    options: &crate::DenoiseOptions,
+    color: &[f32],
+    albedo: Option<&[f32]>,
+    normal: Option<&[f32]>,
+) -> Vec<f32> {
+    let mut filter = RtFilter::new(device).expect("reference RT filter");
+    filter.set_dimensions(width, height);
+    options.apply(&mut filter, format);
+    let mut output = color.to_vec();
+    filter
+        .execute_in_place_with_aux(&mut output, albedo, normal)
+        .expect("reference execute");
+    output
+}
+
+fn test_texture(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    format: crate::DenoiseTextureFormat,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("oidn_wgpu test"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: format.to_wgpu(),
+        usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
+
+#[test]
+fn test_texture_denoiser_reuse_and_resize() {
+    use crate::wgpu_integration::{read_texture_to_rgba_f32, upload_rgba_to_texture};
+    use crate::{AuxImages, DenoiseOptions, DenoiseTextureFormat, Error, TextureDenoiser};
+
+    let Some((wgpu_device, queue)) = wgpu_device() else {
+        return;
+    };
+    let device = OidnDevice::cpu().expect("CPU device");
+    let format = DenoiseTextureFormat::Rgba16Float;
+    let fill = |texture: &wgpu::Texture, seed: f32| {
+        let size = texture.size();
+        let n = (size.width * size.height) as usize;
+        let rgb: Vec<f32> = (0..n * 3).map(|i| (i as f32 * seed).fract()).collect();
+        let alpha: Vec<f32> = (0..n).map(|i| (i % 4) as f32 * 0.25).collect();
+        upload_rgba_to_texture(&wgpu_device, &queue, texture, format, size.width, size.height, &rgb, &alpha)
+            .expect("upload");
+    };
+
+    let (w, h) = (40, 12);
+    let input = test_texture(&wgpu_device, w, h, format);
+    let output = test_texture(&wgpu_device, w, h, format);
+    let albedo = test_texture(&wgpu_device, w, h, format);
+    fill(&albedo, 0.37);
+    let options = DenoiseOptions::default();
+    let mut denoiser = TextureDenoiser::new(
+        &device,
+        &wgpu_device,
+        w,
+        h,
+        format,
+        AuxImages::Albedo,
+        &options,
+    )
+    .expect("texture denoiser");
+
+    let (albedo_rgb, _) = read_texture_to_rgba_f32(&wgpu_device, &queue, &albedo, format).expect("readback");
+    for seed in [0.013, 0.071] {
+        fill(&input, seed);
+        denoiser
+            .denoise_with_aux(&queue, &input, &output, Some(&albedo), None)
+            .expect("denoise");
+        let (color, alpha) = read_texture_to_rgba_f32(&wgpu_device, &queue, &input, format).expect("readback");
+        let expected = reference_denoise(&device, w, h, format, &options, &color, Some(&albedo_rgb), None);
+        let (actual, actual_alpha) =
+            read_texture_to_rgba_f32(&wgpu_device, &queue, &output, format).expect("readback");
+        assert_close_texels(&actual, &expected, format, "reused denoiser");
+        assert_eq!(actual_alpha, alpha);
+    }
+
+    assert!(matches!(
+        denoiser.denoise(&queue, &input, &output),
+        Err(Error::MissingImage(ref n)) if n == "albedo"
+    ));
+    assert!(matches!(
+        denoiser.denoise_with_aux(&queue, &input, &output, Some(&albedo), Some(&albedo)),
+        Err(Error::UnsupportedImage(ref n)) if n == "normal"
+    ));
+
+    denoiser.set_dimensions(24, 24).expect("resize");
+    assert!(matches!(
+        denoiser.denoise_with_aux(&queue, &input, &output, Some(&albedo), None),
+        Err(Error::InvalidDimensions)
+    ));
+    let input = test_texture(&wgpu_device, 24, 24, format);
+    let albedo = test_texture(&wgpu_device, 24, 24, format);
+    fill(&input, 0.029);
+    fill(&albedo, 0.37);
+    let (color, _) = read_texture_to_rgba_f32(&wgpu_device, &queue, &input, format).expect("readback");
+    let (albedo_rgb, _) = read_texture_to_rgba_f32(&wgpu_device, &queue, &albedo, format).expect("readback");
+    denoiser
+        .denoise_with_aux(&queue, &input, &input, Some(&albedo), None)
+        .expect("in-place denoise after resize");
+    let expected = reference_denoise(&device, 24, 24, format, &options, &color, Some(&albedo_rgb), None);
+    let (actual, _) = read_texture_to_rgba_f32(&wgpu_device, &queue, &input, format).expect("readback");
+    assert_close_texels(&actual, &expected, format, "in place after resize");
+    assert!(denoiser.set_dimensions(0, 24).is_err());
+}
+
+#[test]
+fn test_texture_denoiser_set_options_resets_input_scale() {
+    use crate::wgpu_integration::{read_texture_to_rgba_f32, upload_rgba_to_texture};
+    use crate::{AuxImages, DenoiseOptions, DenoiseTextureFormat, TextureDenoiser};
+
+    let Some((wgpu_device, queue)) = wgpu_device() else {
+        return;
+    };
+    let device = OidnDevice::cpu().expect("CPU device");
+    let format = DenoiseTextureFormat::Rgba32Float;
+    let (w, h) = (16, 16);
+    let n = (w * h) as usize;
+    let input = test_texture(&wgpu_device, w, h, format);
+    let output = test_texture(&wgpu_device, w, h, format);
+    let rgb: Vec<f32> = (0..n * 3).map(|i| ((i * 29) % 97) as f32 / 8.0).collect();
+    upload_rgba_to_texture(&wgpu_device, &queue, &input, format, w, h, &rgb, &vec![1.0; n]).expect("upload");
+    let (color, _) = read_texture_to_rgba_f32(&wgpu_device, &queue, &input, format).expect("readback");
+
+    let mut denoiser =
+        TextureDenoiser::new(&device, &wgpu_device, w, h, format, AuxImages::None, &DenoiseOptions::default())
+            .expect("texture denoiser");
+    // Back to the default after an explicit scale: the filter must return to automatic scaling.
+    for options in [DenoiseOptions::default().with_input_scale(0.25), DenoiseOptions::default()] {
+        denoiser.set_options(&options);
+        denoiser.denoise(&queue, &input, &output).expect("denoise");
+        let expected = reference_denoise(&device, w, h, format, &options, &color, None, None);
+        let (actual, _) = read_texture_to_rgba_f32(&wgpu_device, &queue, &output, format).expect("readback");
+        assert_close(&actual, &expected, &format!("{options:?}"));
+    }
+}
+
+#[test]
+fn test_denoise_texture_normals_need_signed_format() {
+    use crate::wgpu_integration::{read_texture_to_rgba_f32, upload_rgba_to_texture};
+    use crate::{
+        denoise_texture_with_aux, AuxImages, DenoiseOptions, DenoiseTextureFormat, Error,
+        PrefilteredRtDenoiser, TextureDenoiser,
+    };
+
+    let Some((wgpu_device, queue)) = wgpu_device() else {
+        return;
+    };
+    let device = OidnDevice::cpu().expect("CPU device");
+    let (w, h) = (12, 10);
+    let n = (w * h) as usize;
+    let color_rgb: Vec<f32> = (0..n * 3).map(|i| ((i * 13) % 31) as f32 / 30.0).collect();
+    let albedo_rgb = vec![0.5; n * 3];
+    // Normals facing away from +x/+y: negative components must reach OIDN unchanged.
+    let normal_rgb: Vec<f32> = (0..n).flat_map(|i| [-0.6, -0.48 * (i % 2) as f32, 0.64]).collect();
+    let alpha = vec![1.0; n];
+    let options = DenoiseOptions::default();
+
+    let format = DenoiseTextureFormat::Rgba16Float;
+    let textures = [&color_rgb, &albedo_rgb, &normal_rgb].map(|rgb| {
+        let texture = test_texture(&wgpu_device, w, h, format);
+        upload_rgba_to_texture(&wgpu_device, &queue, &texture, format, w, h, rgb, &alpha).expect("upload");
+        texture
+    });
+    let [input, albedo, normal] = &textures;
+    let [color, albedo_read, normal_read] = [input, albedo, normal].map(|texture| {
+        read_texture_to_rgba_f32(&wgpu_device, &queue, texture, format).expect("readback").0
+    });
+    assert!(normal_read.iter().any(|&v| v < 0.0));
+    let output = test_texture(&wgpu_device, w, h, format);
+    denoise_texture_with_aux(&device, &wgpu_device, &queue, input, &output, format, &options, Some(albedo), Some(normal))
+        .expect("denoise with signed normals");
+    let expected = reference_denoise(&device, w, h, format, &options, &color, Some(&albedo_read), Some(&normal_read));
+    let (actual, _) = read_texture_to_rgba_f32(&wgpu_device, &queue, &output, format).expect("readback");
+    assert_close_texels(&actual, &expected, format, "signed normals");
+
+    // Unsigned formats would clamp the negative components to 0: rejected.
+    let format = DenoiseTextureFormat::Rgba8Unorm;
+    let [input, albedo, normal, output] = [(); 4].map(|_| test_texture(&wgpu_device, w, h, format));
+    assert!(matches!(
+        denoise_texture_with_aux(&device, &wgpu_device, &queue, &input, &output, format, &options, Some(&albedo), Some(&normal)),
+        Err(Error::UnsupportedFormat)
+    ));
+    assert!(matches!(
+        TextureDenoiser::new(&device, &wgpu_device, w, h, format, AuxImages::AlbedoNormal, &options),
+        Err(Error::UnsupportedFormat)
+    ));
+    let mut prefiltered = PrefilteredRtDenoiser::new(&device).expect("prefiltered denoiser");
+    assert!(matches!(
+        prefiltered.denoise_texture(&wgpu_device, &queue, &input, &output, format, &albedo, Some(&normal)),
+        Err(Error::UnsupportedFormat)
+    ));
+    // Albedo alone is fine in an unsigned format.
+    denoise_texture_with_aux(&device, &wgpu_device, &queue, &input, &output, format, &options, Some(&albedo), None)
+        .expect("unsigned albedo");
+}
+
+#[test]
+fn test_denoise_texture_async() {
+    use crate::wgpu_integration::{read_texture_to_rgba_f32, upload_rgba_to_texture};
+    use crate::{
+        denoise_texture, denoise_texture_async, AuxImages, DenoiseOptions, DenoiseTextureFormat, Poller,
+        TextureDenoiser,
+    };
+    use std::future::Future;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Wake, Waker};
+
+    fn assert_send<T: Send>(_: &T) {}
+    struct NoopWake;
+    impl Wake for NoopWake {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let Some((wgpu_device, queue)) = wgpu_device() else {
+        return;
+    };
+    let device = OidnDevice::cpu().expect("CPU device");
+    let format = DenoiseTextureFormat::Rgba8Unorm;
+    let (w, h) = (33, 17);
+    let input = test_texture(&wgpu_device, w, h, format);
+    let output = test_texture(&wgpu_device, w, h, format);
+    let n = (w * h) as usize;
+    let rgb: Vec<f32> = (0..n * 3).map(|i| (i % 251) as f32 / 250.0).collect();
+    let alpha = vec![0.5; n];
+    upload_rgba_to_texture(&wgpu_device, &queue, &input, format, w, h, &rgb, &alpha).expect("upload");
+    let options = DenoiseOptions::default();
+
+    // The blocking path is the reference.
+    let blocking = test_texture(&wgpu_device, w, h, format);
+    denoise_texture(&device, &wgpu_device, &queue, &input, &blocking, format, &options).expect("blocking denoise");
+    let (expected, expected_alpha) =
+        read_texture_to_rgba_f32(&wgpu_device, &queue, &blocking, format).expect("readback");
+    let check_output = |context: &str| {
+        let (actual, actual_alpha) =
+            read_texture_to_rgba_f32(&wgpu_device, &queue, &output, format).expect("readback");
+        assert_close_texels(&actual, &expected, format, context);
+        assert_eq!(actual_alpha, expected_alpha, "{context}: alpha");
+    };
+
+    // One-shot, background poller.
+    let future = denoise_texture_async(&device, &wgpu_device, &queue, &input, &output, format, &options);
+    assert_send(&future);
+    pollster::block_on(future).expect("async denoise");
+    check_output("one-shot async");
+
+    // Reused denoiser, polled by the application.
+    let mut denoiser = TextureDenoiser::new(&device, &wgpu_device, w, h, format, AuxImages::None, &options)
+        .expect("texture denoiser");
+    denoiser.set_poller(Poller::External);
+
+    // A future dropped while its readback is pending leaves nothing mapped behind.
+    {
+        let waker = Waker::from(Arc::new(NoopWake));
+        let mut future = Box::pin(denoiser.denoise_async(&queue, &input, &output));
+        assert!(future.as_mut().poll(&mut Context::from_waker(&waker)).is_pending());
+    }
+
+    let done = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            while !done.load(Ordering::Acquire) {
+                let _ = wgpu_device.poll(wgpu::PollType::Poll);
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        });
+        for _ in 0..2 {
+            pollster::block_on(denoiser.denoise_async(&queue, &input, &output)).expect("async denoise");
+        }
+        done.store(true, Ordering::Release);
+    });
+    check_output("reused async");
+    denoiser.denoise(&queue, &input, &output).expect("blocking denoise after async");
+    check_output("blocking after async");
+}
+
+#[test]
+fn test_denoise_texture_regions() {
+    use crate::wgpu_integration::read_texture_to_rgba_f32;
+    use crate::{
+        denoise_texture_regions, AuxImages, DenoiseOptions, DenoiseTextureFormat, Error,
+        TextureDenoiser, TextureRegion,
+    };
+
+    let Some((wgpu_device, queue)) = wgpu_device() else {
+        return;
+    };
+    let device = OidnDevice::cpu().expect("CPU device");
+    let format = DenoiseTextureFormat::Rgba32Float;
+    // 32x16, two layers, two mips: mip 1 of layer 1 is 16x8.
+    let array = wgpu_device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("oidn_wgpu test array"),
+        size: wgpu::Extent3d {
+            width: 32,
+            height: 16,
+            depth_or_array_layers: 2,
+        },
+        mip_level_count: 2,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: format.to_wgpu(),
+        usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let texel = |x: u32, y: u32| [x as f32 / 16.0, y as f32 / 8.0, 0.25, 0.75];
+    let data: Vec<f32> = (0..8)
+        .flat_map(|y| (0..16).flat_map(move |x| texel(x, y)))
+        .collect();
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture: &array,
+            mip_level: 1,
+            origin: wgpu::Origin3d { x: 0, y: 0, z: 1 },
+            aspect: wgpu::TextureAspect::All,
+        },
+        bytemuck::cast_slice(&data),
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(16 * 16),
+            rows_per_image: Some(8),
+        },
+        wgpu::Extent3d {
+            width: 16,
+            height: 8,
+            depth_or_array_layers: 1,
+        },
+    );
+    let output = test_texture(&wgpu_device, 20, 10, format);
+
+    let input_region = TextureRegion::rect(3, 2, 8, 5).with_layer(1).with_mip_level(1);
+    let output_region = TextureRegion::rect(10, 4, 8, 5);
+    assert_eq!(input_region.size_in(&array).unwrap(), (8, 5));
+    assert_eq!(TextureRegion::default().with_mip_level(1).size_in(&array).unwrap(), (16, 8));
+    for bad in [
+        TextureRegion::default().with_layer(2),
+        TextureRegion::default().with_mip_level(2),
+        TextureRegion::rect(10, 0, 8, 5).with_mip_level(1),
+        TextureRegion::rect(0, 0, 0, 5),
+        TextureRegion::rect(u32::MAX, 0, 2, 1),
+    ] {
+        assert!(matches!(bad.size_in(&array), Err(Error::InvalidDimensions)));
+    }
+
+    denoise_texture_regions(
+        &device,
+        &wgpu_device,
+        &queue,
+        (&array, input_region),
+        (&output, output_region),
+        format,
+        &DenoiseOptions::default(),
+        None,
+        None,
+    )
+    .expect("denoise regions");
+    let (rgb, alpha) = read_texture_to_rgba_f32(&wgpu_device, &queue, &output, format).expect("readback");
+    // Inside the output region: the 8x5 input sub-image denoised on its own. Outside: untouched.
+    let sub_image: Vec<f32> = (2..7)
+        .flat_map(|y| (3..11).flat_map(move |x| texel(x, y)[..3].to_vec()))
+        .collect();
+    let denoised = reference_denoise(&device, 8, 5, format, &DenoiseOptions::default(), &sub_image, None, None);
+    let mut inside_rgb = Vec::new();
+    for y in 0..10 {
+        for x in 0..20 {
+            let i = (y * 20 + x) as usize;
+            if (10..18).contains(&x) && (4..9).contains(&y) {
+                inside_rgb.extend_from_slice(&rgb[i * 3..i * 3 + 3]);
+                assert_eq!(alpha[i], 0.75, "alpha ({x}, {y})");
+            } else {
+                assert_eq!(&rgb[i * 3..i * 3 + 3], &[0.0; 3], "texel ({x}, {y})");
+                assert_eq!(alpha[i], 0.0, "alpha ({x}, {y})");
+            }
+        }
+    }
+    assert_close(&inside_rgb, &denoised, "output region");
+
+    // Regions must match the denoiser's size.
+    let mut denoiser = TextureDenoiser::new(
+        &device,
+        &wgpu_device,
+        8,
+        5,
+        format,
+        AuxImages::Albedo,
+        &DenoiseOptions::default(),
+    )
+    .expect("texture denoiser");
+    assert!(matches!(
+        denoiser.denoise_regions(
+            &queue,
+            (&array, input_region),
+            (&output, TextureRegion::rect(0, 0, 8, 4)),
+            Some((&array, input_region.with_layer(0))),
+            None,
+        ),
+        Err(Error::InvalidDimensions)
+    ));
+    denoiser
+        .denoise_regions(
+            &queue,
+            (&array, input_region),
+            (&output, TextureRegion::rect(0, 0, 8, 5)),
+            Some((&array, input_region.with_layer(0))),
+            None,
+        )
+        .expect("denoise regions with albedo");
+}
+
+#[test]
+fn test_denoise_buffer_layouts() {
+    use crate::wgpu_integration::map_blocking;
+    use crate::{
+        denoise_buffer, denoise_buffer_with_aux, AuxImages, BufferDenoiser, BufferLayout,
+        DenoiseOptions, DenoiseTextureFormat, Error,
+    };
+    use wgpu::util::DeviceExt;
+
+    let Some((wgpu_device, queue)) = wgpu_device() else {
+        return;
+    };
+    let device = OidnDevice::cpu().expect("CPU device");
+    let read_bytes = |buffer: &wgpu::Buffer| -> Vec<u8> {
+        let staging = wgpu_device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: buffer.size(),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = wgpu_device.create_command_encoder(&Default::default());
+        encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+        queue.submit(Some(encoder.finish()));
+        map_blocking(&wgpu_device, staging.slice(..), wgpu::MapMode::Read).expect("map");
+        let bytes = staging.slice(..).get_mapped_range().to_vec();
+        bytes
+    };
+    // Mapped copies are not necessarily 4-byte aligned, so no cast_slice.
+    let f32s = |bytes: &[u8]| -> Vec<f32> {
+        bytes.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect()
+    };
+
+    let (w, h) = (7u32, 5u32);
+    // Path tracer output: radiance then albedo as vec4<f16>, rows padded to 64 bytes, after a
+    // 16-byte header.
+    let f16_layout = BufferLayout::new(w, h, DenoiseTextureFormat::Rgba16Float)
+        .with_row_stride(64)
+        .with_offset(16);
+    let albedo_layout = f16_layout.with_offset(16 + 64 * u64::from(h));
+    assert_eq!(f16_layout.span().unwrap(), 64 * 4 + 7 * 8);
+    let mut src = vec![0u8; 16 + 2 * 64 * h as usize];
+    let value = |x: u32, y: u32, c: u32| (x * 16 + y * 4 + c) as f32 / 128.0;
+    for (base, scale) in [(16usize, 1.0f32), (16 + 64 * h as usize, 0.5)] {
+        for y in 0..h {
+            for x in 0..w {
+                for c in 0..4 {
+                    let v = half::f16::from_f32(value(x, y, c) * scale).to_bits();
+                    let i = base + (y * 64 + x * 8 + c * 2) as usize;
+                    src[i..i + 2].copy_from_slice(&v.to_le_bytes());
+                }
+            }
+        }
+    }
+    let usage = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST;
+    let input = wgpu_device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("oidn_wgpu test radiance"),
+        contents: &src,
+        usage,
+    });
+
+    // The reference denoises the f16 data as decoded on the host.
+    let options = DenoiseOptions::default();
+    let decoded = |scale: f32, c: u32| -> Vec<f32> {
+        (0..h)
+            .flat_map(|y| (0..w).map(move |x| (x, y)))
+            .flat_map(|(x, y)| (0..c).map(move |c| half::f16::from_f32(value(x, y, c) * scale).to_f32()))
+            .collect()
+    };
+    let expected = reference_denoise(
+        &device,
+        w,
+        h,
+        DenoiseTextureFormat::Rgba16Float,
+        &options,
+        &decoded(1.0, 3),
+        Some(&decoded(0.5, 3)),
+        None,
+    );
+    let expected_alpha: Vec<f32> = decoded(1.0, 4).chunks(4).map(|texel| texel[3]).collect();
+
+    // Output: vec4<f32>, rows padded to 128 bytes (the padding must survive).
+    let out_layout = BufferLayout::new(w, h, DenoiseTextureFormat::Rgba32Float).with_row_stride(128);
+    let output = wgpu_device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("oidn_wgpu test output"),
+        contents: &vec![0xab; 128 * h as usize],
+        usage,
+    });
+    denoise_buffer_with_aux(
+        &device,
+        &wgpu_device,
+        &queue,
+        (&input, f16_layout),
+        (&output, out_layout),
+        &options,
+        Some((&input, albedo_layout)),
+        None,
+    )
+    .expect("denoise buffer");
+    let check_output = |context: &str| {
+        let out = read_bytes(&output);
+        let (mut rgb, mut alpha) = (Vec::new(), Vec::new());
+        for y in 0..h {
+            let row = &out[(y * 128) as usize..((y + 1) * 128) as usize];
+            let texels = f32s(&row[..(w * 16) as usize]);
+            for texel in texels.chunks(4) {
+                rgb.extend_from_slice(&texel[..3]);
+                alpha.push(texel[3]);
+            }
+            assert!(row[(w * 16) as usize..].iter().all(|&b| b == 0xab), "{context}: row {y} padding");
+        }
+        assert_close(&rgb, &expected, context);
+        assert_eq!(alpha, expected_alpha, "{context}: alpha");
+    };
+    check_output("one-shot");
+
+    // Reused, created for smaller layouts: the staging buffers grow on the first call.
+    let mut denoiser = BufferDenoiser::new(
+        &device,
+        &wgpu_device,
+        BufferLayout::new(w, h, DenoiseTextureFormat::Rgba16Float),
+        AuxImages::Albedo,
+        &options,
+    )
+    .expect("buffer denoiser");
+    for _ in 0..2 {
+        denoiser
+            .denoise_with_aux(&queue, (&input, f16_layout), (&output, out_layout), Some((&input, albedo_layout)), None)
+            .expect("reused denoise");
+        check_output("reused");
+    }
+    assert!(matches!(
+        denoiser.denoise(&queue, (&input, f16_layout), (&output, out_layout)),
+        Err(Error::MissingImage(ref n)) if n == "albedo"
+    ));
+
+    // In place, tightly packed.
+    let packed = BufferLayout::new(w, h, DenoiseTextureFormat::Rgba32Float);
+    let before = read_bytes(&output);
+    let color: Vec<f32> = f32s(&before[..(w * h * 16) as usize])
+        .chunks(4)
+        .flat_map(|texel| texel[..3].to_vec())
+        .collect();
+    denoise_buffer(&device, &wgpu_device, &queue, (&output, packed), (&output, packed), &options)
+        .expect("in-place denoise");
+    let after = read_bytes(&output);
+    let denoised: Vec<f32> = f32s(&after[..(w * h * 16) as usize])
+        .chunks(4)
+        .flat_map(|texel| texel[..3].to_vec())
+        .collect();
+    let packed_expected = reference_denoise(&device, w, h, packed.format, &options, &color, None, None);
+    assert_close(&denoised, &packed_expected, "in place");
+
+    for bad in [
+        f16_layout.with_offset(2),
+        f16_layout.with_row_stride(40),
+        f16_layout.with_row_stride(66),
+        BufferLayout::new(0, h, DenoiseTextureFormat::Rgba16Float),
+    ] {
+        assert!(matches!(bad.span(), Err(Error::InvalidDimensions)), "{bad:?}");
+    }
+    let too_far = f16_layout.with_offset(input.size());
+    let smaller = BufferLayout::new(w, h - 1, DenoiseTextureFormat::Rgba32Float);
+    for (inp, out) in [(too_far, out_layout), (f16_layout, smaller)] {
+        assert!(matches!(
+            denoise_buffer(&device, &wgpu_device, &queue, (&input, inp), (&output, out), &options),
+            Err(Error::InvalidDimensions)
+        ));
+    }
+
+    // Normals need a signed format; the same bytes as unorm would lose negative components.
+    let unorm_normal = BufferLayout::new(w, h, DenoiseTextureFormat::Rgba8Unorm);
+    assert!(matches!(
+        denoise_buffer_with_aux(
+            &device,
+            &wgpu_device,
+            &queue,
+            (&input, f16_layout),
+            (&output, out_layout),
+            &options,
+            Some((&input, albedo_layout)),
+            Some((&input, unorm_normal)),
+        ),
+        Err(Error::UnsupportedFormat)
+    ));
+}
+
+#[test]
+fn test_buffer_denoiser_set_options_resets_input_scale() {
+    use crate::wgpu_integration::map_blocking;
+    use crate::{AuxImages, BufferDenoiser, BufferLayout, DenoiseOptions, DenoiseTextureFormat};
+    use wgpu::util::DeviceExt;
+
+    let Some((wgpu_device, queue)) = wgpu_device() else {
+        return;
+    };
+    let device = OidnDevice::cpu().expect("CPU device");
+    let (w, h) = (16u32, 16u32);
+    let layout = BufferLayout::new(w, h, DenoiseTextureFormat::Rgba32Float);
+    let rgba: Vec<f32> = (0..w * h * 4)
+        .map(|i| if i % 4 == 3 { 1.0 } else { ((i * 29) % 97) as f32 / 8.0 })
+        .collect();
+    let color: Vec<f32> = rgba.chunks(4).flat_map(|texel| texel[..3].to_vec()).collect();
+    let usage = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST;
+    let input = wgpu_device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("oidn_wgpu test radiance"),
+        contents: bytemuck::cast_slice(&rgba),
+        usage,
+    });
+    let output = wgpu_device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("oidn_wgpu test output"),
+        contents: &vec![0; rgba.len() * 4],
+        usage,
+    });
+    let read_rgb = || -> Vec<f32> {
+        let staging = wgpu_device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: output.size(),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = wgpu_device.create_command_encoder(&Default::default());
+        encoder.copy_buffer_to_buffer(&output, 0, &staging, 0, output.size());
+        queue.submit(Some(encoder.finish()));
+        map_blocking(&wgpu_device, staging.slice(..), wgpu::MapMode::Read).expect("map");
+        let bytes = staging.slice(..).get_mapped_range();
+        let texels: Vec<f32> =
+            bytes.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect();
+        texels.chunks(4).flat_map(|texel| texel[..3].to_vec()).collect()
+    };
+
+    let mut denoiser =
+        BufferDenoiser::new(&device, &wgpu_device, layout, AuxImages::None, &DenoiseOptions::default())
+            .expect("buffer denoiser");
+    // Back to the default after an explicit scale: the filter must return to automatic scaling.
+    for options in [DenoiseOptions::default().with_input_scale(0.25), DenoiseOptions::default()] {
+        denoiser.set_options(&options);
+        denoiser.denoise(&queue, (&input, layout), (&output, layout)).expect("denoise");
+        let expected = reference_denoise(&device, w, h, layout.format, &options, &color, None, None);
+        assert_close(&read_rgb(), &expected, &format!("{options:?}"));
+    }
+}
+
+#[test]
+fn test_prefiltered_denoise_srgb_texture() {
+    use crate::wgpu_integration::{read_texture_to_rgba_f32, upload_rgba_to_texture};
+    use crate::{DenoiseTextureFormat, PrefilteredRtDenoiser};
+
+    let Some((wgpu_device, queue)) = wgpu_device() else {
+        return;
+    };
+    let device = OidnDevice::cpu().expect("CPU device");
+    let format = DenoiseTextureFormat::Rgba8UnormSrgb;
+    let (w, h) = (16, 12);
+    let n = (w * h) as usize;
+    let input = test_texture(&wgpu_device, w, h, format);
+    let output = test_texture(&wgpu_device, w, h, format);
+    let albedo = test_texture(&wgpu_device, w, h, format);
+    let color_rgb: Vec<f32> = (0..n * 3).map(|i| ((i * 37) % 101) as f32 / 100.0).collect();
+    let albedo_rgb: Vec<f32> = (0..n * 3).map(|i| 0.2 + (i % 7) as f32 / 10.0).collect();
+    let alpha = vec![1.0; n];
+    upload_rgba_to_texture(&wgpu_device, &queue, &input, format, w, h, &color_rgb, &alpha).expect("upload");
+    upload_rgba_to_texture(&wgpu_device, &queue, &albedo, format, w, h, &albedo_rgb, &alpha).expect("upload");
+
+    let mut denoiser = PrefilteredRtDenoiser::new(&device).expect("prefiltered denoiser");
+    denoiser
+        .denoise_texture(&wgpu_device, &queue, &input, &output, format, &albedo, None)
+        .expect("denoise sRGB texture");
+    let (actual, _) = read_texture_to_rgba_f32(&wgpu_device, &queue, &output, format).expect("readback");
+
+    // The same texels denoised as sRGB-encoded LDR on slices.
+    let (mut expected, _) = read_texture_to_rgba_f32(&wgpu_device, &queue, &input, format).expect("readback");
+    let (albedo_read, _) = read_texture_to_rgba_f32(&wgpu_device, &queue, &albedo, format).expect("readback");
+    let mut reference = PrefilteredRtDenoiser::new(&device).expect("prefiltered denoiser");
+    reference
+        .set_dimensions(w, h)
+        .set_hdr(false)
+        .set_srgb(true)
+        .execute_in_place(&mut expected, &albedo_read, None)
+        .expect("reference execute");
+    assert_close_texels(&actual, &expected, format, "sRGB texture");
+}
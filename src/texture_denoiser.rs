@@ -0,0 +1,488 @@
+//! Denoising wgpu textures every frame without per-call allocations.
+//!
+//! See [`TextureDenoiser`].
+
+use crate::async_map::{map_buffer, Poller};
+use crate::device::OidnDevice;
+use crate::filter::RtFilter;
+use crate::memory::AuxImages;
+use crate::wgpu_integration::{
+    map_blocking, DenoiseOptions, DenoiseTextureFormat, StagingLayout, TextureRegion, TextureTarget,
+};
+use crate::Error;
+
+/// Denoises wgpu textures of one size, format and set of aux images, reusing its resources.
+///
+/// Owns the RT filter, a readback buffer for color and the aux images, an upload buffer and the
+/// host-side RGB/alpha scratch. They are allocated once and only reallocated by
+/// [`Self::set_dimensions`] when the size changes, so an interactive viewport can denoise every
+/// frame without churn. [`denoise_texture`](crate::denoise_texture) and
+/// [`denoise_texture_with_aux`](crate::denoise_texture_with_aux) are one-shot uses of this type.
+#[derive(Debug)]
+pub struct TextureDenoiser {
+    wgpu_device: wgpu::Device,
+    filter: RtFilter,
+    layout: StagingLayout,
+    aux: AuxImages,
+    poller: Poller,
+    /// Color, then albedo and normal (if used), back to back.
+    readback: Staging,
+    upload: Staging,
+    color: Vec<f32>,
+    alpha: Vec<f32>,
+    albedo: Vec<f32>,
+    normal: Vec<f32>,
+}
+
+impl TextureDenoiser {
+    /// Creates a denoiser for `width` x `height` textures of `format`, with the aux textures in
+    /// `aux` (same size and format as color).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDimensions`] if a dimension is zero, [`Error::UnsupportedFormat`]
+    /// if `aux` includes normals and `format` cannot hold negative values (see
+    /// [`DenoiseTextureFormat::is_signed`]), or the error of [`RtFilter::new`].
+    pub fn new(
+        device: &OidnDevice,
+        wgpu_device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: DenoiseTextureFormat,
+        aux: AuxImages,
+        options: &DenoiseOptions,
+    ) -> Result<Self, Error> {
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidDimensions);
+        }
+        if aux == AuxImages::AlbedoNormal && !format.is_signed() {
+            return Err(Error::UnsupportedFormat);
+        }
+        let mut filter = RtFilter::new(device)?;
+        filter.set_dimensions(width, height);
+        options.apply(&mut filter, format);
+        let layout = StagingLayout::new(width, height, format);
+        let (readback, upload) = create_staging(wgpu_device, layout, aux);
+        let mut denoiser = Self {
+            wgpu_device: wgpu_device.clone(),
+            filter,
+            layout,
+            aux,
+            poller: Poller::default(),
+            readback,
+            upload,
+            color: Vec::new(),
+            alpha: Vec::new(),
+            albedo: Vec::new(),
+            normal: Vec::new(),
+        };
+        denoiser.resize_scratch();
+        Ok(denoiser)
+    }
+
+    /// Changes the texture size. Buffers and scratch are reallocated only if it differs from the
+    /// current size.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDimensions`] if a dimension is zero.
+    pub fn set_dimensions(&mut self, width: u32, height: u32) -> Result<&mut Self, Error> {
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidDimensions);
+        }
+        if (width, height) != (self.layout.width, self.layout.height) {
+            self.layout = StagingLayout::new(width, height, self.layout.format);
+            (self.readback, self.upload) = create_staging(&self.wgpu_device, self.layout, self.aux);
+            self.filter.set_dimensions(width, height);
+            self.resize_scratch();
+        }
+        Ok(self)
+    }
+
+    /// Applies new options to the filter (format-derived `hdr`/`srgb` follow this denoiser's
+    /// format).
+    pub fn set_options(&mut self, options: &DenoiseOptions) -> &mut Self {
+        options.apply(&mut self.filter, self.layout.format);
+        self
+    }
+
+    /// Who polls the wgpu device in [`Self::denoise_async`]. Default: [`Poller::Background`].
+    pub fn set_poller(&mut self, poller: Poller) -> &mut Self {
+        self.poller = poller;
+        self
+    }
+
+    /// Texture width.
+    pub fn width(&self) -> u32 {
+        self.layout.width
+    }
+
+    /// Texture height.
+    pub fn height(&self) -> u32 {
+        self.layout.height
+    }
+
+    /// Texture format.
+    pub fn format(&self) -> DenoiseTextureFormat {
+        self.layout.format
+    }
+
+    /// Aux images passed with color.
+    pub fn aux(&self) -> AuxImages {
+        self.aux
+    }
+
+    /// The filter, e.g. for a progress monitor or [`RtFilter::set_max_memory_mb`]. Its dimensions
+    /// are managed by this denoiser.
+    pub fn filter_mut(&mut self) -> &mut RtFilter {
+        &mut self.filter
+    }
+
+    /// Denoises `input` into `output` (may be the same texture) for a denoiser created with
+    /// [`AuxImages::None`]. Blocking, like [`denoise_texture`](crate::denoise_texture).
+    ///
+    /// # Errors
+    ///
+    /// As [`Self::denoise_with_aux`].
+    pub fn denoise(
+        &mut self,
+        wgpu_queue: &wgpu::Queue,
+        input: &wgpu::Texture,
+        output: &wgpu::Texture,
+    ) -> Result<(), Error> {
+        self.denoise_with_aux(wgpu_queue, input, output, None, None)
+    }
+
+    /// Denoises `input` into `output` (may be the same texture) with the aux textures this
+    /// denoiser was created for. Only RGB is denoised; alpha is preserved. Mip level 0 and array
+    /// layer 0 are used; see [`Self::denoise_regions`].
+    ///
+    /// # Errors
+    ///
+    /// As [`Self::denoise_regions`].
+    pub fn denoise_with_aux(
+        &mut self,
+        wgpu_queue: &wgpu::Queue,
+        input: &wgpu::Texture,
+        output: &wgpu::Texture,
+        albedo: Option<&wgpu::Texture>,
+        normal: Option<&wgpu::Texture>,
+    ) -> Result<(), Error> {
+        self.denoise_regions(
+            wgpu_queue,
+            whole(input),
+            whole(output),
+            albedo.map(whole),
+            normal.map(whole),
+        )
+    }
+
+    /// Denoises a region of `input` into a region of `output` (the same texture and region for
+    /// in place), with aux regions. Every region must have this denoiser's size; texels of
+    /// `output` outside its region are left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDimensions`] if a region does not fit its texture (see
+    /// [`TextureRegion::size_in`]) or differs from the denoiser's size, [`Error::MissingImage`] /
+    /// [`Error::UnsupportedImage`] if the aux regions do not match [`Self::aux`],
+    /// [`Error::BufferMapFailed`] if mapping a staging buffer fails, or an OIDN error.
+    pub fn denoise_regions(
+        &mut self,
+        wgpu_queue: &wgpu::Queue,
+        input: TextureTarget<'_>,
+        output: TextureTarget<'_>,
+        albedo: Option<TextureTarget<'_>>,
+        normal: Option<TextureTarget<'_>>,
+    ) -> Result<(), Error> {
+        self.submit_readback(wgpu_queue, input, output, albedo, normal)?;
+        self.readback
+            .map_blocking(&self.wgpu_device, wgpu::MapMode::Read)?;
+        self.decode_readback(albedo.is_some(), normal.is_some());
+        self.filter.execute_in_place_with_aux(
+            &mut self.color,
+            albedo.is_some().then_some(&self.albedo[..]),
+            normal.is_some().then_some(&self.normal[..]),
+        )?;
+        self.upload
+            .map_blocking(&self.wgpu_device, wgpu::MapMode::Write)?;
+        self.submit_upload(wgpu_queue, output);
+        Ok(())
+    }
+
+    /// [`Self::denoise`] without blocking: awaits the readback and upload mappings (polled as
+    /// set by [`Self::set_poller`]) and the OIDN execution. Works with any executor.
+    ///
+    /// # Errors
+    ///
+    /// As [`Self::denoise_regions`].
+    pub async fn denoise_async(
+        &mut self,
+        wgpu_queue: &wgpu::Queue,
+        input: &wgpu::Texture,
+        output: &wgpu::Texture,
+    ) -> Result<(), Error> {
+        self.denoise_with_aux_async(wgpu_queue, input, output, None, None)
+            .await
+    }
+
+    /// [`Self::denoise_with_aux`] without blocking. See [`Self::denoise_async`].
+    ///
+    /// # Errors
+    ///
+    /// As [`Self::denoise_regions`].
+    pub async fn denoise_with_aux_async(
+        &mut self,
+        wgpu_queue: &wgpu::Queue,
+        input: &wgpu::Texture,
+        output: &wgpu::Texture,
+        albedo: Option<&wgpu::Texture>,
+        normal: Option<&wgpu::Texture>,
+    ) -> Result<(), Error> {
+        self.denoise_regions_async(
+            wgpu_queue,
+            whole(input),
+            whole(output),
+            albedo.map(whole),
+            normal.map(whole),
+        )
+        .await
+    }
+
+    /// [`Self::denoise_regions`] without blocking. See [`Self::denoise_async`].
+    ///
+    /// Dropping the future before it completes is safe; the next denoise starts over. So is leaking
+    /// it (e.g. with `mem::forget`): OIDN only works on the denoiser's own buffers, and the next
+    /// denoise waits for the device first.
+    ///
+    /// # Errors
+    ///
+    /// As [`Self::denoise_regions`].
+    pub async fn denoise_regions_async(
+        &mut self,
+        wgpu_queue: &wgpu::Queue,
+        input: TextureTarget<'_>,
+        output: TextureTarget<'_>,
+        albedo: Option<TextureTarget<'_>>,
+        normal: Option<TextureTarget<'_>>,
+    ) -> Result<(), Error> {
+        self.submit_readback(wgpu_queue, input, output, albedo, normal)?;
+        self.readback
+            .map_async(&self.wgpu_device, wgpu::MapMode::Read, self.poller)
+            .await?;
+        self.decode_readback(albedo.is_some(), normal.is_some());
+        self.filter
+            .execute_async_with_aux(
+                None,
+                &mut self.color,
+                albedo.is_some().then_some(&self.albedo[..]),
+                normal.is_some().then_some(&self.normal[..]),
+            )?
+            .await?;
+        self.upload
+            .map_async(&self.wgpu_device, wgpu::MapMode::Write, self.poller)
+            .await?;
+        self.submit_upload(wgpu_queue, output);
+        Ok(())
+    }
+
+    /// Validates the regions and submits the copies of color and aux into the readback buffer.
+    fn submit_readback(
+        &mut self,
+        wgpu_queue: &wgpu::Queue,
+        input: TextureTarget<'_>,
+        output: TextureTarget<'_>,
+        albedo: Option<TextureTarget<'_>>,
+        normal: Option<TextureTarget<'_>>,
+    ) -> Result<(), Error> {
+        check_aux("albedo", self.aux != AuxImages::None, albedo.is_some())?;
+        check_aux(
+            "normal",
+            self.aux == AuxImages::AlbedoNormal,
+            normal.is_some(),
+        )?;
+        let size = (self.layout.width, self.layout.height);
+        for (texture, region) in [Some(input), Some(output), albedo, normal]
+            .into_iter()
+            .flatten()
+        {
+            if region.size_in(texture)? != size {
+                return Err(Error::InvalidDimensions);
+            }
+        }
+        self.readback.reset();
+        self.upload.reset();
+
+        let image_size = self.layout.size();
+        let mut encoder = self
+            .wgpu_device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let inputs = [Some(input), albedo, normal];
+        for (i, (texture, region)) in inputs.into_iter().flatten().enumerate() {
+            encoder.copy_texture_to_buffer(
+                region.copy_info(texture),
+                self.layout
+                    .buffer_copy(&self.readback.buffer, i as u64 * image_size),
+                self.layout.extent(),
+            );
+        }
+        wgpu_queue.submit(Some(encoder.finish()));
+        Ok(())
+    }
+
+    /// Decodes the mapped readback buffer into the scratch images and unmaps it.
+    fn decode_readback(&mut self, albedo: bool, normal: bool) {
+        let image_size = self.layout.size() as usize;
+        {
+            let mapped = self.readback.buffer.slice(..).get_mapped_range();
+            let image = |i: usize| &mapped[i * image_size..(i + 1) * image_size];
+            // Aux alpha is not needed: decode it into `alpha` before color overwrites it.
+            if albedo {
+                self.layout
+                    .decode(image(1), &mut self.albedo, &mut self.alpha);
+            }
+            if normal {
+                self.layout
+                    .decode(image(2), &mut self.normal, &mut self.alpha);
+            }
+            self.layout
+                .decode(image(0), &mut self.color, &mut self.alpha);
+        }
+        self.readback.reset();
+    }
+
+    /// Encodes the denoised scratch into the mapped upload buffer, unmaps it and submits the
+    /// copy to `output`.
+    fn submit_upload(&mut self, wgpu_queue: &wgpu::Queue, (output, region): TextureTarget<'_>) {
+        self.layout.encode(
+            &self.color,
+            &self.alpha,
+            &mut self.upload.buffer.slice(..).get_mapped_range_mut(),
+        );
+        self.upload.reset();
+        let mut encoder = self
+            .wgpu_device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_buffer_to_texture(
+            self.layout.buffer_copy(&self.upload.buffer, 0),
+            region.copy_info(output),
+            self.layout.extent(),
+        );
+        wgpu_queue.submit(Some(encoder.finish()));
+    }
+
+    fn resize_scratch(&mut self) {
+        let n = self.layout.pixels();
+        self.color.resize(n * 3, 0.0);
+        self.alpha.resize(n, 0.0);
+        let aux_len = |used: bool| if used { n * 3 } else { 0 };
+        self.albedo
+            .resize(aux_len(self.aux != AuxImages::None), 0.0);
+        self.normal
+            .resize(aux_len(self.aux == AuxImages::AlbedoNormal), 0.0);
+    }
+}
+
+/// The whole of mip level 0, layer 0 of `texture`.
+fn whole(texture: &wgpu::Texture) -> TextureTarget<'_> {
+    (texture, TextureRegion::default())
+}
+
+/// Checks that an aux image is passed exactly when the denoiser uses it.
+pub(crate) fn check_aux(name: &str, used: bool, passed: bool) -> Result<(), Error> {
+    match (used, passed) {
+        (true, false) => Err(Error::MissingImage(name.to_owned())),
+        (false, true) => Err(Error::UnsupportedImage(name.to_owned())),
+        _ => Ok(()),
+    }
+}
+
+/// A staging buffer and whether a mapping of it was requested and not yet undone.
+#[derive(Debug)]
+pub(crate) struct Staging {
+    pub(crate) buffer: wgpu::Buffer,
+    label: &'static str,
+    mapped: bool,
+}
+
+impl Staging {
+    pub(crate) fn new(
+        wgpu_device: &wgpu::Device,
+        label: &'static str,
+        size: u64,
+        usage: wgpu::BufferUsages,
+    ) -> Self {
+        Self {
+            buffer: wgpu_device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage,
+                mapped_at_creation: false,
+            }),
+            label,
+            mapped: false,
+        }
+    }
+
+    /// Replaces the buffer with one of `size` bytes if it is smaller.
+    pub(crate) fn reserve(&mut self, wgpu_device: &wgpu::Device, size: u64) {
+        if self.buffer.size() < size {
+            self.reset();
+            *self = Self::new(wgpu_device, self.label, size, self.buffer.usage());
+        }
+    }
+
+    /// Maps the whole buffer, blocking; [`Self::reset`] unmaps it again.
+    pub(crate) fn map_blocking(
+        &mut self,
+        wgpu_device: &wgpu::Device,
+        mode: wgpu::MapMode,
+    ) -> Result<(), Error> {
+        self.mapped = true;
+        let result = map_blocking(wgpu_device, self.buffer.slice(..), mode);
+        self.mapped = result.is_ok();
+        result
+    }
+
+    /// Maps the whole buffer; [`Self::reset`] unmaps it again.
+    async fn map_async(
+        &mut self,
+        wgpu_device: &wgpu::Device,
+        mode: wgpu::MapMode,
+        poller: Poller,
+    ) -> Result<(), Error> {
+        self.mapped = true;
+        let result = map_buffer(wgpu_device, self.buffer.slice(..), mode, poller).await;
+        self.mapped = result.is_ok();
+        result
+    }
+
+    /// Unmaps the buffer if it is mapped or a mapping is pending (e.g. left by a dropped
+    /// denoise future).
+    pub(crate) fn reset(&mut self) {
+        if std::mem::take(&mut self.mapped) {
+            self.buffer.unmap();
+        }
+    }
+}
+
+/// Creates the readback buffer (color and aux images back to back) and the upload buffer.
+fn create_staging(
+    wgpu_device: &wgpu::Device,
+    layout: StagingLayout,
+    aux: AuxImages,
+) -> (Staging, Staging) {
+    let readback = Staging::new(
+        wgpu_device,
+        "oidn_wgpu readback",
+        (1 + aux.count()) * layout.size(),
+        wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+    );
+    let upload = Staging::new(
+        wgpu_device,
+        "oidn_wgpu upload",
+        layout.size(),
+        wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC,
+    );
+    (readback, upload)
+}
@@ -0,0 +1,270 @@
+//! Texture formats accepted by the wgpu denoising paths and their texel conversion.
+//!
+//! See [`DenoiseTextureFormat`].
+
+use half::f16;
+
+/// Supported texture format for denoising input/output.
+///
+/// Texels are converted to RGB `f32` for OIDN and back; alpha is preserved (at the format's
+/// precision) and formats without alpha read as opaque. 8/10/16-bit unorm formats are LDR, float
+/// formats are HDR, and `*Srgb` formats are passed to OIDN still sRGB-encoded (`srgb = true`);
+/// see [`Self::is_hdr`] and [`Self::is_srgb`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DenoiseTextureFormat {
+    /// RGBA 32-bit float (4 components). Alpha is preserved.
+    Rgba32Float,
+    /// RGBA 16-bit float (4 components). Alpha is preserved.
+    Rgba16Float,
+    /// RGBA 8-bit unorm, linear LDR.
+    Rgba8Unorm,
+    /// RGBA 8-bit unorm, sRGB-encoded LDR.
+    Rgba8UnormSrgb,
+    /// BGRA 8-bit unorm, linear LDR.
+    Bgra8Unorm,
+    /// BGRA 8-bit unorm, sRGB-encoded LDR.
+    Bgra8UnormSrgb,
+    /// RGB 10-bit + 2-bit alpha unorm, linear LDR.
+    Rgb10a2Unorm,
+    /// Packed unsigned 11/11/10-bit floats, HDR. No alpha.
+    Rg11b10Ufloat,
+    /// Packed 9-bit mantissas with a shared 5-bit exponent, HDR. No alpha.
+    Rgb9e5Ufloat,
+    /// RGBA 16-bit unorm, linear LDR (needs `Features::TEXTURE_FORMAT_16BIT_NORM`).
+    Rgba16Unorm,
+}
+
+impl DenoiseTextureFormat {
+    /// Converts from a wgpu texture format if it is supported for denoising.
+    pub fn from_wgpu(format: wgpu::TextureFormat) -> Option<Self> {
+        format.try_into().ok()
+    }
+
+    /// The corresponding wgpu texture format.
+    pub fn to_wgpu(self) -> wgpu::TextureFormat {
+        match self {
+            Self::Rgba32Float => wgpu::TextureFormat::Rgba32Float,
+            Self::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+            Self::Rgba8Unorm => wgpu::TextureFormat::Rgba8Unorm,
+            Self::Rgba8UnormSrgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            Self::Bgra8Unorm => wgpu::TextureFormat::Bgra8Unorm,
+            Self::Bgra8UnormSrgb => wgpu::TextureFormat::Bgra8UnormSrgb,
+            Self::Rgb10a2Unorm => wgpu::TextureFormat::Rgb10a2Unorm,
+            Self::Rg11b10Ufloat => wgpu::TextureFormat::Rg11b10Ufloat,
+            Self::Rgb9e5Ufloat => wgpu::TextureFormat::Rgb9e5Ufloat,
+            Self::Rgba16Unorm => wgpu::TextureFormat::Rgba16Unorm,
+        }
+    }
+
+    /// Whether texels are HDR (float formats). Used for the filter's `hdr` flag unless
+    /// [`DenoiseOptions::hdr`](crate::DenoiseOptions::hdr) overrides it.
+    pub fn is_hdr(self) -> bool {
+        matches!(
+            self,
+            Self::Rgba32Float | Self::Rgba16Float | Self::Rg11b10Ufloat | Self::Rgb9e5Ufloat
+        )
+    }
+
+    /// Whether texels are sRGB-encoded. Used for the filter's `srgb` flag unless
+    /// [`DenoiseOptions::srgb`](crate::DenoiseOptions::srgb) overrides it.
+    pub fn is_srgb(self) -> bool {
+        matches!(self, Self::Rgba8UnormSrgb | Self::Bgra8UnormSrgb)
+    }
+
+    /// Whether texels can hold negative values, as normals in [-1, 1] need (`Rgba32Float` and
+    /// `Rgba16Float`). Normal images in other formats are rejected with
+    /// [`Error::UnsupportedFormat`](crate::Error::UnsupportedFormat).
+    pub fn is_signed(self) -> bool {
+        matches!(self, Self::Rgba32Float | Self::Rgba16Float)
+    }
+
+    pub(crate) fn bytes_per_pixel(self) -> u32 {
+        match self {
+            Self::Rgba32Float => 16,
+            Self::Rgba16Float | Self::Rgba16Unorm => 8,
+            Self::Rgba8Unorm
+            | Self::Rgba8UnormSrgb
+            | Self::Bgra8Unorm
+            | Self::Bgra8UnormSrgb
+            | Self::Rgb10a2Unorm
+            | Self::Rg11b10Ufloat
+            | Self::Rgb9e5Ufloat => 4,
+        }
+    }
+
+    /// Decodes packed texels into RGB (`3` floats per texel) and alpha.
+    pub(crate) fn decode(self, src: &[u8], rgb: &mut [f32], alpha: &mut [f32]) {
+        let bpp = self.bytes_per_pixel() as usize;
+        for ((texel, rgb), alpha) in src
+            .chunks_exact(bpp)
+            .zip(rgb.chunks_exact_mut(3))
+            .zip(alpha.iter_mut())
+        {
+            let [r, g, b, a] = self.decode_texel(texel);
+            rgb.copy_from_slice(&[r, g, b]);
+            *alpha = a;
+        }
+    }
+
+    /// Encodes RGB and alpha into packed texels.
+    pub(crate) fn encode(self, rgb: &[f32], alpha: &[f32], dst: &mut [u8]) {
+        let bpp = self.bytes_per_pixel() as usize;
+        for ((texel, rgb), &alpha) in dst
+            .chunks_exact_mut(bpp)
+            .zip(rgb.chunks_exact(3))
+            .zip(alpha.iter())
+        {
+            self.encode_texel([rgb[0], rgb[1], rgb[2], alpha], texel);
+        }
+    }
+
+    fn decode_texel(self, t: &[u8]) -> [f32; 4] {
+        let u32_at = |i: usize| u32::from_le_bytes([t[i], t[i + 1], t[i + 2], t[i + 3]]);
+        let u16_at = |i: usize| u16::from_le_bytes([t[i], t[i + 1]]);
+        let unorm8 = |i: usize| f32::from(t[i]) / 255.0;
+        let unorm16 = |i: usize| f32::from(u16_at(i)) / 65535.0;
+        match self {
+            Self::Rgba32Float => [0, 4, 8, 12].map(|i| f32::from_bits(u32_at(i))),
+            Self::Rgba16Float => [0, 2, 4, 6].map(|i| f16::from_bits(u16_at(i)).to_f32()),
+            Self::Rgba8Unorm | Self::Rgba8UnormSrgb => [0, 1, 2, 3].map(unorm8),
+            Self::Bgra8Unorm | Self::Bgra8UnormSrgb => [2, 1, 0, 3].map(unorm8),
+            Self::Rgba16Unorm => [0, 2, 4, 6].map(unorm16),
+            Self::Rgb10a2Unorm => {
+                let v = u32_at(0);
+                let c = |shift: u32| ((v >> shift) & 0x3FF) as f32 / 1023.0;
+                [c(0), c(10), c(20), (v >> 30) as f32 / 3.0]
+            }
+            Self::Rg11b10Ufloat => {
+                let v = u32_at(0);
+                [
+                    small_float_to_f32(v & 0x7FF, 6),
+                    small_float_to_f32((v >> 11) & 0x7FF, 6),
+                    small_float_to_f32(v >> 22, 5),
+                    1.0,
+                ]
+            }
+            Self::Rgb9e5Ufloat => {
+                let [r, g, b] = rgb9e5_to_f32(u32_at(0));
+                [r, g, b, 1.0]
+            }
+        }
+    }
+
+    fn encode_texel(self, [r, g, b, a]: [f32; 4], t: &mut [u8]) {
+        let unorm8 = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let unorm16 = |v: f32| (v.clamp(0.0, 1.0) * 65535.0).round() as u16;
+        match self {
+            Self::Rgba32Float => {
+                for (dst, v) in t.chunks_exact_mut(4).zip([r, g, b, a]) {
+                    dst.copy_from_slice(&v.to_le_bytes());
+                }
+            }
+            Self::Rgba16Float => {
+                for (dst, v) in t.chunks_exact_mut(2).zip([r, g, b, a]) {
+                    dst.copy_from_slice(&f16::from_f32(v).to_bits().to_le_bytes());
+                }
+            }
+            Self::Rgba8Unorm | Self::Rgba8UnormSrgb => {
+                t.copy_from_slice(&[r, g, b, a].map(unorm8));
+            }
+            Self::Bgra8Unorm | Self::Bgra8UnormSrgb => {
+                t.copy_from_slice(&[b, g, r, a].map(unorm8));
+            }
+            Self::Rgba16Unorm => {
+                for (dst, v) in t.chunks_exact_mut(2).zip([r, g, b, a]) {
+                    dst.copy_from_slice(&unorm16(v).to_le_bytes());
+                }
+            }
+            Self::Rgb10a2Unorm => {
+                let c = |v: f32| (v.clamp(0.0, 1.0) * 1023.0).round() as u32;
+                let a = (a.clamp(0.0, 1.0) * 3.0).round() as u32;
+                let v = c(r) | c(g) << 10 | c(b) << 20 | a << 30;
+                t.copy_from_slice(&v.to_le_bytes());
+            }
+            Self::Rg11b10Ufloat => {
+                let v = f32_to_small_float(r, 6)
+                    | f32_to_small_float(g, 6) << 11
+                    | f32_to_small_float(b, 5) << 22;
+                t.copy_from_slice(&v.to_le_bytes());
+            }
+            Self::Rgb9e5Ufloat => t.copy_from_slice(&f32_to_rgb9e5([r, g, b]).to_le_bytes()),
+        }
+    }
+}
+
+impl TryFrom<wgpu::TextureFormat> for DenoiseTextureFormat {
+    type Error = ();
+
+    fn try_from(format: wgpu::TextureFormat) -> Result<Self, Self::Error> {
+        match format {
+            wgpu::TextureFormat::Rgba32Float => Ok(Self::Rgba32Float),
+            wgpu::TextureFormat::Rgba16Float => Ok(Self::Rgba16Float),
+            wgpu::TextureFormat::Rgba8Unorm => Ok(Self::Rgba8Unorm),
+            wgpu::TextureFormat::Rgba8UnormSrgb => Ok(Self::Rgba8UnormSrgb),
+            wgpu::TextureFormat::Bgra8Unorm => Ok(Self::Bgra8Unorm),
+            wgpu::TextureFormat::Bgra8UnormSrgb => Ok(Self::Bgra8UnormSrgb),
+            wgpu::TextureFormat::Rgb10a2Unorm => Ok(Self::Rgb10a2Unorm),
+            wgpu::TextureFormat::Rg11b10Ufloat => Ok(Self::Rg11b10Ufloat),
+            wgpu::TextureFormat::Rgb9e5Ufloat => Ok(Self::Rgb9e5Ufloat),
+            wgpu::TextureFormat::Rgba16Unorm => Ok(Self::Rgba16Unorm),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Unsigned float with a 5-bit exponent (bias 15) and `mantissa_bits` mantissa, as in
+/// `Rg11b10Ufloat`; it is the upper bits of a positive `f16`.
+fn small_float_to_f32(bits: u32, mantissa_bits: u32) -> f32 {
+    let shift = 10 - mantissa_bits;
+    f16::from_bits((bits << shift) as u16).to_f32()
+}
+
+/// Rounds to the nearest small float; negatives and NaN become 0, overflow the largest finite
+/// value.
+fn f32_to_small_float(v: f32, mantissa_bits: u32) -> u32 {
+    if v.is_nan() || v <= 0.0 {
+        return 0;
+    }
+    let shift = 10 - mantissa_bits;
+    let max_finite = (30 << mantissa_bits) | ((1 << mantissa_bits) - 1);
+    let bits = u32::from(f16::from_f32(v).to_bits());
+    ((bits + (1 << (shift - 1))) >> shift).min(max_finite)
+}
+
+const RGB9E5_MANTISSA_BITS: i32 = 9;
+const RGB9E5_EXP_BIAS: i32 = 15;
+const RGB9E5_MAX_EXP: i32 = 31;
+
+fn rgb9e5_to_f32(v: u32) -> [f32; 3] {
+    let exp = (v >> 27) as i32;
+    let scale = 2f32.powi(exp - RGB9E5_EXP_BIAS - RGB9E5_MANTISSA_BITS);
+    [0, 9, 18].map(|shift| ((v >> shift) & 0x1FF) as f32 * scale)
+}
+
+/// Shared-exponent encoding as specified for `RGB9_E5` (GL_EXT_texture_shared_exponent).
+fn f32_to_rgb9e5(rgb: [f32; 3]) -> u32 {
+    let mantissa_max = (1 << RGB9E5_MANTISSA_BITS) as f32;
+    let max_value =
+        (mantissa_max - 1.0) / mantissa_max * 2f32.powi(RGB9E5_MAX_EXP - RGB9E5_EXP_BIAS);
+    let rgb = rgb.map(|c| {
+        if c.is_nan() {
+            0.0
+        } else {
+            c.clamp(0.0, max_value)
+        }
+    });
+    let max_c = rgb[0].max(rgb[1]).max(rgb[2]);
+    let floor_log2 = if max_c > 0.0 {
+        max_c.log2().floor() as i32
+    } else {
+        -RGB9E5_EXP_BIAS - 1
+    };
+    let mut exp = floor_log2.max(-RGB9E5_EXP_BIAS - 1) + 1 + RGB9E5_EXP_BIAS;
+    let mut scale = 2f32.powi(exp - RGB9E5_EXP_BIAS - RGB9E5_MANTISSA_BITS);
+    if (max_c / scale + 0.5).floor() >= mantissa_max {
+        exp += 1;
+        scale *= 2.0;
+    }
+    let [r, g, b] = rgb.map(|c| (c / scale + 0.5).floor() as u32);
+    r | g << 9 | b << 18 | (exp as u32) << 27
+}
@@ -0,0 +1,306 @@
+//! Out-of-core denoising of images too large for the device: overlapping tiles, streamed by rows.
+//!
+//! See [`TiledDenoiser`], [`RowReader`] and [`RowWriter`].
+
+use crate::device::OidnDevice;
+use crate::filter::RtFilter;
+use crate::Error;
+
+/// Default tile size (core region, before overlap) in pixels.
+const DEFAULT_TILE_SIZE: u32 = 1024;
+
+/// Source of RGB `f32` rows for [`TiledDenoiser`].
+///
+/// Rows are requested top to bottom, each exactly once, so a reader can stream from a file or
+/// decoder. `&[f32]` implements it by consuming rows from the front of the slice.
+pub trait RowReader {
+    /// Fills `rows` with consecutive rows starting at row `y`; `rows.len()` is a multiple of
+    /// `width * 3`.
+    fn read_rows(&mut self, y: u32, rows: &mut [f32]) -> Result<(), Error>;
+}
+
+/// Sink for denoised RGB `f32` rows from [`TiledDenoiser`].
+///
+/// Rows are written top to bottom, each exactly once. `Vec<f32>` implements it by appending.
+pub trait RowWriter {
+    /// Stores consecutive rows starting at row `y`; `rows.len()` is a multiple of `width * 3`.
+    fn write_rows(&mut self, y: u32, rows: &[f32]) -> Result<(), Error>;
+}
+
+impl RowReader for &[f32] {
+    fn read_rows(&mut self, _y: u32, rows: &mut [f32]) -> Result<(), Error> {
+        if self.len() < rows.len() {
+            return Err(Error::InvalidDimensions);
+        }
+        let (head, tail) = self.split_at(rows.len());
+        rows.copy_from_slice(head);
+        *self = tail;
+        Ok(())
+    }
+}
+
+impl RowWriter for Vec<f32> {
+    fn write_rows(&mut self, _y: u32, rows: &[f32]) -> Result<(), Error> {
+        self.extend_from_slice(rows);
+        Ok(())
+    }
+}
+
+/// Denoises images of any size with one reused [`RtFilter`], one tile at a time.
+///
+/// The frame is split into square tiles ([`Self::set_tile_size`], rounded up to the filter's
+/// `tileAlignment`). Each tile is denoised with `tileOverlap` pixels of context on every side,
+/// and neighbouring tiles are cross-faded over half the overlap so seams do not show. Inputs are
+/// read and outputs written one band of tiles at a time, so only about
+/// `width * (tile_size + 2 * overlap)` pixels per image are held in memory.
+#[derive(Debug)]
+pub struct TiledDenoiser {
+    filter: RtFilter,
+    tile_size: u32,
+}
+
+impl TiledDenoiser {
+    /// Creates a tiled denoiser with a new RT filter on `device`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of [`RtFilter::new`].
+    pub fn new(device: &OidnDevice) -> Result<Self, Error> {
+        Ok(Self::from_filter(RtFilter::new(device)?))
+    }
+
+    /// Uses an existing (configured) RT filter. Its dimensions are overwritten per tile.
+    pub fn from_filter(filter: RtFilter) -> Self {
+        Self {
+            filter,
+            tile_size: DEFAULT_TILE_SIZE,
+        }
+    }
+
+    /// The filter, for configuring hdr, quality, etc.
+    pub fn filter_mut(&mut self) -> &mut RtFilter {
+        &mut self.filter
+    }
+
+    /// The filter (e.g. for [`RtFilter::params`]).
+    pub fn filter(&self) -> &RtFilter {
+        &self.filter
+    }
+
+    /// Returns the filter.
+    pub fn into_filter(self) -> RtFilter {
+        self.filter
+    }
+
+    /// Tile size in pixels, excluding overlap. Default: 1024. Smaller tiles use less device
+    /// memory but denoise more overlap. Sizes are rounded up to the filter's `tileAlignment`;
+    /// sizes larger than the image mean one tile.
+    pub fn set_tile_size(&mut self, tile_size: u32) -> &mut Self {
+        self.tile_size = tile_size.max(1);
+        self
+    }
+
+    /// Denoises a `width` x `height` RGB image read from `color` (and optional albedo/normal
+    /// readers, read in lockstep) into `output`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDimensions`] if a dimension is zero (or so large that the aligned
+    /// tile size overflows `u32`), errors from the readers and writer, or the filter's error for
+    /// a tile.
+    pub fn denoise(
+        &mut self,
+        width: u32,
+        height: u32,
+        color: &mut dyn RowReader,
+        mut albedo: Option<&mut dyn RowReader>,
+        mut normal: Option<&mut dyn RowReader>,
+        output: &mut dyn RowWriter,
+    ) -> Result<(), Error> {
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidDimensions);
+        }
+        let (alignment, overlap) = self
+            .filter
+            .probe_tiling(albedo.is_some(), normal.is_some())?;
+        // Clamped to the image before rounding up to the alignment, so huge tile sizes cannot
+        // overflow.
+        let (tile, alignment64) = (u64::from(self.tile_size.min(width.max(height))), u64::from(alignment));
+        let tile = u32::try_from((tile + alignment64 - 1) / alignment64 * alignment64)
+            .map_err(|_| Error::InvalidDimensions)?;
+        // Cross-fade width on each side of a tile boundary; tiles must not fade on both sides
+        // at once, and the fade must stay inside the tile's context.
+        let blend = (overlap / 2).min(tile / 2);
+        let row_len = width as usize * 3;
+
+        let mut color_band = Band::default();
+        let mut albedo_band = Band::default();
+        let mut normal_band = Band::default();
+        let mut acc = Band::default();
+        let mut tile_in = TileBuffers::default();
+
+        let mut r0 = 0;
+        while r0 < height {
+            let r1 = r0.saturating_add(tile).min(height);
+            let (in_y0, in_y1) = (r0.saturating_sub(overlap), r1.saturating_add(overlap).min(height));
+            color_band.advance(color, row_len, in_y0, in_y1)?;
+            if let Some(reader) = albedo.as_deref_mut() {
+                albedo_band.advance(reader, row_len, in_y0, in_y1)?;
+            }
+            if let Some(reader) = normal.as_deref_mut() {
+                normal_band.advance(reader, row_len, in_y0, in_y1)?;
+            }
+            let (out_y0, out_y1) = (r0.saturating_sub(blend), r1.saturating_add(blend).min(height));
+            acc.extend_zeroed(row_len, out_y0, out_y1);
+
+            let th = in_y1 - in_y0;
+            let mut c0 = 0;
+            while c0 < width {
+                let c1 = c0.saturating_add(tile).min(width);
+                let (in_x0, in_x1) = (c0.saturating_sub(overlap), c1.saturating_add(overlap).min(width));
+                let tw = in_x1 - in_x0;
+                let crop = |band: &Band, dst: &mut Vec<f32>| {
+                    dst.clear();
+                    for y in in_y0..in_y1 {
+                        let row = band.row(row_len, y);
+                        dst.extend_from_slice(&row[in_x0 as usize * 3..in_x1 as usize * 3]);
+                    }
+                };
+                crop(&color_band, &mut tile_in.color);
+                if albedo.is_some() {
+                    crop(&albedo_band, &mut tile_in.albedo);
+                }
+                if normal.is_some() {
+                    crop(&normal_band, &mut tile_in.normal);
+                }
+                tile_in.output.resize(tile_in.color.len(), 0.0);
+                self.filter.set_dimensions(tw, th);
+                self.filter.execute_with_aux(
+                    Some(&tile_in.color),
+                    &mut tile_in.output,
+                    albedo.is_some().then_some(&tile_in.albedo[..]),
+                    normal.is_some().then_some(&tile_in.normal[..]),
+                )?;
+
+                let (out_x0, out_x1) = (c0.saturating_sub(blend), (c1 + blend).min(width));
+                for y in out_y0..out_y1 {
+                    let wy = fade(y, r0, r1, height, blend);
+                    let src = &tile_in.output[(y - in_y0) as usize * tw as usize * 3..];
+                    let dst = acc.row_mut(row_len, y);
+                    for x in out_x0..out_x1 {
+                        let w = wy * fade(x, c0, c1, width, blend);
+                        let s = (x - in_x0) as usize * 3;
+                        let d = x as usize * 3;
+                        for c in 0..3 {
+                            dst[d + c] += w * src[s + c];
+                        }
+                    }
+                }
+                c0 = c1;
+            }
+
+            // Rows above the next band's fade are final.
+            let done = if r1 < height { r1 - blend } else { height };
+            acc.flush(output, row_len, done)?;
+            r0 = r1;
+        }
+        Ok(())
+    }
+}
+
+/// Weight of a tile covering `[c0, c1)` (of `len`) at `p`: 1 inside, fading linearly to 0 over
+/// `blend` pixels on each side of an inner tile boundary. Neighbouring weights sum to 1.
+fn fade(p: u32, c0: u32, c1: u32, len: u32, blend: u32) -> f32 {
+    if blend == 0 {
+        return 1.0;
+    }
+    let p = p as f32 + 0.5;
+    let width = 2.0 * blend as f32;
+    let mut w = 1.0f32;
+    if c0 > 0 {
+        w = w.min((p - (c0 - blend) as f32) / width);
+    }
+    if c1 < len {
+        w = w.min(((c1 + blend) as f32 - p) / width);
+    }
+    w.clamp(0.0, 1.0)
+}
+
+/// Full-width rows `[start, start + data.len() / row_len)` of one image.
+#[derive(Default)]
+struct Band {
+    start: u32,
+    data: Vec<f32>,
+}
+
+impl Band {
+    fn end(&self, row_len: usize) -> u32 {
+        self.start + (self.data.len() / row_len) as u32
+    }
+
+    fn row(&self, row_len: usize, y: u32) -> &[f32] {
+        let i = (y - self.start) as usize * row_len;
+        &self.data[i..i + row_len]
+    }
+
+    fn row_mut(&mut self, row_len: usize, y: u32) -> &mut [f32] {
+        let i = (y - self.start) as usize * row_len;
+        &mut self.data[i..i + row_len]
+    }
+
+    /// Drops rows above `y0`, so the band starts at `y0` (which must not be past its end).
+    fn drop_until(&mut self, row_len: usize, y0: u32) {
+        let skip = ((y0 - self.start) as usize * row_len).min(self.data.len());
+        self.data.drain(..skip);
+        self.start = y0;
+    }
+
+    /// Makes the band hold rows `[y0, y1)`, reading rows it does not have yet.
+    fn advance(
+        &mut self,
+        reader: &mut dyn RowReader,
+        row_len: usize,
+        y0: u32,
+        y1: u32,
+    ) -> Result<(), Error> {
+        self.drop_until(row_len, y0);
+        let end = self.end(row_len);
+        if y1 > end {
+            let old = self.data.len();
+            self.data.resize(old + (y1 - end) as usize * row_len, 0.0);
+            reader.read_rows(end, &mut self.data[old..])?;
+        }
+        Ok(())
+    }
+
+    /// Extends the band with zeroed rows up to `y1`; `y0` is where the band must start.
+    fn extend_zeroed(&mut self, row_len: usize, y0: u32, y1: u32) {
+        if self.data.is_empty() {
+            self.start = y0;
+        }
+        let end = self.end(row_len);
+        if y1 > end {
+            self.data
+                .resize(self.data.len() + (y1 - end) as usize * row_len, 0.0);
+        }
+    }
+
+    /// Writes rows `[start, y)` and drops them.
+    fn flush(&mut self, writer: &mut dyn RowWriter, row_len: usize, y: u32) -> Result<(), Error> {
+        if y > self.start {
+            let n = (y - self.start) as usize * row_len;
+            writer.write_rows(self.start, &self.data[..n])?;
+            self.drop_until(row_len, y);
+        }
+        Ok(())
+    }
+}
+
+/// Per-tile buffers reused across tiles.
+#[derive(Default)]
+struct TileBuffers {
+    color: Vec<f32>,
+    albedo: Vec<f32>,
+    normal: Vec<f32>,
+    output: Vec<f32>,
+}
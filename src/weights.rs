@@ -0,0 +1,147 @@
+//! Custom trained filter weights (OIDN `.tza` files) for the `weights` filter parameter.
+//!
+//! See [`Weights`], [`RtFilter::set_weights`](crate::RtFilter::set_weights) and
+//! [`RtLightmapFilter::set_weights`](crate::RtLightmapFilter::set_weights).
+
+use crate::sys;
+use crate::Error;
+use std::ffi::CString;
+use std::path::Path;
+use std::sync::Arc;
+
+/// TZA magic number (little-endian `u16` at offset 0).
+const TZA_MAGIC: u16 = 0x41D7;
+/// TZA major version understood by OIDN 2.x.
+const TZA_MAJOR_VERSION: u8 = 2;
+/// Magic (2) + major (1) + minor (1) + tensor table offset (8).
+const TZA_HEADER_SIZE: usize = 12;
+
+/// Validated trained weights in OIDN's TZA format.
+///
+/// Cloning is cheap (the bytes are shared), so one set of weights can be bound to several
+/// filters. A filter keeps its weights alive for as long as they are bound.
+#[derive(Clone)]
+pub struct Weights {
+    data: Arc<[u8]>,
+    version: (u8, u8),
+}
+
+impl std::fmt::Debug for Weights {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Weights")
+            .field("len", &self.data.len())
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+impl Weights {
+    /// Reads and validates a `.tza` file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the file cannot be read, or [`Error::InvalidWeights`] if it is not
+    /// a TZA file OIDN can load.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_vec(std::fs::read(path).map_err(Error::Io)?)
+    }
+
+    /// Validates weights in memory (copies `bytes`).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidWeights`] if `bytes` is not a TZA file OIDN can load.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let version = parse_header(bytes)?;
+        Ok(Self {
+            data: bytes.into(),
+            version,
+        })
+    }
+
+    /// Validates weights in memory, taking ownership of `bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidWeights`] if `bytes` is not a TZA file OIDN can load.
+    pub fn from_vec(bytes: Vec<u8>) -> Result<Self, Error> {
+        let version = parse_header(&bytes)?;
+        Ok(Self {
+            data: bytes.into(),
+            version,
+        })
+    }
+
+    /// TZA format version as `(major, minor)`.
+    pub fn version(&self) -> (u8, u8) {
+        self.version
+    }
+
+    /// The raw TZA bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Custom weights bound to a filter's `weights` data parameter, kept alive while bound.
+#[derive(Debug, Default)]
+pub(crate) struct WeightsBinding(Option<Weights>);
+
+impl WeightsBinding {
+    /// Binds `weights` to `filter` as shared data, replacing any bound before.
+    pub(crate) fn set(&mut self, filter: sys::OIDNFilter, weights: Weights) {
+        let c_weights = CString::new("weights").unwrap();
+        let data = weights.as_bytes();
+        unsafe {
+            sys::oidnSetSharedFilterData(
+                filter,
+                c_weights.as_ptr(),
+                data.as_ptr() as *mut _,
+                data.len(),
+            );
+        }
+        self.0 = Some(weights);
+    }
+
+    /// Unbinds custom weights from `filter`. Returns whether any were bound.
+    pub(crate) fn clear(&mut self, filter: sys::OIDNFilter) -> bool {
+        if self.0.take().is_none() {
+            return false;
+        }
+        let c_weights = CString::new("weights").unwrap();
+        unsafe { sys::oidnUnsetFilterData(filter, c_weights.as_ptr()) };
+        true
+    }
+
+    /// The bound weights, if any.
+    pub(crate) fn get(&self) -> Option<&Weights> {
+        self.0.as_ref()
+    }
+}
+
+/// Checks the TZA header and returns `(major, minor)`.
+fn parse_header(bytes: &[u8]) -> Result<(u8, u8), Error> {
+    let invalid = |reason: &str| Error::InvalidWeights(reason.to_owned());
+    if bytes.len() < TZA_HEADER_SIZE {
+        return Err(invalid("file too short for a TZA header"));
+    }
+    if u16::from_le_bytes([bytes[0], bytes[1]]) != TZA_MAGIC {
+        return Err(invalid("not a TZA file (bad magic)"));
+    }
+    let (major, minor) = (bytes[2], bytes[3]);
+    if major != TZA_MAJOR_VERSION {
+        return Err(Error::InvalidWeights(format!(
+            "unsupported TZA version {}.{} (expected {}.x)",
+            major, minor, TZA_MAJOR_VERSION
+        )));
+    }
+    let mut offset = [0u8; 8];
+    offset.copy_from_slice(&bytes[4..12]);
+    let table_offset = u64::from_le_bytes(offset);
+    // The table starts with a u32 tensor count.
+    let table_end = table_offset.saturating_add(4);
+    if table_offset < TZA_HEADER_SIZE as u64 || table_end > bytes.len() as u64 {
+        return Err(invalid("tensor table offset out of range"));
+    }
+    Ok((major, minor))
+}
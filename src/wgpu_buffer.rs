@@ -0,0 +1,413 @@
+//! Denoise wgpu storage buffers (e.g. written by a compute path tracer) without going through
+//! textures.
+//!
+//! See [`denoise_buffer`], [`denoise_buffer_with_aux`], [`BufferDenoiser`] and [`BufferLayout`].
+
+use crate::device::OidnDevice;
+use crate::filter::RtFilter;
+use crate::memory::AuxImages;
+use crate::texture_denoiser::{check_aux, Staging};
+use crate::wgpu_integration::{aux_images, DenoiseOptions, DenoiseTextureFormat, StagingLayout};
+use crate::Error;
+
+/// Where an image lives in a wgpu buffer.
+///
+/// Pixels use the texel encodings of [`DenoiseTextureFormat`]: a WGSL `array<vec4<f32>>` is
+/// [`DenoiseTextureFormat::Rgba32Float`], `array<vec4<f16>>` is
+/// [`DenoiseTextureFormat::Rgba16Float`]. `offset` and `row_stride` must be multiples of
+/// [`wgpu::COPY_BUFFER_ALIGNMENT`] (4 bytes).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferLayout {
+    pub width: u32,
+    pub height: u32,
+    /// Pixel format.
+    pub format: DenoiseTextureFormat,
+    /// Bytes between the starts of consecutive rows. `None` = rows are tightly packed.
+    pub row_stride: Option<u32>,
+    /// Byte offset of the first pixel in the buffer.
+    pub offset: u64,
+}
+
+impl BufferLayout {
+    /// Tightly packed `width` x `height` pixels of `format` at the start of the buffer.
+    pub fn new(width: u32, height: u32, format: DenoiseTextureFormat) -> Self {
+        Self {
+            width,
+            height,
+            format,
+            row_stride: None,
+            offset: 0,
+        }
+    }
+
+    /// Sets the bytes between the starts of consecutive rows.
+    pub fn with_row_stride(mut self, row_stride: u32) -> Self {
+        self.row_stride = Some(row_stride);
+        self
+    }
+
+    /// Sets the byte offset of the first pixel.
+    pub fn with_offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Bytes from `offset` to the end of the last pixel.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDimensions`] if a dimension is zero, the stride is shorter than a
+    /// row, or the offset or stride is not a multiple of 4 bytes.
+    pub fn span(&self) -> Result<u64, Error> {
+        Ok(self.staging()?.span())
+    }
+
+    /// Checks the layout and returns it as a staging layout with the same stride.
+    fn staging(&self) -> Result<StagingLayout, Error> {
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::InvalidDimensions);
+        }
+        let staging = StagingLayout::new(self.width, self.height, self.format);
+        let row_stride = self.row_stride.unwrap_or(staging.bytes_per_row());
+        let align = wgpu::COPY_BUFFER_ALIGNMENT;
+        if row_stride < staging.bytes_per_row()
+            || u64::from(row_stride) % align != 0
+            || self.offset % align != 0
+        {
+            return Err(Error::InvalidDimensions);
+        }
+        Ok(staging.with_row_stride(row_stride))
+    }
+}
+
+/// A buffer and the layout of the image in it.
+pub type BufferTarget<'a> = (&'a wgpu::Buffer, BufferLayout);
+
+/// Denoises the image in a wgpu buffer into another (or the same) buffer by readback → OIDN
+/// (CPU) → upload, like [`denoise_texture`](crate::denoise_texture).
+///
+/// `input` and `output` may use different layouts (e.g. offsets, strides or formats) of the same
+/// size. Only RGB is denoised; alpha is preserved. Bytes of `output` outside its rows' pixels
+/// (row padding) are left unchanged. The filter's `hdr`/`srgb` flags follow the input format
+/// unless set in `options`.
+///
+/// **Buffer usage:** `input` must have [`BufferUsages::COPY_SRC`](wgpu::BufferUsages::COPY_SRC);
+/// `output` must have [`BufferUsages::COPY_DST`](wgpu::BufferUsages::COPY_DST).
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidDimensions`] if a layout is invalid (see [`BufferLayout::span`]), does
+/// not fit its buffer, or the sizes differ, [`Error::BufferMapFailed`] if readback fails, or an
+/// OIDN error.
+pub fn denoise_buffer(
+    device: &OidnDevice,
+    wgpu_device: &wgpu::Device,
+    wgpu_queue: &wgpu::Queue,
+    input: BufferTarget<'_>,
+    output: BufferTarget<'_>,
+    options: &DenoiseOptions,
+) -> Result<(), Error> {
+    denoise_buffer_with_aux(
+        device,
+        wgpu_device,
+        wgpu_queue,
+        input,
+        output,
+        options,
+        None,
+        None,
+    )
+}
+
+/// Denoises the image in a wgpu buffer with optional albedo and normal images (same size; any
+/// layout, also in the same buffer as color). Normals are in [-1, 1], so the normal layout must
+/// use a signed format (`Rgba32Float` or `Rgba16Float`, see
+/// [`DenoiseTextureFormat::is_signed`]). Otherwise identical to [`denoise_buffer`].
+///
+/// # Errors
+///
+/// Same as [`denoise_buffer`]; also [`Error::UnsupportedFormat`] if the normal layout's format is
+/// unsigned.
+#[allow(clippy::too_many_arguments)]
+pub fn denoise_buffer_with_aux(
+    device: &OidnDevice,
+    wgpu_device: &wgpu::Device,
+    wgpu_queue: &wgpu::Queue,
+    input: BufferTarget<'_>,
+    output: BufferTarget<'_>,
+    options: &DenoiseOptions,
+    albedo: Option<BufferTarget<'_>>,
+    normal: Option<BufferTarget<'_>>,
+) -> Result<(), Error> {
+    BufferDenoiser::new(
+        device,
+        wgpu_device,
+        input.1,
+        aux_images(albedo, normal)?,
+        options,
+    )?
+    .denoise_with_aux(wgpu_queue, input, output, albedo, normal)
+}
+
+/// Denoises images of one size in wgpu buffers, reusing its resources.
+///
+/// The buffer counterpart of [`TextureDenoiser`](crate::TextureDenoiser): owns the RT filter,
+/// the readback and upload buffers and the host-side RGB/alpha scratch, so a compute path tracer
+/// can denoise every frame without churn. Each call may use other layouts of the same size; the
+/// staging buffers only grow when a call needs more room than they have.
+/// [`denoise_buffer`] and [`denoise_buffer_with_aux`] are one-shot uses of this type.
+#[derive(Debug)]
+pub struct BufferDenoiser {
+    wgpu_device: wgpu::Device,
+    filter: RtFilter,
+    width: u32,
+    height: u32,
+    aux: AuxImages,
+    options: DenoiseOptions,
+    /// Color format the filter's `hdr`/`srgb` flags were derived from.
+    options_format: DenoiseTextureFormat,
+    /// Color, then albedo and normal (if used), back to back.
+    readback: Staging,
+    upload: Staging,
+    color: Vec<f32>,
+    alpha: Vec<f32>,
+    albedo: Vec<f32>,
+    normal: Vec<f32>,
+}
+
+impl BufferDenoiser {
+    /// Creates a denoiser for images of `layout`'s size, with the aux images in `aux`. The
+    /// staging buffers are sized for color and aux images in `layout`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDimensions`] if `layout` is invalid (see [`BufferLayout::span`]),
+    /// or the error of [`RtFilter::new`].
+    pub fn new(
+        device: &OidnDevice,
+        wgpu_device: &wgpu::Device,
+        layout: BufferLayout,
+        aux: AuxImages,
+        options: &DenoiseOptions,
+    ) -> Result<Self, Error> {
+        let span = layout.span()?;
+        let mut filter = RtFilter::new(device)?;
+        filter.set_dimensions(layout.width, layout.height);
+        options.apply(&mut filter, layout.format);
+        let n = layout.width as usize * layout.height as usize;
+        let aux_len = |used: bool| if used { n * 3 } else { 0 };
+        Ok(Self {
+            wgpu_device: wgpu_device.clone(),
+            filter,
+            width: layout.width,
+            height: layout.height,
+            aux,
+            options: options.clone(),
+            options_format: layout.format,
+            readback: Staging::new(
+                wgpu_device,
+                "oidn_wgpu readback",
+                (1 + aux.count()) * span,
+                wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            ),
+            upload: Staging::new(
+                wgpu_device,
+                "oidn_wgpu upload",
+                span,
+                wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC,
+            ),
+            color: vec![0.0; n * 3],
+            alpha: vec![0.0; n],
+            albedo: vec![0.0; aux_len(aux != AuxImages::None)],
+            normal: vec![0.0; aux_len(aux == AuxImages::AlbedoNormal)],
+        })
+    }
+
+    /// Applies new options to the filter (format-derived `hdr`/`srgb` follow the color format of
+    /// each call).
+    pub fn set_options(&mut self, options: &DenoiseOptions) -> &mut Self {
+        options.apply(&mut self.filter, self.options_format);
+        self.options = options.clone();
+        self
+    }
+
+    /// Image width.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Image height.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Aux images passed with color.
+    pub fn aux(&self) -> AuxImages {
+        self.aux
+    }
+
+    /// The filter, e.g. for a progress monitor or [`RtFilter::set_max_memory_mb`]. Its dimensions
+    /// are managed by this denoiser.
+    pub fn filter_mut(&mut self) -> &mut RtFilter {
+        &mut self.filter
+    }
+
+    /// Denoises the image in `input` into `output` (may be the same buffer and layout) for a
+    /// denoiser created with [`AuxImages::None`], like [`denoise_buffer`].
+    ///
+    /// # Errors
+    ///
+    /// As [`Self::denoise_with_aux`].
+    pub fn denoise(
+        &mut self,
+        wgpu_queue: &wgpu::Queue,
+        input: BufferTarget<'_>,
+        output: BufferTarget<'_>,
+    ) -> Result<(), Error> {
+        self.denoise_with_aux(wgpu_queue, input, output, None, None)
+    }
+
+    /// Denoises the image in `input` into `output` with the aux images this denoiser was created
+    /// for, like [`denoise_buffer_with_aux`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDimensions`] if a layout is invalid (see [`BufferLayout::span`]),
+    /// does not fit its buffer, or differs from the denoiser's size, [`Error::MissingImage`] /
+    /// [`Error::UnsupportedImage`] if the aux images do not match [`Self::aux`],
+    /// [`Error::UnsupportedFormat`] if the normal layout's format is unsigned (see
+    /// [`DenoiseTextureFormat::is_signed`]), [`Error::BufferMapFailed`] if mapping a staging
+    /// buffer fails, or an OIDN error.
+    pub fn denoise_with_aux(
+        &mut self,
+        wgpu_queue: &wgpu::Queue,
+        input: BufferTarget<'_>,
+        output: BufferTarget<'_>,
+        albedo: Option<BufferTarget<'_>>,
+        normal: Option<BufferTarget<'_>>,
+    ) -> Result<(), Error> {
+        check_aux("albedo", self.aux != AuxImages::None, albedo.is_some())?;
+        check_aux(
+            "normal",
+            self.aux == AuxImages::AlbedoNormal,
+            normal.is_some(),
+        )?;
+        if normal.is_some_and(|(_, layout)| !layout.format.is_signed()) {
+            return Err(Error::UnsupportedFormat);
+        }
+        let size = (self.width, self.height);
+        let mut staged = [None; 3];
+        let mut staging_size = 0;
+        for (slot, target) in staged.iter_mut().zip([Some(input), albedo, normal]) {
+            if let Some((buffer, layout)) = target {
+                let staging = checked_staging(buffer, &layout, size)?;
+                *slot = Some((buffer, layout.offset, staging, staging_size));
+                staging_size += staging.span();
+            }
+        }
+        let out_layout = checked_staging(output.0, &output.1, size)?;
+        if input.1.format != self.options_format {
+            self.options.apply(&mut self.filter, input.1.format);
+            self.options_format = input.1.format;
+        }
+        self.readback.reset();
+        self.upload.reset();
+        self.readback.reserve(&self.wgpu_device, staging_size);
+        self.upload.reserve(&self.wgpu_device, out_layout.span());
+
+        let mut encoder = self
+            .wgpu_device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        for &(buffer, offset, staging, staging_offset) in staged.iter().flatten() {
+            encoder.copy_buffer_to_buffer(
+                buffer,
+                offset,
+                &self.readback.buffer,
+                staging_offset,
+                staging.span(),
+            );
+        }
+        wgpu_queue.submit(Some(encoder.finish()));
+
+        self.readback
+            .map_blocking(&self.wgpu_device, wgpu::MapMode::Read)?;
+        {
+            let mapped = self.readback.buffer.slice(..).get_mapped_range();
+            let [color, albedo, normal] = staged;
+            // Aux alpha is not needed: decode it into `alpha` before color overwrites it.
+            for (rgb, image) in [
+                (&mut self.albedo, albedo),
+                (&mut self.normal, normal),
+                (&mut self.color, color),
+            ] {
+                if let Some((_, _, staging, offset)) = image {
+                    let start = offset as usize;
+                    staging.decode(
+                        &mapped[start..start + staging.span() as usize],
+                        rgb,
+                        &mut self.alpha,
+                    );
+                }
+            }
+        }
+        self.readback.reset();
+        self.filter.execute_in_place_with_aux(
+            &mut self.color,
+            albedo.is_some().then_some(&self.albedo[..]),
+            normal.is_some().then_some(&self.normal[..]),
+        )?;
+
+        self.upload
+            .map_blocking(&self.wgpu_device, wgpu::MapMode::Write)?;
+        out_layout.encode(
+            &self.color,
+            &self.alpha,
+            &mut self.upload.buffer.slice(..).get_mapped_range_mut(),
+        );
+        self.upload.reset();
+        let (output, layout) = output;
+        let mut encoder = self
+            .wgpu_device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        if out_layout.row_stride == out_layout.bytes_per_row() {
+            encoder.copy_buffer_to_buffer(
+                &self.upload.buffer,
+                0,
+                output,
+                layout.offset,
+                out_layout.span(),
+            );
+        } else {
+            // Row by row, so the padding between rows is not overwritten.
+            for y in 0..u64::from(out_layout.height) {
+                let row = y * u64::from(out_layout.row_stride);
+                encoder.copy_buffer_to_buffer(
+                    &self.upload.buffer,
+                    row,
+                    output,
+                    layout.offset + row,
+                    u64::from(out_layout.bytes_per_row()),
+                );
+            }
+        }
+        wgpu_queue.submit(Some(encoder.finish()));
+        Ok(())
+    }
+}
+
+/// Checks `layout` against its buffer and the denoiser's size.
+fn checked_staging(
+    buffer: &wgpu::Buffer,
+    layout: &BufferLayout,
+    size: (u32, u32),
+) -> Result<StagingLayout, Error> {
+    let staging = layout.staging()?;
+    let fits = layout
+        .offset
+        .checked_add(staging.span())
+        .is_some_and(|end| end <= buffer.size());
+    if !fits || (layout.width, layout.height) != size {
+        return Err(Error::InvalidDimensions);
+    }
+    Ok(staging)
+}
@@ -2,84 +2,191 @@
 
 use crate::device::OidnDevice;
 use crate::filter::{Quality, RtFilter};
+use crate::memory::AuxImages;
+use crate::texture_denoiser::TextureDenoiser;
 use crate::Error;
-use bytemuck::{cast_slice, cast_slice_mut};
 use std::sync::mpsc;
 use wgpu::util::DeviceExt;
 
-/// Supported texture format for denoising input/output.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum DenoiseTextureFormat {
-    /// RGBA 32-bit float (4 components). Alpha is preserved.
-    Rgba32Float,
-    /// RGBA 16-bit float (4 components). Alpha is preserved.
-    Rgba16Float,
-}
-
-impl DenoiseTextureFormat {
-    /// Converts from a wgpu texture format if it is supported for denoising.
-    pub fn from_wgpu(format: wgpu::TextureFormat) -> Option<Self> {
-        format.try_into().ok()
-    }
-
-    fn bytes_per_pixel(self) -> u32 {
-        match self {
-            DenoiseTextureFormat::Rgba32Float => 16,
-            DenoiseTextureFormat::Rgba16Float => 8,
-        }
-    }
-}
-
-impl TryFrom<wgpu::TextureFormat> for DenoiseTextureFormat {
-    type Error = ();
-
-    fn try_from(format: wgpu::TextureFormat) -> Result<Self, Self::Error> {
-        match format {
-            wgpu::TextureFormat::Rgba32Float => Ok(Self::Rgba32Float),
-            wgpu::TextureFormat::Rgba16Float => Ok(Self::Rgba16Float),
-            _ => Err(()),
-        }
-    }
-}
+pub use crate::texture_format::DenoiseTextureFormat;
 
 /// Options for denoising a wgpu texture.
+///
+/// Start from [`DenoiseOptions::default`] and use the `with_*` setters; new options may be added
+/// in later versions.
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct DenoiseOptions {
     /// Quality vs performance: `Fast`, `Balanced`, or `High`.
     pub quality: Quality,
-    /// `true` if the image is HDR (linear, possibly > 1.0).
-    pub hdr: bool,
-    /// `true` if the image is sRGB-encoded LDR.
-    pub srgb: bool,
+    /// `Some(true)` if the image is HDR (linear, possibly > 1.0). `None` = from the texture
+    /// format ([`DenoiseTextureFormat::is_hdr`]).
+    pub hdr: Option<bool>,
+    /// `Some(true)` if the image is sRGB-encoded LDR. `None` = from the texture format
+    /// ([`DenoiseTextureFormat::is_srgb`]).
+    pub srgb: Option<bool>,
     /// Input scale for HDR (e.g. exposure). `None` = auto.
     pub input_scale: Option<f32>,
+    /// `true` if the albedo/normal textures are noise-free (e.g. rasterized or already
+    /// prefiltered). For noisy AOVs use [`PrefilteredRtDenoiser`](crate::PrefilteredRtDenoiser).
+    pub clean_aux: bool,
 }
 
 impl Default for DenoiseOptions {
     fn default() -> Self {
         Self {
             quality: Quality::Default,
-            hdr: true,
-            srgb: false,
+            hdr: None,
+            srgb: None,
             input_scale: None,
+            clean_aux: false,
         }
     }
 }
 
+impl DenoiseOptions {
+    /// Sets the quality vs performance trade-off.
+    pub fn with_quality(mut self, quality: Quality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Overrides whether the image is HDR (default: from the texture format).
+    pub fn with_hdr(mut self, hdr: bool) -> Self {
+        self.hdr = Some(hdr);
+        self
+    }
+
+    /// Overrides whether the image is sRGB-encoded LDR (default: from the texture format).
+    pub fn with_srgb(mut self, srgb: bool) -> Self {
+        self.srgb = Some(srgb);
+        self
+    }
+
+    /// Sets the input scale for HDR (default: auto).
+    pub fn with_input_scale(mut self, input_scale: f32) -> Self {
+        self.input_scale = Some(input_scale);
+        self
+    }
+
+    /// Marks the albedo/normal textures as noise-free (see [`Self::clean_aux`]).
+    pub fn with_clean_aux(mut self, clean_aux: bool) -> Self {
+        self.clean_aux = clean_aux;
+        self
+    }
+
+    /// Configures `filter` for denoising textures of `format`.
+    pub(crate) fn apply(&self, filter: &mut RtFilter, format: DenoiseTextureFormat) {
+        filter
+            .set_hdr(self.hdr.unwrap_or(format.is_hdr()))
+            .set_srgb(self.srgb.unwrap_or(format.is_srgb()))
+            .set_clean_aux(self.clean_aux)
+            .set_quality(self.quality)
+            // NaN = automatic, also undoing a scale set by earlier options on a reused filter.
+            .set_input_scale(self.input_scale.unwrap_or(f32::NAN));
+    }
+}
+
+/// Part of a texture to read from or write to: a rectangle in one mip level and array layer.
+///
+/// The default is the whole of mip level 0, layer 0.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextureRegion {
+    /// Mip level.
+    pub mip_level: u32,
+    /// Array layer (or depth slice of a 3D texture).
+    pub layer: u32,
+    /// Top-left texel `(x, y)` in the mip level.
+    pub origin: (u32, u32),
+    /// `(width, height)`. `None` = from `origin` to the edges of the mip level.
+    pub extent: Option<(u32, u32)>,
+}
+
+impl TextureRegion {
+    /// The `width` x `height` rectangle at `(x, y)` in mip level 0, layer 0.
+    pub fn rect(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            origin: (x, y),
+            extent: Some((width, height)),
+            ..Self::default()
+        }
+    }
+
+    /// Selects the array layer.
+    pub fn with_layer(mut self, layer: u32) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    /// Selects the mip level.
+    pub fn with_mip_level(mut self, mip_level: u32) -> Self {
+        self.mip_level = mip_level;
+        self
+    }
+
+    /// `(width, height)` of the region in `texture`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDimensions`] if the mip level or layer does not exist, or the
+    /// rectangle is empty or does not fit in the mip level.
+    pub fn size_in(&self, texture: &wgpu::Texture) -> Result<(u32, u32), Error> {
+        if self.mip_level >= texture.mip_level_count() {
+            return Err(Error::InvalidDimensions);
+        }
+        let mip = texture
+            .size()
+            .mip_level_size(self.mip_level, texture.dimension());
+        if self.layer >= mip.depth_or_array_layers {
+            return Err(Error::InvalidDimensions);
+        }
+        let (x, y) = self.origin;
+        let (w, h) = self.extent.unwrap_or((
+            mip.width.saturating_sub(x),
+            mip.height.saturating_sub(y),
+        ));
+        let fits = |origin: u32, len: u32, size: u32| origin.checked_add(len).is_some_and(|end| end <= size);
+        if w == 0 || h == 0 || !fits(x, w, mip.width) || !fits(y, h, mip.height) {
+            return Err(Error::InvalidDimensions);
+        }
+        Ok((w, h))
+    }
+
+    /// Copy info for the region's origin in `texture`.
+    pub(crate) fn copy_info(self, texture: &wgpu::Texture) -> wgpu::TexelCopyTextureInfo<'_> {
+        wgpu::TexelCopyTextureInfo {
+            texture,
+            mip_level: self.mip_level,
+            origin: wgpu::Origin3d {
+                x: self.origin.0,
+                y: self.origin.1,
+                z: self.layer,
+            },
+            aspect: wgpu::TextureAspect::All,
+        }
+    }
+}
+
+/// A texture and the region of it to use.
+pub type TextureTarget<'a> = (&'a wgpu::Texture, TextureRegion);
+
 /// Denoises a wgpu texture by readback → OIDN (CPU) → upload.
 ///
 /// Input and output can be the same texture for in-place denoising, or different.
-/// Supported formats: [`DenoiseTextureFormat::Rgba32Float`], [`DenoiseTextureFormat::Rgba16Float`].
-/// Only RGB is denoised; alpha is preserved.
+/// All [`DenoiseTextureFormat`]s are supported; the filter's `hdr`/`srgb` flags follow the format
+/// unless set in `options`. Only RGB is denoised; alpha is preserved. Mip level 0 and array
+/// layer 0 are used; [`denoise_texture_regions`] selects other levels, layers or rectangles.
 ///
 /// **Texture usage:** `input` must have [`TextureUsages::COPY_SRC`](wgpu::TextureUsages::COPY_SRC);
 /// `output` must have [`TextureUsages::COPY_DST`](wgpu::TextureUsages::COPY_DST).
 ///
-/// This is a blocking call: it submits copy commands, waits for readback, runs OIDN, then uploads.
+/// This is a blocking call: it submits copy commands, waits for readback, runs OIDN, then uploads
+/// ([`denoise_texture_async`] does not block). It allocates a filter and staging buffers per
+/// call; to denoise every frame, keep a [`TextureDenoiser`] instead.
 ///
 /// # Errors
 ///
-/// Returns [`Error::InvalidDimensions`] if texture sizes or array layers are incompatible, or
+/// Returns [`Error::InvalidDimensions`] if texture sizes are incompatible, or
 /// [`Error::BufferMapFailed`] if wgpu buffer mapping fails. OIDN execution errors are returned
 /// as [`Error::OidnError`] or other [`Error`] variants.
 pub fn denoise_texture(
@@ -91,185 +198,132 @@ pub fn denoise_texture(
     format: DenoiseTextureFormat,
     options: &DenoiseOptions,
 ) -> Result<(), Error> {
-    let size = input.size();
-    if size.depth_or_array_layers != 1 {
-        return Err(Error::InvalidDimensions);
-    }
-    let out_size = output.size();
-    if out_size.width != size.width
-        || out_size.height != size.height
-        || out_size.depth_or_array_layers != 1
-    {
-        return Err(Error::InvalidDimensions);
-    }
-    let w = size.width;
-    let h = size.height;
-    let bpp = format.bytes_per_pixel();
-    let bytes_per_row = w * bpp;
-    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
-    let padded_bytes_per_row = (bytes_per_row + alignment - 1) / alignment * alignment;
-    let buffer_size = padded_bytes_per_row as u64 * h as u64;
-
-    // Create staging buffer for readback
-    let read_buffer = wgpu_device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("oidn_wgpu readback"),
-        size: buffer_size,
-        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
-        mapped_at_creation: false,
-    });
-
-    let mut encoder = wgpu_device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-    encoder.copy_texture_to_buffer(
-        wgpu::TexelCopyTextureInfo {
-            texture: input,
-            mip_level: 0,
-            origin: wgpu::Origin3d::ZERO,
-            aspect: wgpu::TextureAspect::All,
-        },
-        wgpu::TexelCopyBufferInfo {
-            buffer: &read_buffer,
-            layout: wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(padded_bytes_per_row),
-                rows_per_image: Some(h),
-            },
-        },
-        size,
-    );
-    wgpu_queue.submit(Some(encoder.finish()));
-
-    // Map and read
-    let slice = read_buffer.slice(..);
-    let (tx, rx) = mpsc::channel();
-    slice.map_async(wgpu::MapMode::Read, move |r| {
-        let _ = tx.send(r);
-    });
-    loop {
-        let _ = wgpu_device.poll(wgpu::PollType::wait_indefinitely());
-        match rx.try_recv() {
-            Ok(Ok(())) => break,
-            Ok(Err(_)) => return Err(Error::BufferMapFailed(wgpu::BufferAsyncError)),
-            Err(mpsc::TryRecvError::Disconnected) => return Err(Error::BufferMapFailed(wgpu::BufferAsyncError)),
-            Err(mpsc::TryRecvError::Empty) => std::thread::sleep(std::time::Duration::from_micros(100)),
-        }
-    }
-
-    let mapped = slice.get_mapped_range();
-    let raw: &[u8] = &mapped;
-
-    let n_pixels = (w * h) as usize;
-    let mut color_f32 = vec![0.0f32; n_pixels * 3];
-    let alpha_f32: Vec<f32> = match format {
-        DenoiseTextureFormat::Rgba32Float => {
-            let floats: &[f32] = cast_slice(raw);
-            for i in 0..n_pixels {
-                let j = i * 4;
-                color_f32[i * 3] = floats[j];
-                color_f32[i * 3 + 1] = floats[j + 1];
-                color_f32[i * 3 + 2] = floats[j + 2];
-            }
-            floats.iter().skip(3).step_by(4).copied().collect()
-        }
-        DenoiseTextureFormat::Rgba16Float => {
-            let u16s: &[u16] = cast_slice(raw);
-            for i in 0..n_pixels {
-                let j = i * 4;
-                color_f32[i * 3] = half::f16::from_bits(u16s[j]).to_f32();
-                color_f32[i * 3 + 1] = half::f16::from_bits(u16s[j + 1]).to_f32();
-                color_f32[i * 3 + 2] = half::f16::from_bits(u16s[j + 2]).to_f32();
-            }
-            u16s.iter()
-                .skip(3)
-                .step_by(4)
-                .map(|&b| half::f16::from_bits(b).to_f32())
-                .collect()
-        }
-    };
-
-    drop(mapped);
-
-    // Run OIDN
-    let mut filter = RtFilter::new(device)?;
-    filter
-        .set_dimensions(w, h)
-        .set_hdr(options.hdr)
-        .set_srgb(options.srgb)
-        .set_quality(options.quality);
-    if let Some(scale) = options.input_scale {
-        filter.set_input_scale(scale);
-    }
-    filter.execute_in_place(&mut color_f32)?;
-
-    // Convert back to RGBA (preserve alpha from original)
-    let mut output_bytes: Vec<u8> = vec![0; n_pixels * bpp as usize];
-    match format {
-        DenoiseTextureFormat::Rgba32Float => {
-            let out_f: &mut [f32] = cast_slice_mut(&mut output_bytes);
-            for i in 0..n_pixels {
-                let j = i * 4;
-                out_f[j] = color_f32[i * 3];
-                out_f[j + 1] = color_f32[i * 3 + 1];
-                out_f[j + 2] = color_f32[i * 3 + 2];
-                out_f[j + 3] = alpha_f32.get(i).copied().unwrap_or(1.0);
-            }
-        }
-        DenoiseTextureFormat::Rgba16Float => {
-            let out_u16: &mut [u16] = cast_slice_mut(&mut output_bytes);
-            for i in 0..n_pixels {
-                out_u16[i * 4] = half::f16::from_f32(color_f32[i * 3]).to_bits();
-                out_u16[i * 4 + 1] = half::f16::from_f32(color_f32[i * 3 + 1]).to_bits();
-                out_u16[i * 4 + 2] = half::f16::from_f32(color_f32[i * 3 + 2]).to_bits();
-                out_u16[i * 4 + 3] = half::f16::from_f32(alpha_f32.get(i).copied().unwrap_or(1.0)).to_bits();
-            }
-        }
-    }
-
-    // Upload: buffer must use padded row stride for WebGPU alignment.
-    let mut upload_data = vec![0u8; (padded_bytes_per_row * h) as usize];
-    for row in 0..h {
-        let src_off = (row * bytes_per_row) as usize;
-        let dst_off = (row * padded_bytes_per_row) as usize;
-        upload_data[dst_off..dst_off + bytes_per_row as usize]
-            .copy_from_slice(&output_bytes[src_off..src_off + bytes_per_row as usize]);
-    }
-    let write_buffer = wgpu_device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("oidn_wgpu upload"),
-        contents: &upload_data,
-        usage: wgpu::BufferUsages::COPY_SRC,
-    });
+    denoise_texture_with_aux(
+        device,
+        wgpu_device,
+        wgpu_queue,
+        input,
+        output,
+        format,
+        options,
+        None,
+        None,
+    )
+}
 
-    let mut enc2 = wgpu_device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-    enc2.copy_buffer_to_texture(
-        wgpu::TexelCopyBufferInfo {
-            buffer: &write_buffer,
-            layout: wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(padded_bytes_per_row),
-                rows_per_image: Some(h),
-            },
-        },
-        wgpu::TexelCopyTextureInfo {
-            texture: output,
-            mip_level: 0,
-            origin: wgpu::Origin3d::ZERO,
-            aspect: wgpu::TextureAspect::All,
-        },
-        size,
-    );
-    wgpu_queue.submit(Some(enc2.finish()));
+/// Denoises a wgpu color texture with optional albedo and normal AOV textures (same size and
+/// format as color).
+///
+/// Higher quality when albedo and normal are provided. Normals are in [-1, 1], so with a normal
+/// texture the format must be signed (`Rgba32Float` or `Rgba16Float`, see
+/// [`DenoiseTextureFormat::is_signed`]). Otherwise identical to [`denoise_texture`].
+///
+/// # Errors
+///
+/// Same as [`denoise_texture`]; also [`Error::InvalidDimensions`] if any aux texture size does not
+/// match, [`Error::MissingImage`] if `normal` is passed without `albedo`, or
+/// [`Error::UnsupportedFormat`] if `normal` is passed with an unsigned `format`.
+#[allow(clippy::too_many_arguments)]
+pub fn denoise_texture_with_aux(
+    device: &OidnDevice,
+    wgpu_device: &wgpu::Device,
+    wgpu_queue: &wgpu::Queue,
+    input: &wgpu::Texture,
+    output: &wgpu::Texture,
+    format: DenoiseTextureFormat,
+    options: &DenoiseOptions,
+    albedo: Option<&wgpu::Texture>,
+    normal: Option<&wgpu::Texture>,
+) -> Result<(), Error> {
+    let size = input.size();
+    TextureDenoiser::new(
+        device,
+        wgpu_device,
+        size.width,
+        size.height,
+        format,
+        aux_images(albedo, normal)?,
+        options,
+    )?
+    .denoise_with_aux(wgpu_queue, input, output, albedo, normal)
+}
 
-    Ok(())
+/// Denoises a region of a texture into a region of the same size of another (or the same)
+/// texture, with optional albedo and normal regions of that size. Any mip level and array layer
+/// can be used, e.g. one camera's layer of a texture array or the valid viewport of an oversized
+/// render target. Otherwise identical to [`denoise_texture_with_aux`].
+///
+/// Texels of `output` outside its region are left unchanged.
+///
+/// # Errors
+///
+/// Same as [`denoise_texture_with_aux`]; [`Error::InvalidDimensions`] if a region does not fit
+/// its texture (see [`TextureRegion::size_in`]) or the region sizes differ.
+#[allow(clippy::too_many_arguments)]
+pub fn denoise_texture_regions(
+    device: &OidnDevice,
+    wgpu_device: &wgpu::Device,
+    wgpu_queue: &wgpu::Queue,
+    input: TextureTarget<'_>,
+    output: TextureTarget<'_>,
+    format: DenoiseTextureFormat,
+    options: &DenoiseOptions,
+    albedo: Option<TextureTarget<'_>>,
+    normal: Option<TextureTarget<'_>>,
+) -> Result<(), Error> {
+    let (width, height) = input.1.size_in(input.0)?;
+    TextureDenoiser::new(
+        device,
+        wgpu_device,
+        width,
+        height,
+        format,
+        aux_images(albedo, normal)?,
+        options,
+    )?
+    .denoise_regions(wgpu_queue, input, output, albedo, normal)
 }
 
-/// Denoises a wgpu color texture with optional albedo and normal AOV textures (same size/format as color).
+/// [`denoise_texture`] without blocking the caller: readback and upload are awaited through
+/// `map_async` callbacks, with a background thread polling `wgpu_device`, and OIDN runs
+/// asynchronously. Works with any executor (winit loop, tokio, `pollster`, ...).
 ///
-/// Higher quality when albedo and normal are provided. Otherwise identical to [`denoise_texture`].
+/// To poll the device yourself or to reuse resources across frames, use
+/// [`TextureDenoiser::denoise_async`] with [`Poller::External`](crate::Poller::External).
 ///
 /// # Errors
 ///
-/// Same as [`denoise_texture`]; also [`Error::InvalidDimensions`] if any aux texture size does not match.
+/// Same as [`denoise_texture`].
+pub async fn denoise_texture_async(
+    device: &OidnDevice,
+    wgpu_device: &wgpu::Device,
+    wgpu_queue: &wgpu::Queue,
+    input: &wgpu::Texture,
+    output: &wgpu::Texture,
+    format: DenoiseTextureFormat,
+    options: &DenoiseOptions,
+) -> Result<(), Error> {
+    denoise_texture_with_aux_async(
+        device,
+        wgpu_device,
+        wgpu_queue,
+        input,
+        output,
+        format,
+        options,
+        None,
+        None,
+    )
+    .await
+}
+
+/// [`denoise_texture_with_aux`] without blocking the caller. See [`denoise_texture_async`].
+///
+/// # Errors
+///
+/// Same as [`denoise_texture_with_aux`].
 #[allow(clippy::too_many_arguments)]
-pub fn denoise_texture_with_aux(
+pub async fn denoise_texture_with_aux_async(
     device: &OidnDevice,
     wgpu_device: &wgpu::Device,
     wgpu_queue: &wgpu::Queue,
@@ -281,159 +335,183 @@ pub fn denoise_texture_with_aux(
     normal: Option<&wgpu::Texture>,
 ) -> Result<(), Error> {
     let size = input.size();
-    if size.depth_or_array_layers != 1 {
-        return Err(Error::InvalidDimensions);
+    let mut denoiser = TextureDenoiser::new(
+        device,
+        wgpu_device,
+        size.width,
+        size.height,
+        format,
+        aux_images(albedo, normal)?,
+        options,
+    )?;
+    denoiser
+        .denoise_with_aux_async(wgpu_queue, input, output, albedo, normal)
+        .await
+}
+
+/// The aux set of optional albedo/normal inputs.
+pub(crate) fn aux_images<T>(albedo: Option<T>, normal: Option<T>) -> Result<AuxImages, Error> {
+    match (albedo, normal) {
+        (None, None) => Ok(AuxImages::None),
+        (Some(_), None) => Ok(AuxImages::Albedo),
+        (Some(_), Some(_)) => Ok(AuxImages::AlbedoNormal),
+        (None, Some(_)) => Err(Error::MissingImage("albedo".to_owned())),
     }
-    let out_size = output.size();
-    if out_size.width != size.width
-        || out_size.height != size.height
-        || out_size.depth_or_array_layers != 1
-    {
-        return Err(Error::InvalidDimensions);
+}
+
+/// Layout of one image in a staging buffer: texels packed per row, rows `row_stride` bytes apart
+/// (by default padded to [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`], as texture copies require).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct StagingLayout {
+    pub width: u32,
+    pub height: u32,
+    pub format: DenoiseTextureFormat,
+    pub row_stride: u32,
+}
+
+impl StagingLayout {
+    pub(crate) fn new(width: u32, height: u32, format: DenoiseTextureFormat) -> Self {
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let bytes_per_row = width * format.bytes_per_pixel();
+        Self {
+            width,
+            height,
+            format,
+            row_stride: (bytes_per_row + alignment - 1) / alignment * alignment,
+        }
     }
-    if let Some(tex) = albedo {
-        let s = tex.size();
-        if s.width != size.width || s.height != size.height || s.depth_or_array_layers != 1 {
-            return Err(Error::InvalidDimensions);
+
+    /// The same image with rows `row_stride` bytes apart (at least [`Self::bytes_per_row`]).
+    pub(crate) fn with_row_stride(mut self, row_stride: u32) -> Self {
+        self.row_stride = row_stride;
+        self
+    }
+
+    /// Bytes of the texels of one row.
+    pub(crate) fn bytes_per_row(self) -> u32 {
+        self.width * self.format.bytes_per_pixel()
+    }
+
+    /// Bytes from the start of the first row to the end of the last one's texels.
+    pub(crate) fn span(self) -> u64 {
+        u64::from(self.row_stride) * u64::from(self.height - 1) + u64::from(self.bytes_per_row())
+    }
+
+    /// Bytes of the image in the buffer (a multiple of the row stride, so images can be placed
+    /// back to back).
+    pub(crate) fn size(self) -> u64 {
+        u64::from(self.row_stride) * u64::from(self.height)
+    }
+
+    /// Number of pixels.
+    pub(crate) fn pixels(self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    pub(crate) fn extent(self) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: 1,
         }
     }
-    if let Some(tex) = normal {
-        let s = tex.size();
-        if s.width != size.width || s.height != size.height || s.depth_or_array_layers != 1 {
-            return Err(Error::InvalidDimensions);
+
+    /// Copy info for the image at `offset` in `buffer`.
+    pub(crate) fn buffer_copy(self, buffer: &wgpu::Buffer, offset: u64) -> wgpu::TexelCopyBufferInfo<'_> {
+        wgpu::TexelCopyBufferInfo {
+            buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset,
+                bytes_per_row: Some(self.row_stride),
+                rows_per_image: Some(self.height),
+            },
         }
     }
-    let w = size.width;
-    let h = size.height;
-
-    let (mut color_rgb, alpha) = read_texture_to_rgba_f32(wgpu_device, wgpu_queue, input, format)?;
-    let albedo_rgb = albedo
-        .map(|t| read_texture_to_rgba_f32(wgpu_device, wgpu_queue, t, format).map(|(rgb, _)| rgb))
-        .transpose()?;
-    let normal_rgb = normal
-        .map(|t| read_texture_to_rgba_f32(wgpu_device, wgpu_queue, t, format).map(|(rgb, _)| rgb))
-        .transpose()?;
-
-    let mut filter = RtFilter::new(device)?;
-    filter
-        .set_dimensions(w, h)
-        .set_hdr(options.hdr)
-        .set_srgb(options.srgb)
-        .set_quality(options.quality);
-    if let Some(scale) = options.input_scale {
-        filter.set_input_scale(scale);
+
+    /// Decodes the padded rows in `src` to RGB and alpha.
+    pub(crate) fn decode(self, src: &[u8], rgb: &mut [f32], alpha: &mut [f32]) {
+        let row_pixels = self.width as usize;
+        let rows = src.chunks(self.row_stride as usize).take(self.height as usize);
+        for (y, src) in rows.enumerate() {
+            let pixels = y * row_pixels..(y + 1) * row_pixels;
+            self.format.decode(
+                &src[..self.bytes_per_row() as usize],
+                &mut rgb[pixels.start * 3..pixels.end * 3],
+                &mut alpha[pixels],
+            );
+        }
     }
-    filter.execute_in_place_with_aux(
-        &mut color_rgb,
-        albedo_rgb.as_deref(),
-        normal_rgb.as_deref(),
-    )?;
 
-    upload_rgba_to_texture(
-        wgpu_device,
-        wgpu_queue,
-        output,
-        format,
-        w,
-        h,
-        &color_rgb,
-        &alpha,
-    )
+    /// Encodes RGB and alpha into padded rows in `dst`.
+    pub(crate) fn encode(self, rgb: &[f32], alpha: &[f32], dst: &mut [u8]) {
+        let row_pixels = self.width as usize;
+        let rows = dst.chunks_mut(self.row_stride as usize).take(self.height as usize);
+        for (y, dst) in rows.enumerate() {
+            let pixels = y * row_pixels..(y + 1) * row_pixels;
+            self.format.encode(
+                &rgb[pixels.start * 3..pixels.end * 3],
+                &alpha[pixels],
+                &mut dst[..self.bytes_per_row() as usize],
+            );
+        }
+    }
+}
+
+/// Maps `slice` with `mode` and blocks until the mapping has completed.
+pub(crate) fn map_blocking(
+    wgpu_device: &wgpu::Device,
+    slice: wgpu::BufferSlice<'_>,
+    mode: wgpu::MapMode,
+) -> Result<(), Error> {
+    let (tx, rx) = mpsc::channel();
+    slice.map_async(mode, move |r| {
+        let _ = tx.send(r);
+    });
+    // Waiting for the queue to finish runs the callback, so this normally polls once.
+    loop {
+        let _ = wgpu_device.poll(wgpu::PollType::wait_indefinitely());
+        match rx.try_recv() {
+            Ok(result) => return result.map_err(Error::BufferMapFailed),
+            Err(mpsc::TryRecvError::Disconnected) => return Err(Error::BufferMapFailed(wgpu::BufferAsyncError)),
+            Err(mpsc::TryRecvError::Empty) => {}
+        }
+    }
 }
 
 /// Reads a wgpu texture to CPU as (RGB f32, alpha f32). Blocking.
-fn read_texture_to_rgba_f32(
+pub(crate) fn read_texture_to_rgba_f32(
     wgpu_device: &wgpu::Device,
     wgpu_queue: &wgpu::Queue,
     texture: &wgpu::Texture,
     format: DenoiseTextureFormat,
 ) -> Result<(Vec<f32>, Vec<f32>), Error> {
     let size = texture.size();
-    let w = size.width;
-    let h = size.height;
-    let bpp = format.bytes_per_pixel();
-    let bytes_per_row = w * bpp;
-    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
-    let padded_bytes_per_row = (bytes_per_row + alignment - 1) / alignment * alignment;
-    let buffer_size = padded_bytes_per_row as u64 * h as u64;
-    let n_pixels = (w * h) as usize;
-
+    let layout = StagingLayout::new(size.width, size.height, format);
     let read_buffer = wgpu_device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("oidn_wgpu readback"),
-        size: buffer_size,
+        size: layout.size(),
         usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation: false,
     });
     let mut encoder = wgpu_device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
     encoder.copy_texture_to_buffer(
-        wgpu::TexelCopyTextureInfo {
-            texture,
-            mip_level: 0,
-            origin: wgpu::Origin3d::ZERO,
-            aspect: wgpu::TextureAspect::All,
-        },
-        wgpu::TexelCopyBufferInfo {
-            buffer: &read_buffer,
-            layout: wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(padded_bytes_per_row),
-                rows_per_image: Some(h),
-            },
-        },
-        size,
+        TextureRegion::default().copy_info(texture),
+        layout.buffer_copy(&read_buffer, 0),
+        layout.extent(),
     );
     wgpu_queue.submit(Some(encoder.finish()));
 
     let slice = read_buffer.slice(..);
-    let (tx, rx) = mpsc::channel();
-    slice.map_async(wgpu::MapMode::Read, move |r| {
-        let _ = tx.send(r);
-    });
-    loop {
-        let _ = wgpu_device.poll(wgpu::PollType::wait_indefinitely());
-        match rx.try_recv() {
-            Ok(Ok(())) => break,
-            Ok(Err(_)) => return Err(Error::BufferMapFailed(wgpu::BufferAsyncError)),
-            Err(mpsc::TryRecvError::Disconnected) => return Err(Error::BufferMapFailed(wgpu::BufferAsyncError)),
-            Err(mpsc::TryRecvError::Empty) => std::thread::sleep(std::time::Duration::from_micros(100)),
-        }
-    }
-    let mapped = slice.get_mapped_range();
-    let raw: &[u8] = &mapped;
-    let mut rgb = vec![0.0f32; n_pixels * 3];
-    let alpha: Vec<f32> = match format {
-        DenoiseTextureFormat::Rgba32Float => {
-            let floats: &[f32] = cast_slice(raw);
-            for i in 0..n_pixels {
-                let j = i * 4;
-                rgb[i * 3] = floats[j];
-                rgb[i * 3 + 1] = floats[j + 1];
-                rgb[i * 3 + 2] = floats[j + 2];
-            }
-            floats.iter().skip(3).step_by(4).copied().collect()
-        }
-        DenoiseTextureFormat::Rgba16Float => {
-            let u16s: &[u16] = cast_slice(raw);
-            for i in 0..n_pixels {
-                let j = i * 4;
-                rgb[i * 3] = half::f16::from_bits(u16s[j]).to_f32();
-                rgb[i * 3 + 1] = half::f16::from_bits(u16s[j + 1]).to_f32();
-                rgb[i * 3 + 2] = half::f16::from_bits(u16s[j + 2]).to_f32();
-            }
-            u16s.iter()
-                .skip(3)
-                .step_by(4)
-                .map(|&b| half::f16::from_bits(b).to_f32())
-                .collect()
-        }
-    };
-    drop(mapped);
+    map_blocking(wgpu_device, slice, wgpu::MapMode::Read)?;
+    let mut rgb = vec![0.0f32; layout.pixels() * 3];
+    let mut alpha = vec![0.0f32; layout.pixels()];
+    layout.decode(&slice.get_mapped_range(), &mut rgb, &mut alpha);
     Ok((rgb, alpha))
 }
 
 /// Uploads denoised RGB + preserved alpha to a wgpu texture (padded row alignment).
 #[allow(clippy::too_many_arguments)]
-fn upload_rgba_to_texture(
+pub(crate) fn upload_rgba_to_texture(
     wgpu_device: &wgpu::Device,
     wgpu_queue: &wgpu::Queue,
     output: &wgpu::Texture,
@@ -443,41 +521,9 @@ fn upload_rgba_to_texture(
     color_f32: &[f32],
     alpha_f32: &[f32],
 ) -> Result<(), Error> {
-    let n_pixels = (w * h) as usize;
-    let bpp = format.bytes_per_pixel();
-    let bytes_per_row = w * bpp;
-    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
-    let padded_bytes_per_row = (bytes_per_row + alignment - 1) / alignment * alignment;
-    let mut output_bytes = vec![0u8; n_pixels * bpp as usize];
-    match format {
-        DenoiseTextureFormat::Rgba32Float => {
-            let out_f: &mut [f32] = cast_slice_mut(&mut output_bytes);
-            for i in 0..n_pixels {
-                let j = i * 4;
-                out_f[j] = color_f32[i * 3];
-                out_f[j + 1] = color_f32[i * 3 + 1];
-                out_f[j + 2] = color_f32[i * 3 + 2];
-                out_f[j + 3] = alpha_f32.get(i).copied().unwrap_or(1.0);
-            }
-        }
-        DenoiseTextureFormat::Rgba16Float => {
-            let out_u16: &mut [u16] = cast_slice_mut(&mut output_bytes);
-            for i in 0..n_pixels {
-                out_u16[i * 4] = half::f16::from_f32(color_f32[i * 3]).to_bits();
-                out_u16[i * 4 + 1] = half::f16::from_f32(color_f32[i * 3 + 1]).to_bits();
-                out_u16[i * 4 + 2] = half::f16::from_f32(color_f32[i * 3 + 2]).to_bits();
-                out_u16[i * 4 + 3] = half::f16::from_f32(alpha_f32.get(i).copied().unwrap_or(1.0)).to_bits();
-            }
-        }
-    }
-    let mut upload_data = vec![0u8; (padded_bytes_per_row * h) as usize];
-    for row in 0..h {
-        let src_off = (row * bytes_per_row) as usize;
-        let dst_off = (row * padded_bytes_per_row) as usize;
-        upload_data[dst_off..dst_off + bytes_per_row as usize]
-            .copy_from_slice(&output_bytes[src_off..src_off + bytes_per_row as usize]);
-    }
-    let size = output.size();
+    let layout = StagingLayout::new(w, h, format);
+    let mut upload_data = vec![0u8; layout.size() as usize];
+    layout.encode(color_f32, alpha_f32, &mut upload_data);
     let write_buffer = wgpu_device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: Some("oidn_wgpu upload"),
         contents: &upload_data,
@@ -485,21 +531,9 @@ fn upload_rgba_to_texture(
     });
     let mut enc = wgpu_device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
     enc.copy_buffer_to_texture(
-        wgpu::TexelCopyBufferInfo {
-            buffer: &write_buffer,
-            layout: wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(padded_bytes_per_row),
-                rows_per_image: Some(h),
-            },
-        },
-        wgpu::TexelCopyTextureInfo {
-            texture: output,
-            mip_level: 0,
-            origin: wgpu::Origin3d::ZERO,
-            aspect: wgpu::TextureAspect::All,
-        },
-        size,
+        layout.buffer_copy(&write_buffer, 0),
+        TextureRegion::default().copy_info(output),
+        layout.extent(),
     );
     wgpu_queue.submit(Some(enc.finish()));
     Ok(())